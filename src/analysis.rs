@@ -0,0 +1,273 @@
+/*
+This is where graphs are searched for points worth pointing out: the roots, maxima and
+minima of a variable as a function of `x`, and the places where two variables meet.
+
+The range is split into pieces, and pieces that interval evaluation shows can't hold
+a zero are dropped. The pieces left over are split further, then searched: where the
+sign changes, Brent's method closes in on the zero, and where it doesn't, Newton's
+method looks for a zero the graph only touches (like `x^2` at `0`). A sign change
+where the value blows up instead of shrinking is a pole, not a zero.
+
+Extrema are zeros of the derivative, kept when the values on either side agree.
+*/
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use num_complex::Complex64;
+
+use crate::expr::{f, Context, Expr, Func};
+use crate::interval::Interval;
+
+/// The number of evenly spaced pieces the search starts with.
+const INITIAL_PIECES: usize = 64;
+/// How many times a piece that might hold a zero can be split in half.
+const MAX_DEPTH: u32 = 6;
+/// How close (relative to the range) the ends of a bracket have to get.
+const X_TOLERANCE: f64 = 1e-13;
+/// How small a value has to be to count as a zero the graph touches.
+const TOUCH: f64 = 1e-10;
+/// How close (relative to the range) two zeros can be before they count as one.
+const SAME: f64 = 1e-9;
+/// The most steps Brent's and Newton's methods take.
+const MAX_STEPS: usize = 100;
+
+/// What a point on a graph is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Root,
+    Maximum,
+    Minimum,
+    Intersection,
+}
+
+/// A point on a graph for the renderer to point out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub kind: Kind,
+    pub x: f64,
+    pub y: f64,
+    /// Like `maximum of f (1.5708, 1)`.
+    pub label: String,
+}
+impl Marker {
+    fn new(kind: Kind, of: &str, x: f64, y: f64) -> Self {
+        let what = match kind {
+            Kind::Root => "root",
+            Kind::Maximum => "maximum",
+            Kind::Minimum => "minimum",
+            Kind::Intersection => "intersection",
+        };
+        Self { kind, x, y, label: format!("{what} of {of} ({}, {})", short(x), short(y)) }
+    }
+}
+
+/// Rounds to 4 decimal places for labels.
+fn short(v: f64) -> f64 {
+    // * adding 0 turns -0 into 0
+    (v * 1e4).round() / 1e4 + 0.0
+}
+
+/// Finds where `var` is 0 for `x` in `range`. Every item of a list is searched.
+pub fn roots(ctx: &Context, var: &str, range: RangeInclusive<f64>) -> Result<Vec<Marker>, String> {
+    let (e, funcs) = simplify(ctx, var)?;
+    let mut markers = Vec::new();
+    for item in e.items() {
+        markers.extend(roots_of(&item, &funcs, range.clone()).into_iter().map(|x| Marker::new(Kind::Root, var, x, 0.0)));
+    }
+    Ok(markers)
+}
+
+/// Finds the maxima and minima of `var` for `x` inside `range`. Every item of a list is searched.
+pub fn extrema(ctx: &Context, var: &str, range: RangeInclusive<f64>) -> Result<Vec<Marker>, String> {
+    let (e, funcs) = simplify(ctx, var)?;
+    let mut markers = Vec::new();
+    for item in e.items() {
+        let found = extrema_of(&item, &funcs, range.clone()).ok_or_else(|| format!("ERROR: {var} can't be differentiated!"))?;
+        markers.extend(found.into_iter().map(|(x, kind)| Marker::new(kind, var, x, real(&item, &funcs, x).unwrap())));
+    }
+    Ok(markers)
+}
+
+/// Finds where `a` and `b` are equal for `x` in `range`. Lists are compared item by item with every item of the other.
+pub fn intersections(ctx: &Context, a: &str, b: &str, range: RangeInclusive<f64>) -> Result<Vec<Marker>, String> {
+    let (ea, mut funcs) = simplify(ctx, a)?;
+    let (eb, fb) = simplify(ctx, b)?;
+    funcs.extend(fb);
+
+    let mut markers = Vec::new();
+    for ia in ea.items() {
+        for ib in eb.items() {
+            let diff = Expr::Add(vec![ia.clone(), Expr::Mul(vec![*f::num(-1.0), ib])]);
+            for x in roots_of(&diff, &funcs, range.clone()) {
+                let Some(y) = real(&ia, &funcs, x) else { continue };
+                markers.push(Marker::new(Kind::Intersection, &format!("{a} and {b}"), x, y));
+            }
+        }
+    }
+    Ok(markers)
+}
+
+fn simplify(ctx: &Context, var: &str) -> Result<(Expr, HashMap<String, Func>), String> {
+    if ctx.get_var(var).is_none() {
+        return Err(format!("ERROR: variable {var} is not defined!"));
+    }
+    Ok(ctx.simplify_for_var(var))
+}
+
+/// The real zeros of `e` as a function of `x` in `range`, in order.
+pub fn roots_of(e: &Expr, funcs: &HashMap<String, Func>, range: RangeInclusive<f64>) -> Vec<f64> {
+    let slope = e.derivative("x");
+    zeros(e, slope.as_ref(), funcs, range)
+}
+
+/// The maxima and minima of `e` as a function of `x` strictly inside `range`, in order.
+/// `None` if `e` can't be differentiated.
+pub fn extrema_of(e: &Expr, funcs: &HashMap<String, Func>, range: RangeInclusive<f64>) -> Option<Vec<(f64, Kind)>> {
+    let slope = e.derivative("x")?;
+    let curvature = slope.derivative("x");
+    let h = (range.end() - range.start()) / (INITIAL_PIECES << MAX_DEPTH) as f64;
+
+    let mut found = Vec::new();
+    for x in zeros(&slope, curvature.as_ref(), funcs, range.clone()) {
+        if (x - range.start() < h) | (range.end() - x < h) { continue }
+        let (Some(l), Some(m), Some(r)) = (real(e, funcs, x - h), real(e, funcs, x), real(e, funcs, x + h)) else { continue };
+        // Flat parts and inflection points like `x^3` at `0` aren't extrema.
+        if (m >= l) & (m >= r) & ((m > l) | (m > r)) {
+            found.push((x, Kind::Maximum));
+        } else if (m <= l) & (m <= r) & ((m < l) | (m < r)) {
+            found.push((x, Kind::Minimum));
+        }
+    }
+    Some(found)
+}
+
+/// The value of `e` at `x`, if it's real. Complex versions of real functions can leave
+/// rounding noise in the imaginary part, so that's ignored.
+fn real(e: &Expr, funcs: &HashMap<String, Func>, x: f64) -> Option<f64> {
+    let v = e.evaluate(&HashMap::from([("x".to_string(), Complex64::from(x))]), funcs);
+    (v.re.is_finite() & (v.im.abs() <= 1e-12 * (1.0 + v.re.abs()))).then_some(v.re)
+}
+
+/// Finds the zeros of `e`, using `slope` for Newton's method if there is one.
+fn zeros(e: &Expr, slope: Option<&Expr>, funcs: &HashMap<String, Func>, range: RangeInclusive<f64>) -> Vec<f64> {
+    let (start, end) = (*range.start(), *range.end());
+    let step = (end - start) / INITIAL_PIECES as f64;
+    let mut found = Vec::new();
+    for i in 0..INITIAL_PIECES {
+        let lo = start + step * i as f64;
+        let hi = if i + 1 == INITIAL_PIECES { end } else { lo + step };
+        search(e, slope, funcs, lo, hi, 0, &mut found);
+    }
+
+    // Zeros on the edge between two pieces are found from both sides.
+    found.sort_by(f64::total_cmp);
+    found.dedup_by(|b, a| *b - *a <= SAME * (end - start));
+    found
+}
+
+/// Looks for zeros of `e` in `lo..=hi`, splitting the piece until it's small enough.
+fn search(e: &Expr, slope: Option<&Expr>, funcs: &HashMap<String, Func>, lo: f64, hi: f64, depth: u32, found: &mut Vec<f64>) {
+    let vars = HashMap::from([("x".to_string(), Interval::new(lo, hi))]);
+    if !e.evaluate_interval(&vars, funcs).contains(0.0) { return }
+
+    if depth < MAX_DEPTH {
+        let mid = (lo + hi) / 2.0;
+        search(e, slope, funcs, lo, mid, depth + 1, found);
+        search(e, slope, funcs, mid, hi, depth + 1, found);
+        return;
+    }
+
+    let f = |x: f64| real(e, funcs, x);
+    match (f(lo), f(hi)) {
+        (Some(a), Some(b)) if (a == 0.0) | (b == 0.0) | ((a < 0.0) != (b < 0.0)) => {
+            let Some(x) = brent(&f, lo, hi, a, b) else { return };
+            // Across a pole the sign changes too, but the value grows instead of shrinking.
+            if f(x).is_some_and(|v| v.abs() <= a.abs().max(b.abs())) {
+                found.push(x);
+            }
+        },
+        _ => {
+            let Some(slope) = slope else { return };
+            if let Some(x) = newton(&f, &|x| real(slope, funcs, x), lo, hi) {
+                found.push(x);
+            }
+        },
+    }
+}
+
+/// Brent's method on the bracket `a..=b`, where `f` is `fa` and `fb`.
+fn brent(f: &dyn Fn(f64) -> Option<f64>, a: f64, b: f64, fa: f64, fb: f64) -> Option<f64> {
+    let (mut a, mut b, mut fa, mut fb) = (a, b, fa, fb);
+    if fa == 0.0 { return Some(a) }
+    if fb == 0.0 { return Some(b) }
+    let width = (b - a).abs();
+
+    // `b` is the best guess so far, `c` is on the other side of the zero from it.
+    let (mut c, mut fc) = (a, fa);
+    let mut d = b - a;
+    let mut last = d;
+    for _ in 0..MAX_STEPS {
+        if (fb < 0.0) == (fc < 0.0) {
+            (c, fc) = (a, fa);
+            d = b - a;
+            last = d;
+        }
+        if fc.abs() < fb.abs() {
+            (a, fa) = (b, fb);
+            (b, fb) = (c, fc);
+            (c, fc) = (a, fa);
+        }
+
+        let tol = X_TOLERANCE * width + f64::EPSILON * b.abs();
+        let m = (c - b) / 2.0;
+        if (m.abs() <= tol) | (fb == 0.0) { return Some(b) }
+
+        if (last.abs() >= tol) & (fa.abs() > fb.abs()) {
+            // Secant or inverse quadratic interpolation, if it lands somewhere sensible.
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0 * m * s, 1.0 - s)
+            } else {
+                let (q, r) = (fa / fc, fb / fc);
+                (s * (2.0 * m * q * (q - r) - (b - a) * (r - 1.0)), (q - 1.0) * (r - 1.0) * (s - 1.0))
+            };
+            if p > 0.0 { q = -q } else { p = -p }
+            if (2.0 * p < 3.0 * m * q - (tol * q).abs()) & (p < (last * q / 2.0).abs()) {
+                last = d;
+                d = p / q;
+            } else {
+                d = m;
+                last = m;
+            }
+        } else {
+            d = m;
+            last = m;
+        }
+
+        (a, fa) = (b, fb);
+        b += if d.abs() > tol { d } else { tol.copysign(m) };
+        fb = f(b)?;
+    }
+    Some(b)
+}
+
+/// Newton's method from the middle of `lo..=hi`, for zeros where the sign doesn't change.
+/// Gives up if it leaves the piece.
+fn newton(f: &dyn Fn(f64) -> Option<f64>, slope: &dyn Fn(f64) -> Option<f64>, lo: f64, hi: f64) -> Option<f64> {
+    let mut x = (lo + hi) / 2.0;
+    let scale = f(lo)?.abs().max(f(hi)?.abs()).max(1.0);
+    for _ in 0..MAX_STEPS {
+        let v = f(x)?;
+        if v == 0.0 { return Some(x) }
+        let s = slope(x)?;
+        if s == 0.0 { break }
+        let next = x - v / s;
+        if !(lo..=hi).contains(&next) { return None }
+        // * zeros that are only touched are approached slowly, so this waits for the steps to run out
+        let done = (next - x).abs() <= f64::EPSILON * x.abs().max(hi - lo);
+        x = next;
+        if done { break }
+    }
+    (f(x)?.abs() <= TOUCH * scale).then_some(x)
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use num_complex::Complex64;
+
+use crate::analysis::{self, Kind};
+use crate::expr::{f::*, Context, Expr};
+use crate::test_util::var;
+
+
+fn at(e: &Expr, x: f64) -> Complex64 {
+    e.evaluate(&HashMap::from([("x".to_string(), Complex64::from(x))]), &HashMap::new())
+}
+
+fn close(a: &[f64], b: &[f64]) -> bool {
+    (a.len() == b.len()) & a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-9)
+}
+
+
+#[test]
+fn derivatives_match_differences() {
+    let x = var("x");
+    let es = vec![
+        mul(num(3.0), pow(x.clone(), num(4.0))),
+        mul(sin(x.clone()), exp_of(x.clone())),
+        div(ln(x.clone()), add(x.clone(), num(2.0))),
+        pow(x.clone(), x.clone()),
+        log(num(2.0), x.clone()),
+        root(num(3.0), x.clone()),
+        sqrt(add(pow(x.clone(), num(2.0)), num(1.0))),
+        tan(x.clone()),
+        tanh(x.clone()),
+        asin(div(x.clone(), num(2.0))),
+        acos(div(x.clone(), num(2.0))),
+        atan(x.clone()),
+        asinh(x.clone()),
+        acosh(add(x.clone(), num(2.0))),
+        atanh(div(x.clone(), num(2.0))),
+        cbrt(add(x.clone(), num(3.0))),
+        abs(sub(x.clone(), num(5.0))),
+        polar(x.clone(), x.clone()),
+        sum("k".to_string(), num(1.0), num(100.0), div(pow(x.clone(), var("k")), var("k"))),
+        prod("k".to_string(), num(1.0), num(3.0), add(x.clone(), var("k"))),
+        gamma(x.clone()),
+        lgamma(add(x.clone(), num(3.0))),
+        factorial(mul(x.clone(), num(2.0))),
+        beta(x.clone(), mul(x.clone(), x.clone())),
+        erf(x.clone()),
+        erfc(mul(x.clone(), num(3.0))),
+        besselj(num(2.0), mul(x.clone(), num(5.0))),
+        bessely(num(0.0), x.clone()),
+    ];
+    for e in es {
+        let d = e.derivative("x").unwrap();
+        for x in [0.3, 0.7, 1.1] {
+            let h = 1e-6;
+            let numeric = (at(&e, x + h) - at(&e, x - h)) / (2.0 * h);
+            let exact = at(&d, x);
+            assert!((numeric - exact).norm() < 1e-5 * (1.0 + exact.norm()), "{e:?} at {x}: {exact} but about {numeric}");
+        }
+    }
+}
+
+/// `e^a`, written the way the parser does.
+fn exp_of(a: Box<Expr>) -> Box<Expr> {
+    pow(num(std::f64::consts::E), a)
+}
+
+#[test]
+fn some_things_cant_be_differentiated() {
+    let x = var("x");
+    assert!(builtin(crate::expr::Builtin::Max, vec![x.clone(), num(0.0)]).derivative("x").is_none());
+    assert!(sum("k".to_string(), num(1.0), x.clone(), var("k")).derivative("x").is_none());
+    assert!(func("f".to_string(), vec![x.clone()]).derivative("x").is_none());
+    assert!(zeta(x.clone()).derivative("x").is_none());
+    assert!(floor(x.clone()).derivative("x").is_none());
+    assert!(besselj(x.clone(), num(1.0)).derivative("x").is_none());
+    // `x` is bound inside the sum, so this doesn't depend on it.
+    assert!(sum("x".to_string(), num(1.0), num(3.0), var("x")).derivative("x").unwrap().is_zero());
+}
+
+#[test]
+fn roots() {
+    let x = var("x");
+    let funcs = HashMap::new();
+    assert!(close(&analysis::roots_of(&sin(x.clone()), &funcs, -4.0..=4.0), &[-PI, 0.0, PI]));
+    let two = 2.0f64.sqrt();
+    assert!(close(&analysis::roots_of(&sub(pow(x.clone(), num(2.0)), num(2.0)), &funcs, -3.0..=3.0), &[-two, two]));
+    // Zeros the graph only touches.
+    assert!(close(&analysis::roots_of(&pow(sub(x.clone(), num(1.0)), num(2.0)), &funcs, -3.0..=3.0), &[1.0]));
+    // Poles change sign too.
+    assert!(analysis::roots_of(&div(num(1.0), x.clone()), &funcs, -3.0..=3.0).is_empty());
+    assert!(close(&analysis::roots_of(&tan(x.clone()), &funcs, -4.0..=4.0), &[-PI, 0.0, PI]));
+    // Only real values count.
+    assert!(analysis::roots_of(&add(sqrt(x.clone()), num(1.0)), &funcs, -3.0..=3.0).is_empty());
+}
+
+#[test]
+fn extrema() {
+    let x = var("x");
+    let funcs = HashMap::new();
+    let found = analysis::extrema_of(&sin(x.clone()), &funcs, -4.0..=4.0).unwrap();
+    assert_eq!(found.iter().map(|p| p.1).collect::<Vec<Kind>>(), vec![Kind::Minimum, Kind::Maximum]);
+    assert!(close(&found.iter().map(|p| p.0).collect::<Vec<f64>>(), &[-PI / 2.0, PI / 2.0]));
+
+    assert!(analysis::extrema_of(&pow(x.clone(), num(3.0)), &funcs, -3.0..=3.0).unwrap().is_empty());
+    assert!(analysis::extrema_of(&div(num(1.0), pow(x.clone(), num(2.0))), &funcs, -3.0..=3.0).unwrap().is_empty());
+    // A corner is an extremum even though the derivative isn't 0 there.
+    let found = analysis::extrema_of(&abs(sub(x.clone(), num(0.5))), &funcs, -3.0..=3.0).unwrap();
+    assert_eq!(found.len(), 1);
+    assert!((found[0].0 - 0.5).abs() < 1e-9 && found[0].1 == Kind::Minimum);
+}
+
+#[test]
+fn markers_from_context() {
+    let mut ctx = Context::new();
+    ctx.def_var("f", *cos(var("x"))).unwrap();
+    ctx.def_var("g", *var("x")).unwrap();
+
+    let found = analysis::intersections(&ctx, "f", "g", -3.0..=3.0).unwrap();
+    assert_eq!(found.len(), 1);
+    assert!((found[0].x - 0.7390851332151607).abs() < 1e-9 && (found[0].y - found[0].x).abs() < 1e-9);
+    assert_eq!(found[0].label, "intersection of f and g (0.7391, 0.7391)");
+
+    let found = analysis::extrema(&ctx, "f", -1.0..=7.0).unwrap();
+    assert_eq!(found.iter().map(|m| m.label.as_str()).collect::<Vec<&str>>(), vec!["maximum of f (0, 1)", "minimum of f (3.1416, -1)", "maximum of f (6.2832, 1)"]);
+    assert_eq!(analysis::roots(&ctx, "g", -1.0..=1.0).unwrap()[0].kind, Kind::Root);
+    assert!(analysis::roots(&ctx, "h", -1.0..=1.0).is_err());
+}
@@ -0,0 +1,690 @@
+/*
+This is where numbers with more precision than `f64` are worked out, for expressions
+that floats aren't good enough for (see `number.rs`).
+
+A `BigFloat` is a big integer times a power of 2, rounded to a set number of bits
+after every operation. Its exponent can't overflow, so `2^5000` is fine. Functions
+like `exp` and `sin` are worked out from their series with some extra bits, after
+shrinking the input so the series converge quickly. Infinities and NaN aren't worth
+the trouble, so anything involving them is worked out with `f64` instead.
+
+`BigComplex` builds complex numbers on top, with the same principal branches as
+`Complex64`.
+*/
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::f64::consts::LN_2;
+
+use num::{BigInt, BigRational, BigUint, Integer, One, Signed, ToPrimitive, Zero};
+use num_complex::Complex64;
+
+/// Extra bits functions work with, so rounding errors don't reach the result.
+const GUARD: u32 = 32;
+/// How many times `exp` halves its input before the series.
+const EXP_HALVINGS: u32 = 16;
+/// Inputs to `sin` and `cos` bigger than `2^this` are left to `f64`, there'd be no digits left anyway.
+const MAX_TRIG_LOG2: i64 = 4096;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    /// `mantissa * 2^exponent`.
+    Finite(BigInt, i64),
+    /// An infinity or NaN.
+    Special(f64),
+}
+
+/// A binary floating point number with a set number of bits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BigFloat {
+    value: Value,
+    /// How many bits the mantissa is rounded to.
+    precision: u32,
+}
+impl BigFloat {
+    pub fn zero(precision: u32) -> Self {
+        Self { value: Value::Finite(BigInt::zero(), 0), precision }
+    }
+    pub fn one(precision: u32) -> Self {
+        Self::from_int(1, precision)
+    }
+    pub fn from_int(n: i64, precision: u32) -> Self {
+        Self::finite(BigInt::from(n), 0, precision)
+    }
+
+    /// The exact value of `v`, rounded to `precision` bits.
+    pub fn from_f64(v: f64, precision: u32) -> Self {
+        if !v.is_finite() { return Self { value: Value::Special(v), precision } }
+        let bits = v.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = (bits & ((1 << 52) - 1)) as i64;
+        // * subnormals don't have the leading 1
+        let (m, e) = if exponent == 0 { (fraction, -1074) } else { (fraction | (1 << 52), exponent - 1075) };
+        Self::finite(BigInt::from(if v.is_sign_negative() { -m } else { m }), e, precision)
+    }
+
+    pub fn from_ratio(r: &BigRational, precision: u32) -> Self {
+        // Enough bits that the quotient has `precision` of them and a couple to round with.
+        let shift = (precision as i64 + 2 + r.denom().bits() as i64 - r.numer().bits() as i64).max(0);
+        Self::finite((r.numer() << shift as usize) / r.denom(), -shift, precision)
+    }
+
+    /// The closest `f64`, give or take rounding twice.
+    pub fn to_f64(&self) -> f64 {
+        match &self.value {
+            Value::Special(v) => *v,
+            Value::Finite(m, e) => {
+                // Only the top 64 bits can make it into an f64.
+                let drop = (m.bits() as i64 - 64).max(0);
+                let top = (m.magnitude() >> drop as usize).to_u64().unwrap() as f64;
+                let v = ldexp(top, e + drop);
+                if m.is_negative() { -v } else { v }
+            },
+        }
+    }
+
+    /// Rounds `m * 2^e` to `precision` bits, to nearest with ties away from 0.
+    fn finite(m: BigInt, e: i64, precision: u32) -> Self {
+        if m.is_zero() { return Self::zero(precision) }
+        let extra = m.bits() as i64 - precision as i64;
+        if extra <= 0 { return Self { value: Value::Finite(m, e), precision } }
+        let (sign, mag) = (m.sign(), m.magnitude());
+        let mag = (mag + (BigUint::one() << (extra - 1) as usize)) >> extra as usize;
+        Self { value: Value::Finite(BigInt::from_biguint(sign, mag), e + extra), precision }
+    }
+
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+    pub fn with_precision(&self, precision: u32) -> Self {
+        match &self.value {
+            Value::Finite(m, e) => Self::finite(m.clone(), *e, precision),
+            Value::Special(v) => Self { value: Value::Special(*v), precision },
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        matches!(&self.value, Value::Finite(m, _) if m.is_zero())
+    }
+    pub fn is_negative(&self) -> bool {
+        match &self.value {
+            Value::Finite(m, _) => m.is_negative(),
+            Value::Special(v) => *v < 0.0,
+        }
+    }
+    pub fn is_finite(&self) -> bool {
+        matches!(self.value, Value::Finite(_, _))
+    }
+
+    /// The power of 2 just above the size of `self`. Very negative for 0.
+    fn log2(&self) -> i64 {
+        match &self.value {
+            Value::Finite(m, _) if m.is_zero() => i64::MIN / 2,
+            Value::Finite(m, e) => m.bits() as i64 + e,
+            Value::Special(_) => i64::MAX / 2,
+        }
+    }
+
+    /// `self` if it's a whole number that fits in an `i64`.
+    pub fn to_integer(&self) -> Option<i64> {
+        let Value::Finite(m, e) = &self.value else { return None };
+        if m.is_zero() { return Some(0) }
+        if *e >= 0 {
+            if m.bits() as i64 + e > 63 { return None }
+            return (m << *e as usize).to_i64();
+        }
+        let unit = BigInt::one() << (-e) as usize;
+        if !m.is_multiple_of(&unit) { return None }
+        (m / unit).to_i64()
+    }
+
+    /// The closest whole number.
+    fn round(&self) -> BigInt {
+        let Value::Finite(m, e) = &self.value else { return BigInt::zero() };
+        if *e >= 0 { return m << *e as usize }
+        let shift = (-e) as usize;
+        let (sign, mag) = (m.sign(), m.magnitude());
+        BigInt::from_biguint(sign, (mag + (BigUint::one() << (shift - 1))) >> shift)
+    }
+
+    /// The largest whole number that isn't bigger than `self`.
+    pub fn floor(&self) -> Self {
+        let Value::Finite(m, e) = &self.value else { return self.clone() };
+        if *e >= 0 { return self.clone() }
+        Self::finite(m.div_floor(&(BigInt::one() << (-e) as usize)), 0, self.precision)
+    }
+
+    /// The closest whole number, with halves rounded away from 0 like `f64::round`.
+    pub fn nearest(&self) -> Self {
+        let Value::Finite(_, e) = &self.value else { return self.clone() };
+        if *e >= 0 { return self.clone() }
+        Self::finite(self.round(), 0, self.precision)
+    }
+
+    /// `self * 2^k`, which is exact.
+    pub fn mul_pow2(&self, k: i64) -> Self {
+        match &self.value {
+            Value::Finite(m, e) => Self { value: Value::Finite(m.clone(), e + k), precision: self.precision },
+            Value::Special(_) => self.clone(),
+        }
+    }
+
+    pub fn neg(&self) -> Self {
+        match &self.value {
+            Value::Finite(m, e) => Self { value: Value::Finite(-m, *e), precision: self.precision },
+            Value::Special(v) => Self { value: Value::Special(-v), precision: self.precision },
+        }
+    }
+    pub fn abs(&self) -> Self {
+        if self.is_negative() { self.neg() } else { self.clone() }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let precision = self.precision.max(other.precision);
+        let (Value::Finite(a, ea), Value::Finite(b, eb)) = (&self.value, &other.value) else {
+            return Self::from_f64(self.to_f64() + other.to_f64(), precision);
+        };
+        if a.is_zero() { return other.with_precision(precision) }
+        if b.is_zero() { return self.with_precision(precision) }
+
+        let (hi, ehi, lo, elo) = if ea >= eb { (a, *ea, b, *eb) } else { (b, *eb, a, *ea) };
+        // A number that doesn't reach the last bit of the other can't change it.
+        let last = ehi + hi.bits() as i64 - precision as i64;
+        if elo + (lo.bits() as i64) < last - 2 { return Self::finite(hi.clone(), ehi, precision) }
+        Self::finite((hi << (ehi - elo) as usize) + lo, elo, precision)
+    }
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let precision = self.precision.max(other.precision);
+        let (Value::Finite(a, ea), Value::Finite(b, eb)) = (&self.value, &other.value) else {
+            return Self::from_f64(self.to_f64() * other.to_f64(), precision);
+        };
+        Self::finite(a * b, ea + eb, precision)
+    }
+
+    pub fn div(&self, other: &Self) -> Self {
+        let precision = self.precision.max(other.precision);
+        let (Value::Finite(a, ea), Value::Finite(b, eb)) = (&self.value, &other.value) else {
+            return Self::from_f64(self.to_f64() / other.to_f64(), precision);
+        };
+        if b.is_zero() {
+            // * signs of zero aren't kept, so dividing by 0 is always by +0
+            return Self::from_f64(if a.is_zero() { f64::NAN } else { self.to_f64().signum() * f64::INFINITY }, precision);
+        }
+        let shift = (precision as i64 + 2 + b.bits() as i64 - a.bits() as i64).max(0);
+        Self::finite((a << shift as usize) / b, ea - eb - shift, precision)
+    }
+
+    /// Whether `self < other`.
+    pub fn less(&self, other: &Self) -> bool {
+        if !(self.is_finite() & other.is_finite()) { return self.to_f64() < other.to_f64() }
+        self.sub(other).is_negative()
+    }
+
+    pub fn sqrt(&self) -> Self {
+        let p = self.precision;
+        let Value::Finite(m, e) = &self.value else { return Self::from_f64(self.to_f64().sqrt(), p) };
+        if m.is_negative() { return Self::from_f64(f64::NAN, p) }
+        if m.is_zero() { return self.clone() }
+        // Shift so the mantissa has about twice the bits wanted and the exponent is even.
+        let mut shift = (2 * p as i64 + 2 - m.bits() as i64).max(0);
+        if (e - shift) % 2 != 0 { shift += 1 }
+        Self::finite((m << shift as usize).sqrt(), (e - shift) / 2, p)
+    }
+
+    pub fn exp(&self) -> Self {
+        let p = self.precision;
+        if !self.is_finite() { return Self::from_f64(self.to_f64().exp(), p) }
+        if self.is_zero() { return Self::one(p) }
+        let approx = self.to_f64();
+        // Past this the result has an exponent too big to keep.
+        if approx.abs() > 2f64.powi(40) { return Self::from_f64(approx.exp(), p) }
+
+        // e^x = 2^k * e^r, with r small, and e^r = (e^(r/2^h))^(2^h).
+        let w = p + GUARD + EXP_HALVINGS;
+        let k = (approx / LN_2).round() as i64;
+        let r = self.with_precision(w).sub(&ln2(w + 64).mul(&Self::from_int(k, w))).mul_pow2(-(EXP_HALVINGS as i64));
+
+        let mut sum = Self::one(w);
+        let mut term = Self::one(w);
+        for n in 1.. {
+            term = term.mul(&r).div(&Self::from_int(n, w));
+            sum = sum.add(&term);
+            if term.is_zero() || term.log2() < -(w as i64) { break }
+        }
+        for _ in 0..EXP_HALVINGS {
+            sum = sum.mul(&sum);
+        }
+        sum.mul_pow2(k).with_precision(p)
+    }
+
+    pub fn ln(&self) -> Self {
+        let p = self.precision;
+        if !self.is_finite() | self.is_negative() | self.is_zero() { return Self::from_f64(self.to_f64().ln(), p) }
+        let w = p + GUARD;
+        // x = f * 2^k, with f between 0.75 and 1.5.
+        let mut k = self.log2();
+        let mut f = self.with_precision(w).mul_pow2(-k);
+        if f.less(&Self::from_f64(0.75, w)) {
+            f = f.mul_pow2(1);
+            k -= 1;
+        }
+        // ln f = 2 atanh((f - 1) / (f + 1))
+        let one = Self::one(w);
+        let y = f.sub(&one).div(&f.add(&one));
+        atanh_series(&y, w).mul_pow2(1).add(&ln2(w + 64).mul(&Self::from_int(k, w))).with_precision(p)
+    }
+
+    pub fn atan(&self) -> Self {
+        let p = self.precision;
+        if !self.is_finite() { return Self::from_f64(self.to_f64().atan(), p) }
+        if self.is_zero() { return self.clone() }
+        let w = p + GUARD;
+        let one = Self::one(w);
+        let mut x = self.with_precision(w).abs();
+
+        // atan(x) = pi/2 - atan(1/x)
+        let flip = one.less(&x);
+        if flip { x = one.div(&x) }
+        // atan(x) = 2 atan(x / (1 + sqrt(1 + x^2))), until x is small enough for the series.
+        let mut doublings = 0;
+        while x.log2() > -8 {
+            x = x.div(&one.add(&one.add(&x.mul(&x)).sqrt()));
+            doublings += 1;
+        }
+        let x2 = x.mul(&x);
+        let (mut sum, mut power) = (x.clone(), x.clone());
+        for n in 1.. {
+            power = power.mul(&x2).neg();
+            let term = power.div(&Self::from_int(2 * n + 1, w));
+            sum = sum.add(&term);
+            if term.is_zero() || term.log2() < sum.log2() - w as i64 { break }
+        }
+
+        let mut r = sum.mul_pow2(doublings);
+        if flip { r = pi(w).mul_pow2(-1).sub(&r) }
+        if self.is_negative() { r = r.neg() }
+        r.with_precision(p)
+    }
+
+    /// The angle of the point `(x, y)`, between -pi and pi.
+    pub fn atan2(y: &Self, x: &Self) -> Self {
+        let p = y.precision.max(x.precision);
+        if !(y.is_finite() & x.is_finite()) { return Self::from_f64(y.to_f64().atan2(x.to_f64()), p) }
+        if x.is_zero() {
+            if y.is_zero() { return Self::zero(p) }
+            let half = pi(p).mul_pow2(-1);
+            return if y.is_negative() { half.neg() } else { half };
+        }
+        let w = p + GUARD;
+        let a = y.with_precision(w).div(&x.with_precision(w)).atan();
+        let r = match (x.is_negative(), y.is_negative()) {
+            (false, _) => a,
+            (true, false) => a.add(&pi(w)),
+            (true, true) => a.sub(&pi(w)),
+        };
+        r.with_precision(p)
+    }
+
+    /// `(sin(self), cos(self))`.
+    pub fn sin_cos(&self) -> (Self, Self) {
+        let p = self.precision;
+        if !self.is_finite() || self.log2() > MAX_TRIG_LOG2 {
+            let v = self.to_f64();
+            return (Self::from_f64(v.sin(), p), Self::from_f64(v.cos(), p));
+        }
+        // x = k pi/2 + r with r between -pi/4 and pi/4. Big inputs need more bits of pi for r to be right.
+        let w = p + GUARD + self.log2().max(0) as u32;
+        let half_pi = pi(w).mul_pow2(-1);
+        let x = self.with_precision(w);
+        let k = x.div(&half_pi).round();
+        let r = x.sub(&half_pi.mul(&Self::finite(k.clone(), 0, w)));
+
+        let r2 = r.mul(&r);
+        let (mut sin, mut cos) = (r.clone(), Self::one(w));
+        let (mut s_term, mut c_term) = (r.clone(), Self::one(w));
+        for n in 1.. {
+            s_term = s_term.mul(&r2).div(&Self::from_int((2 * n) * (2 * n + 1), w)).neg();
+            c_term = c_term.mul(&r2).div(&Self::from_int((2 * n - 1) * (2 * n), w)).neg();
+            sin = sin.add(&s_term);
+            cos = cos.add(&c_term);
+            if c_term.log2() < -(w as i64) { break }
+        }
+
+        let (sin, cos) = match k.mod_floor(&BigInt::from(4)).to_u8().unwrap() {
+            0 => (sin, cos),
+            1 => (cos, sin.neg()),
+            2 => (sin.neg(), cos.neg()),
+            _ => (cos.neg(), sin),
+        };
+        (sin.with_precision(p), cos.with_precision(p))
+    }
+
+    /// `(sinh(self), cosh(self))`.
+    pub fn sinh_cosh(&self) -> (Self, Self) {
+        let p = self.precision;
+        let w = p + GUARD;
+        let x = self.with_precision(w);
+        let (e, inv) = (x.exp(), x.neg().exp());
+        let cosh = e.add(&inv).mul_pow2(-1);
+        // Near 0 the difference cancels, so the series is used instead.
+        let sinh = if x.log2() <= 0 {
+            let x2 = x.mul(&x);
+            let (mut sum, mut term) = (x.clone(), x.clone());
+            for n in 1.. {
+                term = term.mul(&x2).div(&Self::from_int((2 * n) * (2 * n + 1), w));
+                sum = sum.add(&term);
+                if term.is_zero() || term.log2() < sum.log2() - w as i64 { break }
+            }
+            sum
+        } else {
+            e.sub(&inv).mul_pow2(-1)
+        };
+        (sinh.with_precision(p), cosh.with_precision(p))
+    }
+}
+
+/// `x + x^3/3 + x^5/5 + ...`, for small `x`.
+fn atanh_series(x: &BigFloat, w: u32) -> BigFloat {
+    let x2 = x.mul(x);
+    let (mut sum, mut power) = (x.clone(), x.clone());
+    for n in 1.. {
+        power = power.mul(&x2);
+        let term = power.div(&BigFloat::from_int(2 * n + 1, w));
+        sum = sum.add(&term);
+        if term.is_zero() || term.log2() < sum.log2() - w as i64 { break }
+    }
+    sum
+}
+
+/// `atan(1/n)`, for whole `n` above 1.
+fn atan_inv(n: i64, w: u32) -> BigFloat {
+    let n2 = BigFloat::from_int(n * n, w);
+    let mut power = BigFloat::one(w).div(&BigFloat::from_int(n, w));
+    let mut sum = power.clone();
+    for k in 1.. {
+        power = power.div(&n2).neg();
+        let term = power.div(&BigFloat::from_int(2 * k + 1, w));
+        sum = sum.add(&term);
+        if term.log2() < -(w as i64) - 2 { break }
+    }
+    sum
+}
+
+thread_local! {
+    /// Constants that have already been worked out, by name and precision.
+    static CONSTANTS: RefCell<HashMap<(&'static str, u32), BigFloat>> = RefCell::new(HashMap::new());
+}
+
+fn constant(name: &'static str, precision: u32, f: impl FnOnce(u32) -> BigFloat) -> BigFloat {
+    if let Some(c) = CONSTANTS.with(|c| c.borrow().get(&(name, precision)).cloned()) {
+        return c;
+    }
+    let c = f(precision + GUARD).with_precision(precision);
+    CONSTANTS.with(|cs| cs.borrow_mut().insert((name, precision), c.clone()));
+    c
+}
+
+/// Pi, with Machin's formula.
+pub fn pi(precision: u32) -> BigFloat {
+    constant("pi", precision, |w| atan_inv(5, w).mul_pow2(2).sub(&atan_inv(239, w)).mul_pow2(2))
+}
+
+/// `ln(2) = 2 atanh(1/3)`
+pub fn ln2(precision: u32) -> BigFloat {
+    constant("ln2", precision, |w| atanh_series(&BigFloat::one(w).div(&BigFloat::from_int(3, w)), w).mul_pow2(1))
+}
+
+/// `x * 2^e` without the power of 2 overflowing on its own.
+fn ldexp(mut x: f64, mut e: i64) -> f64 {
+    while (e > 1000) & x.is_finite() {
+        x *= 2f64.powi(1000);
+        e -= 1000;
+    }
+    while (e < -1000) & (x != 0.0) {
+        x *= 2f64.powi(-1000);
+        e += 1000;
+    }
+    x * 2f64.powi(e.clamp(-1100, 1100) as i32)
+}
+
+
+/// A complex number made of two `BigFloat`s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BigComplex {
+    pub re: BigFloat,
+    pub im: BigFloat,
+}
+impl BigComplex {
+    pub fn new(re: BigFloat, im: BigFloat) -> Self {
+        Self { re, im }
+    }
+    pub fn real(re: BigFloat) -> Self {
+        let im = BigFloat::zero(re.precision);
+        Self { re, im }
+    }
+    pub fn from_complex(c: Complex64, precision: u32) -> Self {
+        Self::new(BigFloat::from_f64(c.re, precision), BigFloat::from_f64(c.im, precision))
+    }
+    pub fn to_complex(&self) -> Complex64 {
+        Complex64::new(self.re.to_f64(), self.im.to_f64())
+    }
+
+    pub fn precision(&self) -> u32 {
+        self.re.precision.max(self.im.precision)
+    }
+    pub fn with_precision(&self, precision: u32) -> Self {
+        Self::new(self.re.with_precision(precision), self.im.with_precision(precision))
+    }
+    fn one(precision: u32) -> Self {
+        Self::real(BigFloat::one(precision))
+    }
+    fn i(precision: u32) -> Self {
+        Self::new(BigFloat::zero(precision), BigFloat::one(precision))
+    }
+
+    pub fn is_real(&self) -> bool {
+        self.im.is_zero()
+    }
+    pub fn is_zero(&self) -> bool {
+        self.re.is_zero() & self.im.is_zero()
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        Self::new(self.re.add(&other.re), self.im.add(&other.im))
+    }
+    pub fn sub(&self, other: &Self) -> Self {
+        Self::new(self.re.sub(&other.re), self.im.sub(&other.im))
+    }
+    pub fn neg(&self) -> Self {
+        Self::new(self.re.neg(), self.im.neg())
+    }
+    pub fn conj(&self) -> Self {
+        Self::new(self.re.clone(), self.im.neg())
+    }
+    pub fn mul(&self, other: &Self) -> Self {
+        if self.is_real() & other.is_real() { return Self::real(self.re.mul(&other.re)) }
+        Self::new(
+            self.re.mul(&other.re).sub(&self.im.mul(&other.im)),
+            self.re.mul(&other.im).add(&self.im.mul(&other.re)),
+        )
+    }
+    pub fn div(&self, other: &Self) -> Self {
+        if self.is_real() & other.is_real() { return Self::real(self.re.div(&other.re)) }
+        if other.is_zero() { return Self::from_complex(self.to_complex() / other.to_complex(), self.precision()) }
+        let d = other.re.mul(&other.re).add(&other.im.mul(&other.im));
+        Self::new(
+            self.re.mul(&other.re).add(&self.im.mul(&other.im)).div(&d),
+            self.im.mul(&other.re).sub(&self.re.mul(&other.im)).div(&d),
+        )
+    }
+    fn scale(&self, k: &BigFloat) -> Self {
+        Self::new(self.re.mul(k), self.im.mul(k))
+    }
+
+    pub fn norm(&self) -> BigFloat {
+        if self.is_real() { return self.re.abs() }
+        self.re.mul(&self.re).add(&self.im.mul(&self.im)).sqrt()
+    }
+    pub fn arg(&self) -> BigFloat {
+        BigFloat::atan2(&self.im, &self.re)
+    }
+
+    /// Runs `f` with extra bits and rounds the result back.
+    fn guarded(&self, f: impl FnOnce(&Self) -> Self) -> Self {
+        let p = self.precision();
+        f(&self.with_precision(p + GUARD)).with_precision(p)
+    }
+
+    pub fn exp(&self) -> Self {
+        if self.is_real() { return Self::real(self.re.exp()) }
+        let (sin, cos) = self.im.sin_cos();
+        Self::new(cos, sin).scale(&self.re.exp())
+    }
+    pub fn ln(&self) -> Self {
+        if self.is_real() & !self.re.is_negative() { return Self::real(self.re.ln()) }
+        Self::new(self.norm().ln(), self.arg())
+    }
+    pub fn sqrt(&self) -> Self {
+        if self.is_real() {
+            if self.re.is_negative() { return Self::new(BigFloat::zero(self.precision()), self.re.neg().sqrt()) }
+            return Self::real(self.re.sqrt());
+        }
+        self.guarded(|z| {
+            // The larger part comes from `|z| + |re|`, and the other from dividing by it so nothing cancels.
+            let big = z.norm().add(&z.re.abs()).mul_pow2(-1).sqrt();
+            let small = z.im.abs().div(&big).mul_pow2(-1);
+            let small_im = if z.im.is_negative() { small.neg() } else { small.clone() };
+            let big_im = if z.im.is_negative() { big.neg() } else { big.clone() };
+            if z.re.is_negative() { Self::new(small, big_im) } else { Self::new(big, small_im) }
+        })
+    }
+    /// The real cube root for real numbers, like `Builtin::Cbrt`, and the principal one otherwise.
+    pub fn cbrt(&self) -> Self {
+        let p = self.precision();
+        if self.is_real() {
+            if self.re.is_zero() { return self.clone() }
+            let r = self.re.abs().ln().div(&BigFloat::from_int(3, p)).exp();
+            return Self::real(if self.re.is_negative() { r.neg() } else { r });
+        }
+        self.ln().scale(&BigFloat::one(p).div(&BigFloat::from_int(3, p))).exp()
+    }
+
+    /// `self^n`, by squaring.
+    pub fn powi(&self, n: i64) -> Self {
+        let p = self.precision();
+        let w = p + GUARD + 64;
+        let mut base = self.with_precision(w);
+        let mut r = Self::one(w);
+        let mut k = n.unsigned_abs();
+        while k > 0 {
+            if k & 1 == 1 { r = r.mul(&base) }
+            base = base.mul(&base);
+            k >>= 1;
+        }
+        if n < 0 { r = Self::one(w).div(&r) }
+        r.with_precision(p)
+    }
+
+    /// `self^b`, keeping real results real where possible like `pow_complex`.
+    pub fn pow(&self, b: &Self) -> Self {
+        if b.is_real() {
+            if let Some(n) = b.re.to_integer().filter(|n| n.abs() <= i32::MAX as i64) { return self.powi(n) }
+        }
+        if self.is_zero() {
+            // 0^b is 0 for positive b, and infinite for negative b.
+            let p = self.precision();
+            if b.re.is_negative() { return Self::real(BigFloat::from_f64(f64::INFINITY, p)) }
+            return Self::real(BigFloat::zero(p));
+        }
+        self.guarded(|a| a.ln().mul(&b.with_precision(a.precision())).exp())
+    }
+
+    pub fn sin(&self) -> Self {
+        let (s, c) = self.re.sin_cos();
+        if self.is_real() { return Self::real(s) }
+        let (sh, ch) = self.im.sinh_cosh();
+        Self::new(s.mul(&ch), c.mul(&sh))
+    }
+    pub fn cos(&self) -> Self {
+        let (s, c) = self.re.sin_cos();
+        if self.is_real() { return Self::real(c) }
+        let (sh, ch) = self.im.sinh_cosh();
+        Self::new(c.mul(&ch), s.mul(&sh).neg())
+    }
+    pub fn tan(&self) -> Self {
+        self.guarded(|z| z.sin().div(&z.cos()))
+    }
+    pub fn sinh(&self) -> Self {
+        let (sh, ch) = self.re.sinh_cosh();
+        if self.is_real() { return Self::real(sh) }
+        let (s, c) = self.im.sin_cos();
+        Self::new(sh.mul(&c), ch.mul(&s))
+    }
+    pub fn cosh(&self) -> Self {
+        let (sh, ch) = self.re.sinh_cosh();
+        if self.is_real() { return Self::real(ch) }
+        let (s, c) = self.im.sin_cos();
+        Self::new(ch.mul(&c), sh.mul(&s))
+    }
+    pub fn tanh(&self) -> Self {
+        self.guarded(|z| z.sinh().div(&z.cosh()))
+    }
+
+    // The inverse functions use the same formulas as `Complex64`, so they have the same branches.
+
+    /// `-i ln(sqrt(1 - z^2) + i z)`
+    pub fn asin(&self) -> Self {
+        self.guarded(|z| {
+            let (one, i) = (Self::one(z.precision()), Self::i(z.precision()));
+            let r = one.sub(&z.mul(z)).sqrt().add(&i.mul(z)).ln().mul(&i).neg();
+            if z.is_real() & !one.re.less(&z.re.abs()) { Self::real(r.re) } else { r }
+        })
+    }
+    /// `-i ln(i sqrt(1 - z^2) + z)`
+    pub fn acos(&self) -> Self {
+        self.guarded(|z| {
+            let (one, i) = (Self::one(z.precision()), Self::i(z.precision()));
+            let r = i.mul(&one.sub(&z.mul(z)).sqrt()).add(z).ln().mul(&i).neg();
+            if z.is_real() & !one.re.less(&z.re.abs()) { Self::real(r.re) } else { r }
+        })
+    }
+    /// `(ln(1 + i z) - ln(1 - i z)) / 2i`
+    pub fn atan(&self) -> Self {
+        if self.is_real() { return Self::real(self.re.atan()) }
+        self.guarded(|z| {
+            let (one, i) = (Self::one(z.precision()), Self::i(z.precision()));
+            let iz = i.mul(z);
+            one.add(&iz).ln().sub(&one.sub(&iz).ln()).div(&i.scale(&BigFloat::from_int(2, z.precision())))
+        })
+    }
+    /// `ln(z + sqrt(z^2 + 1))`
+    pub fn asinh(&self) -> Self {
+        self.guarded(|z| {
+            // * odd, so negative real inputs are flipped to avoid cancelling
+            if z.is_real() & z.re.is_negative() { return z.neg().asinh().neg() }
+            z.add(&z.mul(z).add(&Self::one(z.precision())).sqrt()).ln()
+        })
+    }
+    /// `2 ln(sqrt((z + 1) / 2) + sqrt((z - 1) / 2))`
+    pub fn acosh(&self) -> Self {
+        self.guarded(|z| {
+            let one = Self::one(z.precision());
+            let a = z.add(&one).scale(&BigFloat::one(z.precision()).mul_pow2(-1)).sqrt();
+            let b = z.sub(&one).scale(&BigFloat::one(z.precision()).mul_pow2(-1)).sqrt();
+            let r = a.add(&b).ln().scale(&BigFloat::from_int(2, z.precision()));
+            if z.is_real() & !z.re.less(&one.re) { Self::real(r.re) } else { r }
+        })
+    }
+    /// `(ln(1 + z) - ln(1 - z)) / 2`
+    pub fn atanh(&self) -> Self {
+        self.guarded(|z| {
+            let one = Self::one(z.precision());
+            let r = one.add(z).ln().sub(&one.sub(z).ln()).scale(&BigFloat::one(z.precision()).mul_pow2(-1));
+            if z.is_real() & z.re.abs().less(&one.re) { Self::real(r.re) } else { r }
+        })
+    }
+}
@@ -0,0 +1,290 @@
+/*
+This is where the constants `pi`, `e` and `i` are kept exact.
+
+They're terms of their own, so constant reduction doesn't fold them into floats.
+Instead the identities that make them worth keeping are applied exactly, like
+`e^(i*pi) = -1`, `sin(pi) = 0`, `i^2 = -1`, `zeta(2) = pi^2/6` and `floor(pi) = 3`.
+Whatever's left is only turned into a number when it's evaluated, to as many bits
+as the evaluation uses.
+*/
+
+use num::{BigInt, BigRational, Integer, One, Signed, ToPrimitive, Zero};
+use num_complex::Complex64;
+
+use crate::expr::{f, Builtin, Expr, Term};
+use crate::special;
+
+/// How close (relative to its size) a float has to be to a jump of a step function before
+/// it could be on the wrong side.
+const JUMP_MARGIN: f64 = 1e-9;
+
+/// A constant that's kept symbolic until it's evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Constant {
+    Pi,
+    E,
+    I,
+}
+impl Constant {
+    /// The closest float or complex number to the constant.
+    pub fn value(&self) -> Term {
+        match self {
+            Self::Pi => Term::Real(std::f64::consts::PI),
+            Self::E => Term::Real(std::f64::consts::E),
+            Self::I => Term::Complex(Complex64::I),
+        }
+    }
+}
+
+impl Expr {
+    /// `self` as a number, with constants turned into floats. `None` if it has variables or
+    /// anything else that doesn't reduce to a term.
+    pub fn as_number(&self) -> Option<Term> {
+        let r = self.numeric()?.flatten().reduce_const();
+        r.is_number().then(|| r.force_const())
+    }
+
+    fn numeric(&self) -> Option<Expr> {
+        let all = |n: &[Expr]| n.iter().map(|a| a.numeric()).collect::<Option<Vec<Expr>>>();
+        match self {
+            Self::Term(Term::Const(c)) => Some(c.value().into()),
+            Self::Term(Term::Var(_)) => None,
+            Self::Term(_) => Some(self.clone()),
+            Self::Add(n) => Some(Self::Add(all(n)?)),
+            Self::Mul(n) => Some(Self::Mul(all(n)?)),
+            Self::Pow(a, b) => Some(Self::Pow(a.numeric()?.r#box(), b.numeric()?.r#box())),
+            Self::Builtin(b, n) if !b.is_linalg() => {
+                Some(Self::Builtin(*b, n.iter().map(|a| Some(a.numeric()?.r#box())).collect::<Option<Vec<Box<Expr>>>>()?))
+            },
+            _ => None,
+        }
+    }
+
+    /// `self` as `q*pi` or `q*i*pi` with a rational `q`, and whether the `i` is there.
+    /// Expects constants to have been reduced.
+    fn pi_multiple(&self) -> Option<(BigRational, bool)> {
+        match self {
+            Self::Term(Term::Const(Constant::Pi)) => Some((BigRational::one(), false)),
+            Self::Mul(n) => {
+                let (mut q, mut pi, mut i) = (BigRational::one(), false, false);
+                for a in n {
+                    match a {
+                        Self::Term(Term::Rational(r)) => q *= r,
+                        Self::Term(Term::Const(Constant::Pi)) if !pi => pi = true,
+                        Self::Term(Term::Const(Constant::I)) if !i => i = true,
+                        _ => return None,
+                    }
+                }
+                pi.then_some((q, i))
+            },
+            _ => None,
+        }
+    }
+
+    fn is_constant(&self, c: Constant) -> bool {
+        matches!(self, Self::Term(Term::Const(k)) if *k == c)
+    }
+}
+
+/// `a^b` when it can be worked out exactly, like `e^(i*pi) = -1` and `i^3 = -i`.
+/// Expects constants in `a` and `b` to have been reduced.
+pub fn symbolic_pow(a: &Expr, b: &Expr) -> Option<Expr> {
+    if a.is_constant(Constant::E) {
+        // * `ln` is the principal branch, so this holds for every input
+        if let Expr::Builtin(Builtin::Ln, n) = b { return Some(*n[0].clone()) }
+        // e^(q*i*pi) = i^(2q)
+        let (q, true) = b.pi_multiple()? else { return None };
+        let twice = q * BigRational::from_integer(2.into());
+        return twice.is_integer().then(|| i_power(twice.to_integer()));
+    }
+    if a.is_constant(Constant::I) {
+        let Expr::Term(Term::Rational(k)) = b else { return None };
+        return k.is_integer().then(|| i_power(k.to_integer()));
+    }
+    None
+}
+
+/// Replaces the `i`s in a product with what they multiply to, `i*i = -1`.
+pub fn combine_i(n: &mut Vec<Expr>) {
+    let count = n.iter().filter(|a| a.is_constant(Constant::I)).count();
+    if count < 2 { return }
+    n.retain(|a| !a.is_constant(Constant::I));
+    match i_power(count.into()) {
+        Expr::Mul(m) => n.extend(m),
+        o => n.push(o),
+    }
+}
+
+/// `i^k`, as `1`, `i`, `-1` or `-1*i`.
+fn i_power(k: BigInt) -> Expr {
+    let i = || *f::term(Term::Const(Constant::I));
+    match k.mod_floor(&4.into()).to_u8().unwrap() {
+        0 => *f::num(1.0),
+        1 => i(),
+        2 => *f::num(-1.0),
+        _ => Expr::Mul(vec![*f::num(-1.0), i()]),
+    }
+}
+
+/// `sin(q*pi)`, if it's rational.
+fn sin_pi(q: &BigRational) -> Option<BigRational> {
+    // * only sixths of pi have rational sines
+    let sixths = q * BigRational::from_integer(6.into());
+    if !sixths.is_integer() { return None }
+    let half = || BigRational::new(1.into(), 2.into());
+    let s = match sixths.to_integer().mod_floor(&12.into()).to_u8().unwrap() {
+        0 | 6 => BigRational::zero(),
+        1 | 5 => half(),
+        3 => BigRational::one(),
+        7 | 11 => -half(),
+        9 => -BigRational::one(),
+        _ => return None,
+    };
+    Some(s)
+}
+
+fn cos_pi(q: &BigRational) -> Option<BigRational> {
+    sin_pi(&(q + BigRational::new(1.into(), 2.into())))
+}
+
+impl Builtin {
+    /// Applies the function to reduced inputs with constants in them, if the result is exact.
+    pub fn apply_symbolic(&self, args: &[Expr]) -> Option<Expr> {
+        let exact = |r: BigRational| Some(Expr::Term(Term::Rational(r)));
+        let a = args.first()?;
+        match self {
+            Self::Sin => exact(sin_pi(&real_pi_multiple(a)?)?),
+            Self::Cos => exact(cos_pi(&real_pi_multiple(a)?)?),
+            Self::Tan => {
+                let q = real_pi_multiple(a)?;
+                let (s, c) = (sin_pi(&q)?, cos_pi(&q)?);
+                if c.is_zero() { return None }
+                exact(s / c)
+            },
+            Self::Ln => {
+                if a.is_constant(Constant::E) { return exact(BigRational::one()) }
+                // ln(i) = i*pi/2 and ln(-1) = i*pi
+                let half = if a.is_constant(Constant::I) {
+                    BigRational::new(1.into(), 2.into())
+                } else if matches!(a, Expr::Term(Term::Rational(r)) if (-r).is_one()) {
+                    BigRational::one()
+                } else {
+                    return None;
+                };
+                Some(Expr::Mul(vec![Term::Rational(half).into(), Term::Const(Constant::I).into(), Term::Const(Constant::Pi).into()]))
+            },
+            // sqrt(-r^2) = r*i
+            Self::Sqrt => {
+                let Expr::Term(Term::Rational(r)) = a else { return None };
+                if !r.is_negative() { return None }
+                let Term::Rational(root) = self.apply(&[Term::Rational(-r)]) else { return None };
+                Some(Expr::Mul(vec![Term::Rational(root).into(), Term::Const(Constant::I).into()]))
+            },
+
+            // pi and e are real and positive, and i is on the imaginary axis.
+            Self::Abs | Self::Re | Self::Conj if a.is_constant(Constant::Pi) | a.is_constant(Constant::E) => Some(a.clone()),
+            Self::Im | Self::Arg if a.is_constant(Constant::Pi) | a.is_constant(Constant::E) => exact(BigRational::zero()),
+            Self::Abs | Self::Im if a.is_constant(Constant::I) => exact(BigRational::one()),
+            Self::Re if a.is_constant(Constant::I) => exact(BigRational::zero()),
+            Self::Conj if a.is_constant(Constant::I) => Some(Expr::Mul(vec![*f::num(-1.0), a.clone()])),
+            Self::Arg if a.is_constant(Constant::I) => {
+                Some(Expr::Mul(vec![Term::Rational(BigRational::new(1.into(), 2.into())).into(), Term::Const(Constant::Pi).into()]))
+            },
+            // Sums of rationals and rational multiples of i, like `3 + 4i`.
+            Self::Re => exact(gaussian(a)?.0),
+            Self::Im => exact(gaussian(a)?.1),
+            Self::Conj => {
+                let (re, im) = gaussian(a)?;
+                Some(complex(re, -im))
+            },
+            Self::Abs => {
+                let (re, im) = gaussian(a)?;
+                let Term::Rational(r) = Self::Sqrt.apply(&[Term::Rational(&re * &re + &im * &im)]) else { return None };
+                exact(r)
+            },
+
+            Self::Zeta => {
+                // zeta(2k) = (-1)^(k+1) B_2k (2pi)^2k / (2 (2k)!)
+                let r = rational(a)?;
+                if !r.is_integer() { return None }
+                let k = r.to_integer().to_usize().filter(|k| k % 2 == 0 && *k > 0 && *k < special::BERNOULLI_LIMIT)?;
+                let q = special::bernoulli(k) * BigRational::from_integer(BigInt::from(2).pow(k as u32))
+                    / BigRational::from_integer(special::factorial(k as u32) * 2);
+                let q = if k % 4 == 0 { -q } else { q };
+                Some(pi_power(q, BigRational::from_integer(k.into())))
+            },
+            Self::Gamma => {
+                // gamma(1/2 + n) = (2n)! / (4^n n!) sqrt(pi), and gamma(1/2 - n) = (-4)^n n! / (2n)! sqrt(pi)
+                let r = rational(a)?;
+                let half = BigRational::new(1.into(), 2.into());
+                let n = (r - &half).to_integer();
+                if &(BigRational::from_integer(n.clone()) + &half) != r { return None }
+                let m = n.abs().to_u32().filter(|m| *m <= 500)?;
+                let ratio = BigRational::new(special::factorial(2 * m), BigInt::from(4).pow(m) * special::factorial(m));
+                let q = if n.is_negative() {
+                    let q = ratio.recip();
+                    if m % 2 == 1 { -q } else { q }
+                } else {
+                    ratio
+                };
+                Some(pi_power(q, half))
+            },
+
+            // These only jump at whole numbers (or halves, or 0), so a float is good enough
+            // when it isn't close to one. `floor(pi)` is 3.
+            Self::Floor | Self::Ceil | Self::Round | Self::Sign => {
+                let v = a.as_number()?.as_complex();
+                if v.im != 0.0 { return Some(Term::Real(f64::NAN).into()) }
+                let edge = match self {
+                    Self::Round => v.re - 0.5,
+                    Self::Sign => return (v.re.abs() > JUMP_MARGIN).then(|| self.apply(&[v.re.into()]).into()),
+                    _ => v.re,
+                };
+                if (edge - edge.round()).abs() <= JUMP_MARGIN * (1.0 + edge.abs()) { return None }
+                Some(self.apply(&[v.re.into()]).into())
+            },
+            _ => None,
+        }
+    }
+}
+
+fn rational(a: &Expr) -> Option<&BigRational> {
+    let Expr::Term(Term::Rational(r)) = a else { return None };
+    Some(r)
+}
+
+/// `a` as `re + im*i` with rational parts. Expects constants to have been reduced.
+fn gaussian(a: &Expr) -> Option<(BigRational, BigRational)> {
+    match a {
+        Expr::Term(Term::Rational(r)) => Some((r.clone(), BigRational::zero())),
+        Expr::Term(Term::Const(Constant::I)) => Some((BigRational::zero(), BigRational::one())),
+        Expr::Mul(n) => match &n[..] {
+            [Expr::Term(Term::Rational(r)), i] if i.is_constant(Constant::I) => Some((BigRational::zero(), r.clone())),
+            _ => None,
+        },
+        Expr::Add(n) => n.iter().try_fold((BigRational::zero(), BigRational::zero()), |(re, im), a| {
+            let (r, i) = gaussian(a)?;
+            Some((re + r, im + i))
+        }),
+        _ => None,
+    }
+}
+
+/// `re + im*i`, written the way constant reduction leaves it.
+fn complex(re: BigRational, im: BigRational) -> Expr {
+    if im.is_zero() { return Term::Rational(re).into() }
+    let im = Expr::Mul(vec![Term::Rational(im).into(), Term::Const(Constant::I).into()]);
+    if re.is_zero() { return im }
+    Expr::Add(vec![Term::Rational(re).into(), im])
+}
+
+/// `q * pi^p`.
+fn pi_power(q: BigRational, p: BigRational) -> Expr {
+    Expr::Mul(vec![Term::Rational(q).into(), Expr::Pow(f::term(Term::Const(Constant::Pi)), f::term(Term::Rational(p)))])
+}
+
+/// `q` when `a` is `q*pi`.
+fn real_pi_multiple(a: &Expr) -> Option<BigRational> {
+    let (q, false) = a.pi_multiple()? else { return None };
+    Some(q)
+}
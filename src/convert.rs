@@ -3,99 +3,465 @@
 This is where the parsel trees are converted into a `Context`
 */
 
-use num::ToPrimitive;
-use parsel::ast::LeftAssoc;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use parsel::ast::{LeftAssoc, RightAssoc};
 
 // * I would expand these, but there are duplicate names in these modules,
 // * so I just refer to their whole name instead.
-use crate::parse;
+use crate::{data, parse};
+use crate::constant::Constant;
 use crate::expr::{self, f::*};
 
-pub fn convert(statements: Vec<parse::Statement>) -> expr::Context {
-    let mut c = expr::Context::new();
+/// Converts statements into a `Context`. Includes are found relative to the current directory.
+pub fn convert(statements: Vec<parse::Statement>) -> Result<expr::Context, Vec<String>> {
+    let mut c = Converter::new();
+    c.statements(statements, Path::new("."), &[], "");
+    c.finish()
+}
+
+/// Reads, parses and converts a file and everything it includes into a `Context`.
+pub fn convert_file(path: impl AsRef<Path>) -> Result<expr::Context, Vec<String>> {
+    let mut c = Converter::new();
+    c.file(path.as_ref(), "");
+    c.finish()
+}
+
+/// The state kept while converting statements from multiple files.
+struct Converter {
+    ctx: expr::Context,
+    /// The files currently being converted, innermost last. Used to find include cycles.
+    stack: Vec<PathBuf>,
+    errs: Vec<String>,
+}
+impl Converter {
+    /// Starts with the constants `Context::new` defines.
+    fn new() -> Self {
+        Self { ctx: expr::Context::new(), stack: Vec::new(), errs: Vec::new() }
+    }
 
-    for statement in statements {
-        match statement {
-            parse::Statement::Let { kw_let: _, name, kw_eq: _, body, kw_semi: _ } => {
-                c.def_var(&name.to_string(), *convert_expr(body));
+    fn finish(mut self) -> Result<expr::Context, Vec<String>> {
+        // Calls and shapes can only be checked once everything is defined, and calls to
+        // functions that don't exist don't have a shape.
+        match self.ctx.check_calls() {
+            Ok(()) => if let Err(e) = self.ctx.check_shapes() { self.errs.extend(e) },
+            Err(e) => self.errs.extend(e),
+        }
+        // Fitting evaluates things, so it waits until everything else is known to be fine.
+        if self.errs.is_empty() {
+            if let Err(e) = self.ctx.fit() {
+                self.errs.extend(e);
             }
-            parse::Statement::Fn { kw_fn: _, recursive, name, args, kw_eq: _, body , kw_semi: _} => {
-                c.def_func(&name.to_string(), recursive.is_some(), args.into_inner().iter().map(|n| n.to_string()).collect(), *convert_expr(body));
+        }
+
+        if self.errs.is_empty() { return Ok(self.ctx) }
+        Err(self.errs)
+    }
+
+    /// Converts a file with all of its definitions put under `prefix`.
+    fn file(&mut self, path: &Path, prefix: &str) {
+        let full = match path.canonicalize() {
+            Ok(p) => p,
+            Err(e) => {
+                self.errs.push(format!("ERROR: couldn't find {}! ({e})", path.display()));
+                return;
+            },
+        };
+
+        if let Some(i) = self.stack.iter().position(|p| *p == full) {
+            let cycle: Vec<String> = self.stack[i..].iter().chain([&full]).map(|p| p.display().to_string()).collect();
+            self.errs.push(format!("ERROR: include cycle! {}", cycle.join(" -> ")));
+            return;
+        }
+
+        let src = match fs::read_to_string(&full) {
+            Ok(s) => s,
+            Err(e) => {
+                self.errs.push(format!("ERROR: couldn't read {}! ({e})", full.display()));
+                return;
+            },
+        };
+
+        let statements = match parse::try_parse(&src) {
+            Ok(s) => s,
+            Err(e) => {
+                self.errs.push(format!("ERROR: couldn't parse {}! ({e})", full.display()));
+                return;
+            },
+        };
+
+        // Included files only see their own definitions (and the root ones through unqualified names).
+        self.stack.push(full.clone());
+        self.statements(statements, full.parent().unwrap(), &[], prefix);
+        self.stack.pop();
+    }
+
+    /// Converts the statements of one file or `mod` block. `dir` is where includes are looked
+    /// for and `outer` are the scopes this one is nested in.
+    fn statements(&mut self, statements: Vec<parse::Statement>, dir: &Path, outer: &[Scope], prefix: &str) {
+        let local = defined_names(&statements, dir, &mut self.stack.clone());
+        let mut scopes = outer.to_vec();
+        scopes.push(Scope { prefix: prefix.to_string(), local });
+
+        for statement in statements {
+            match statement {
+                parse::Statement::Let { kw_let: _, name, kw_eq: _, body, kw_semi: _ } => {
+                    let body = qualify(&convert_expr(body), &scopes, &[]);
+                    if let Err(e) = self.ctx.def_var(&format!("{prefix}{name}"), body) {
+                        self.errs.push(e);
+                    }
+                },
+                parse::Statement::Fn { kw_fn: _, recursive, name, args, kw_eq: _, body , kw_semi: _} => {
+                    let args: Vec<String> = args.into_inner().iter().map(|n| n.to_string()).collect();
+                    let body = qualify(&convert_expr(body), &scopes, &args);
+                    if let Err(e) = self.ctx.def_func(&format!("{prefix}{name}"), recursive.is_some(), args, body) {
+                        self.errs.push(e);
+                    }
+                },
+                parse::Statement::Curve { kw_curve: _, name, param, kw_eq: _, body, kw_for: _, var, kw_in: _, range, kw_semi: _ } => {
+                    let components = body.into_inner().into_iter().map(|a| *convert_expr(a)).collect();
+                    self.curve(&format!("{prefix}{name}"), param.into_inner(), var, components, range, &scopes);
+                },
+                parse::Statement::Polar { kw_polar: _, name, param, kw_eq: _, body, kw_for: _, var, kw_in: _, range, kw_semi: _ } => {
+                    // `r = f(θ)` is the curve `(r cos θ, r sin θ)`.
+                    let param = param.into_inner();
+                    let r = convert_expr(body);
+                    let theta = term(param.to_string().into());
+                    let components = vec![*mul(r.clone(), cos(theta.clone())), *mul(r, sin(theta))];
+                    self.curve(&format!("{prefix}{name}"), param, var, components, range, &scopes);
+                },
+                parse::Statement::Surface { kw_surface: _, name, params, kw_eq: _, body, kw_for: _, x, kw_in_x: _, x_range, comma: _, y, kw_in_y: _, y_range, kw_semi: _ } => {
+                    let params: Vec<String> = params.into_inner().iter().map(|n| n.to_string()).collect();
+                    if params.len() != 2 || x != params[0] || y != params[1] {
+                        self.errs.push(format!("ERROR: surface {name} has to be defined with two parameters, ranged over in the same order!"));
+                        continue;
+                    }
+                    let body = qualify(&convert_expr(body), &scopes, &params);
+                    let range = |r: parse::Range| (qualify(&convert_expr(*r.from), &scopes, &[]), qualify(&convert_expr(*r.to), &scopes, &[]));
+                    let params = [params[0].clone(), params[1].clone()];
+                    if let Err(e) = self.ctx.def_surface(&format!("{prefix}{name}"), params, body, range(x_range), range(y_range)) {
+                        self.errs.push(e);
+                    }
+                },
+                parse::Statement::Implicit { kw_implicit: _, name, params, colon: _, lhs, kw_eq: _, rhs, kw_for: _, bounds, kw_semi: _ } => {
+                    let params: Vec<String> = params.into_inner().iter().map(|n| n.to_string()).collect();
+                    if bounds.0.len() != params.len() || bounds.0.iter().zip(params.iter()).any(|((v, _), p)| v != p) {
+                        self.errs.push(format!("ERROR: implicit {name} has to range over its variables in the order they're written!"));
+                        continue;
+                    }
+                    let lhs = qualify(&convert_expr(lhs), &scopes, &params);
+                    let rhs = qualify(&convert_expr(rhs), &scopes, &params);
+                    let ranges = bounds.0.into_iter().map(|(_, r)| (qualify(&convert_expr(*r.from), &scopes, &[]), qualify(&convert_expr(*r.to), &scopes, &[]))).collect();
+                    if let Err(e) = self.ctx.def_implicit(&format!("{prefix}{name}"), params, lhs, rhs, ranges) {
+                        self.errs.push(e);
+                    }
+                },
+                parse::Statement::Table { kw_table: _, name, columns, kw_eq: _, source, kw_semi: _ } => {
+                    let columns: Vec<String> = columns.into_inner().iter().map(|n| format!("{prefix}{n}")).collect();
+                    let values = match source {
+                        parse::TableSource::File(path) => {
+                            let path = dir.join(path.value());
+                            let read = fs::read_to_string(&path)
+                                .map_err(|e| format!("ERROR: couldn't read {}! ({e})", path.display()))
+                                .and_then(|src| data::read_csv(&src, columns.len(), &path.display().to_string()));
+                            match read {
+                                Ok(c) => c.into_iter().map(|c| *list(c.into_iter().map(num).collect())).collect(),
+                                Err(e) => {
+                                    self.errs.push(e);
+                                    continue;
+                                },
+                            }
+                        },
+                        parse::TableSource::Columns(c) => c.into_inner().into_iter().map(|a| qualify(&convert_expr(a), &scopes, &[])).collect(),
+                    };
+                    if let Err(e) = self.ctx.def_table(&format!("{prefix}{name}"), columns, values) {
+                        self.errs.push(e);
+                    }
+                },
+                parse::Statement::Fit { kw_fit: _, name, params, colon: _, lhs, tilde: _, rhs, kw_semi: _ } => {
+                    // The parameters are defined in this scope, so they're qualified like any other name.
+                    let params = params.into_inner().iter().map(|n| format!("{prefix}{n}")).collect();
+                    let lhs = qualify(&convert_expr(lhs), &scopes, &[]);
+                    let rhs = qualify(&convert_expr(rhs), &scopes, &[]);
+                    if let Err(e) = self.ctx.def_fit(&format!("{prefix}{name}"), params, lhs, rhs) {
+                        self.errs.push(e);
+                    }
+                },
+                parse::Statement::IncludeAs { kw_include: _, path, kw_as: _, name, kw_semi: _ } => {
+                    self.file(&dir.join(path.value()), &format!("{prefix}{name}::"));
+                },
+                parse::Statement::Include { kw_include: _, path, kw_semi: _ } => {
+                    self.file(&dir.join(path.value()), prefix);
+                },
+                parse::Statement::Mod { kw_mod: _, name, body } => {
+                    let body = body.into_inner().into_iter().collect();
+                    self.statements(body, dir, &scopes, &format!("{prefix}{name}::"));
+                },
             }
         }
     }
-    return c;
+
+    /// Defines a curve. `var` is the name used after `for`, which has to be the parameter.
+    fn curve(&mut self, name: &str, param: parsel::syn::Ident, var: parsel::syn::Ident, components: Vec<expr::Expr>, range: parse::Range, scopes: &[Scope]) {
+        if var != param {
+            self.errs.push(format!("ERROR: curve {name} is defined with {param} but ranges over {var}!"));
+            return;
+        }
+        let param = param.to_string();
+        let args = [param.clone()];
+        let components = components.iter().map(|a| qualify(a, scopes, &args)).collect();
+        // The range can't use the parameter, so it isn't shadowed there.
+        let from = qualify(&convert_expr(*range.from), scopes, &[]);
+        let to = qualify(&convert_expr(*range.to), scopes, &[]);
+        if let Err(e) = self.ctx.def_curve(name, param, components, from, to) {
+            self.errs.push(e);
+        }
+    }
 }
 
-fn convert_expr(e: parse::Expr) -> Box<expr::Expr> {
+/// The names `statements` define, along with the ones defined by files they include without
+/// a name, which end up in the same scope. `seen` are the files already looked at, so cycles
+/// stop (they're reported when the files are converted).
+fn defined_names(statements: &[parse::Statement], dir: &Path, seen: &mut Vec<PathBuf>) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for s in statements {
+        match s {
+            parse::Statement::Let { name, .. }
+            | parse::Statement::Fn { name, .. }
+            | parse::Statement::Curve { name, .. }
+            | parse::Statement::Polar { name, .. }
+            | parse::Statement::Surface { name, .. }
+            | parse::Statement::Implicit { name, .. }
+            | parse::Statement::IncludeAs { name, .. }
+            | parse::Statement::Mod { name, .. } => { names.insert(name.to_string()); },
+            parse::Statement::Table { name, columns, .. } => names.extend([name].into_iter().chain(columns.iter()).map(|n| n.to_string())),
+            parse::Statement::Fit { name, params, .. } => names.extend([name].into_iter().chain(params.iter()).map(|n| n.to_string())),
+            parse::Statement::Include { path, .. } => {
+                // * missing and broken files are reported when they're converted
+                let Ok(full) = dir.join(path.value()).canonicalize() else { continue };
+                if seen.contains(&full) { continue }
+                let Ok(src) = fs::read_to_string(&full) else { continue };
+                let Ok(included) = parse::try_parse(&src) else { continue };
+                seen.push(full.clone());
+                names.extend(defined_names(&included, full.parent().unwrap(), seen));
+            },
+        }
+    }
+    names
+}
+
+/// Names defined in one file or `mod` block.
+#[derive(Clone)]
+struct Scope {
+    /// What's put in front of the names to qualify them.
+    prefix: String,
+    local: HashSet<String>,
+}
+
+/// Qualifies every name whose first part is defined in one of `scopes`, searching the
+/// innermost scope first. Names in `args` are function arguments and shadow everything.
+fn qualify(e: &expr::Expr, scopes: &[Scope], args: &[String]) -> expr::Expr {
+    e.rename(&|n| {
+        if args.iter().any(|a| a == n) { return None }
+        let first = n.split("::").next().unwrap();
+        scopes.iter().rev().find(|s| s.local.contains(first)).map(|s| format!("{}{n}", s.prefix))
+    })
+}
+
+pub fn convert_expr(e: parse::Expr) -> Box<expr::Expr> {
     match e {
         LeftAssoc::Binary { lhs, op, rhs } => {
             let a = convert_expr(*lhs);
-            let b = convert_expr(parsel::ast::LeftAssoc::Rhs(rhs));
+            let b = convert_product(rhs);
             match op {
                 parse::AddOp::Add(_) => add(a, b),
                 parse::AddOp::Sub(_) => sub(a, b),
             }
         },
-        LeftAssoc::Rhs(e) => {
-            match e {
-                LeftAssoc::Binary { lhs, op, rhs } => {
-                    let a = convert_expr(parsel::ast::LeftAssoc::Rhs(*lhs));
-                    let b = convert_expr(parsel::ast::LeftAssoc::Rhs(parsel::ast::LeftAssoc::Rhs(rhs)));
-                    match op {
-                        parse::MulOp::Mul(_) => mul(a, b),
-                        parse::MulOp::Div(_) => div(a, b),
-                    }
+        LeftAssoc::Rhs(e) => convert_product(e),
+    }
+}
+
+fn convert_product(e: parse::Product) -> Box<expr::Expr> {
+    match e {
+        LeftAssoc::Binary { lhs, op, rhs } => {
+            let a = convert_product(*lhs);
+            let b = convert_factor(rhs);
+            match op {
+                parse::MulOp::Mul(_) => mul(a, b),
+                parse::MulOp::Div(_) => div(a, b),
+                parse::MulOp::Implicit(_) => mul(a, b),
+            }
+        },
+        LeftAssoc::Rhs(e) => convert_factor(e),
+    }
+}
+
+fn convert_factor(e: parse::Factor) -> Box<expr::Expr> {
+    match e {
+        RightAssoc::Binary { lhs, op, rhs } => {
+            let b = convert_factor(*rhs);
+            let a = match lhs {
+                // `2x^2` is `2 * x^2`, not `(2x)^2`
+                parse::Postfix { node: parse::Node::Term(parse::Term::Suffixed(s)), bang } => {
+                    return mul(term(expr::Term::Rational(s.value())), pow(postfix(term(suffix_term(&s)), bang), b));
                 },
-                LeftAssoc::Rhs(e) => {
-                    match e {
-                        LeftAssoc::Binary { lhs, op, rhs } => {
-                            let a = convert_expr(parsel::ast::LeftAssoc::Rhs(parsel::ast::LeftAssoc::Rhs(*lhs)));
-                            let b = convert_expr(parsel::ast::LeftAssoc::Rhs(parsel::ast::LeftAssoc::Rhs(parsel::ast::LeftAssoc::Rhs(rhs))));
-                            match op {
-                                parse::PowOp::Pow(_) => pow(a, b),
-                            }
-                        },
-                        LeftAssoc::Rhs(n) => convert_node(n),
-                    }
-                    
+                parse::Postfix { node: parse::Node::SuffixedCall(c), bang } => {
+                    return mul(term(expr::Term::Rational(c.number.value())), pow(postfix(convert_node(*c.call), bang), b));
                 },
+                lhs => convert_postfix(lhs),
+            };
+            match op {
+                parse::PowOp::Pow(_) => pow(a, b),
             }
         },
+        RightAssoc::Lhs(n) => convert_postfix(n),
     }
 }
 
+fn convert_postfix(p: parse::Postfix) -> Box<expr::Expr> {
+    match p.node {
+        // `2x!` is `2 * x!`, like `2x^2`
+        parse::Node::Term(parse::Term::Suffixed(s)) if !s.is_imaginary() && p.bang.is_some() => {
+            mul(term(expr::Term::Rational(s.value())), factorial(term(suffix_term(&s))))
+        },
+        parse::Node::SuffixedCall(c) if p.bang.is_some() => {
+            mul(term(expr::Term::Rational(c.number.value())), factorial(convert_node(*c.call)))
+        },
+        n => postfix(convert_node(n), p.bang),
+    }
+}
+
+/// `a!` if there's a `!`, otherwise `a`.
+fn postfix(a: Box<expr::Expr>, bang: parsel::ast::Maybe<parsel::syn::Token![!]>) -> Box<expr::Expr> {
+    if bang.is_some() { factorial(a) } else { a }
+}
+
 fn convert_node(n: parse::Node) -> Box<expr::Expr> {
     match n {
-        parse::Node::Ln(_, a) => panic!()/*ln(convert_expr(*a.into_inner()))*/,
-        parse::Node::Log(_, a, b) => panic!()/*log(convert_expr(*a.into_inner()), convert_expr(*b.into_inner()))*/,
-        parse::Node::Root(_, a, b) => panic!()/*root(convert_expr(*a.into_inner()), convert_expr(*b.into_inner()))*/,
-        parse::Node::Sqrt(_, a) => panic!()/*sqrt(convert_expr(*a.into_inner()))*/,
-        parse::Node::Cbrt(_, a) => panic!()/*cbrt(convert_expr(*a.into_inner()))*/,
-        parse::Node::Sin(_, a) => panic!()/*sin(convert_expr(*a.into_inner()))*/,
-        parse::Node::Cos(_, a) => panic!()/*cos(convert_expr(*a.into_inner()))*/,
-        parse::Node::Tan(_, a) => panic!()/*tan(convert_expr(*a.into_inner()))*/,
-        parse::Node::Sinh(_, a) => panic!()/*sinh(convert_expr(*a.into_inner()))*/,
-        parse::Node::Cosh(_, a) => panic!()/*cosh(convert_expr(*a.into_inner()))*/,
-        parse::Node::Tanh(_, a) => panic!()/*tanh(convert_expr(*a.into_inner()))*/,
-        parse::Node::Asin(_, a) => panic!()/*asin(convert_expr(*a.into_inner()))*/,
-        parse::Node::Acos(_, a) => panic!()/*acos(convert_expr(*a.into_inner()))*/,
-        parse::Node::Atan(_, a) => panic!()/*atan(convert_expr(*a.into_inner()))*/,
-        parse::Node::Asinh(_, a) => panic!()/*asinh(convert_expr(*a.into_inner()))*/,
-        parse::Node::Acosh(_, a) => panic!()/*acosh(convert_expr(*a.into_inner()))*/,
-        parse::Node::Atanh(_, a) => panic!()/*atanh(convert_expr(*a.into_inner()))*/,
-        parse::Node::Neg(_, a) => neg(convert_expr(*a)),
-        parse::Node::Abs(_, a, _) => panic!()/*abs(convert_expr(*a))*/,
+        parse::Node::Ln(_, a) => ln(convert_expr(*a.into_inner())),
+        parse::Node::Log(_, a, b) => log(convert_expr(*a.into_inner()), convert_expr(*b.into_inner())),
+        parse::Node::Root(_, a, b) => root(convert_expr(*a.into_inner()), convert_expr(*b.into_inner())),
+        parse::Node::Sqrt(_, a) => sqrt(convert_expr(*a.into_inner())),
+        parse::Node::Cbrt(_, a) => cbrt(convert_expr(*a.into_inner())),
+        parse::Node::Sin(_, a) => sin(convert_expr(*a.into_inner())),
+        parse::Node::Cos(_, a) => cos(convert_expr(*a.into_inner())),
+        parse::Node::Tan(_, a) => tan(convert_expr(*a.into_inner())),
+        parse::Node::Sinh(_, a) => sinh(convert_expr(*a.into_inner())),
+        parse::Node::Cosh(_, a) => cosh(convert_expr(*a.into_inner())),
+        parse::Node::Tanh(_, a) => tanh(convert_expr(*a.into_inner())),
+        parse::Node::Asin(_, a) => asin(convert_expr(*a.into_inner())),
+        parse::Node::Acos(_, a) => acos(convert_expr(*a.into_inner())),
+        parse::Node::Atan(_, a) => atan(convert_expr(*a.into_inner())),
+        parse::Node::Asinh(_, a) => asinh(convert_expr(*a.into_inner())),
+        parse::Node::Acosh(_, a) => acosh(convert_expr(*a.into_inner())),
+        parse::Node::Atanh(_, a) => atanh(convert_expr(*a.into_inner())),
+        parse::Node::Neg(_, a) => neg(convert_factor(*a)),
+        parse::Node::Abs(_, a, _) => abs(convert_expr(*a)),
+        parse::Node::AbsFn(_, a) => abs(convert_expr(*a.into_inner())),
+        parse::Node::Re(_, a) => re(convert_expr(*a.into_inner())),
+        parse::Node::Im(_, a) => im(convert_expr(*a.into_inner())),
+        parse::Node::Arg(_, a) => arg(convert_expr(*a.into_inner())),
+        parse::Node::Conj(_, a) => conj(convert_expr(*a.into_inner())),
+        parse::Node::Polar(_, p) => {
+            let p = p.into_inner();
+            // `r e^(i theta)`, so `polar(1, pi)` is exactly -1.
+            let (r, theta) = (convert_expr(*p.a), convert_expr(*p.b));
+            mul(r, pow(term(expr::Term::Const(Constant::E)), mul(term(expr::Term::Const(Constant::I)), theta)))
+        },
+        parse::Node::Gamma(_, a) => gamma(convert_expr(*a.into_inner())),
+        parse::Node::Lgamma(_, a) => lgamma(convert_expr(*a.into_inner())),
+        parse::Node::Digamma(_, a) => digamma(convert_expr(*a.into_inner())),
+        parse::Node::Factorial(_, a) => factorial(convert_expr(*a.into_inner())),
+        parse::Node::Beta(_, p) => {
+            let p = p.into_inner();
+            beta(convert_expr(*p.a), convert_expr(*p.b))
+        },
+        parse::Node::Erf(_, a) => erf(convert_expr(*a.into_inner())),
+        parse::Node::Erfc(_, a) => erfc(convert_expr(*a.into_inner())),
+        parse::Node::BesselJ(_, n, a) => besselj(convert_expr(*n.into_inner()), convert_expr(*a.into_inner())),
+        parse::Node::BesselY(_, n, a) => bessely(convert_expr(*n.into_inner()), convert_expr(*a.into_inner())),
+        parse::Node::Zeta(_, a) => zeta(convert_expr(*a.into_inner())),
+        parse::Node::Floor(_, a) => floor(convert_expr(*a.into_inner())),
+        parse::Node::Ceil(_, a) => ceil(convert_expr(*a.into_inner())),
+        parse::Node::Round(_, a) => round(convert_expr(*a.into_inner())),
+        parse::Node::Sign(_, a) => sign(convert_expr(*a.into_inner())),
+        parse::Node::Mod(_, p) => {
+            let p = p.into_inner();
+            modulo(convert_expr(*p.a), convert_expr(*p.b))
+        },
+        parse::Node::Gcd(_, a) => reduce(expr::Reducer::Gcd, convert_args(a.into_inner())),
+        parse::Node::Lcm(_, a) => reduce(expr::Reducer::Lcm, convert_args(a.into_inner())),
+        parse::Node::NCr(_, p) => {
+            let p = p.into_inner();
+            ncr(convert_expr(*p.a), convert_expr(*p.b))
+        },
+        parse::Node::NPr(_, p) => {
+            let p = p.into_inner();
+            npr(convert_expr(*p.a), convert_expr(*p.b))
+        },
+        parse::Node::Sum(_, s) => {
+            let s = s.into_inner();
+            sum(s.var.to_string(), convert_expr(*s.from), convert_expr(*s.to), convert_expr(*s.body))
+        },
+        parse::Node::Prod(_, s) => {
+            let s = s.into_inner();
+            prod(s.var.to_string(), convert_expr(*s.from), convert_expr(*s.to), convert_expr(*s.body))
+        },
+        parse::Node::Total(_, a) => reduce(expr::Reducer::Total, convert_expr(*a.into_inner())),
+        parse::Node::Mean(_, a) => reduce(expr::Reducer::Mean, convert_expr(*a.into_inner())),
+        parse::Node::Len(_, a) => reduce(expr::Reducer::Len, convert_expr(*a.into_inner())),
+        parse::Node::Max(_, a) => reduce(expr::Reducer::Max, convert_args(a.into_inner())),
+        parse::Node::Min(_, a) => reduce(expr::Reducer::Min, convert_args(a.into_inner())),
+        parse::Node::Range(r) => {
+            let r = r.into_inner();
+            range(convert_expr(*r.from), convert_expr(*r.to))
+        },
+        parse::Node::List(n) => list(n.into_inner().into_iter().map(convert_expr).collect()),
+        parse::Node::Vec(_, n) => vector(n.into_inner().into_iter().map(convert_expr).collect()),
+        parse::Node::Mat(_, m) => matrix(m.into_inner().into_iter().map(|r| r.into_inner().into_iter().map(convert_expr).collect()).collect()),
+        parse::Node::Dot(_, p) => {
+            let p = p.into_inner();
+            dot(convert_expr(*p.a), convert_expr(*p.b))
+        },
+        parse::Node::Cross(_, p) => {
+            let p = p.into_inner();
+            cross(convert_expr(*p.a), convert_expr(*p.b))
+        },
+        parse::Node::Det(_, a) => det(convert_expr(*a.into_inner())),
+        parse::Node::Inv(_, a) => inverse(convert_expr(*a.into_inner())),
+        parse::Node::Transpose(_, a) => transpose(convert_expr(*a.into_inner())),
         parse::Node::Fn(name, args) => func(name.to_string(), args.into_inner().into_iter().map(|a| convert_expr(a)).collect()),
         parse::Node::Paren(a) => convert_expr(*a.into_inner()),
+        parse::Node::Term(parse::Term::Suffixed(s)) => mul(term(expr::Term::Rational(s.value())), term(suffix_term(&s))),
+        parse::Node::SuffixedCall(c) => mul(term(expr::Term::Rational(c.number.value())), convert_node(*c.call)),
         parse::Node::Term(t) => Box::new(expr::Expr::Term(convert_term(t))),
     }
 }
 
+/// Converts the inputs of `max`, `min`, `gcd` and `lcm`. Multiple inputs are the same as a list of them.
+fn convert_args(args: parsel::ast::Punctuated<parse::Expr, parsel::syn::token::Comma>) -> Box<expr::Expr> {
+    let mut args: Vec<Box<expr::Expr>> = args.into_iter().map(convert_expr).collect();
+    if args.len() == 1 {
+        return args.remove(0);
+    }
+    list(args)
+}
+
+/// The term a suffixed number is multiplied by.
+fn suffix_term(s: &parse::Suffixed) -> expr::Term {
+    if s.is_imaginary() {
+        return expr::Term::Const(Constant::I);
+    }
+    s.suffix().to_string().into()
+}
+
 fn convert_term(t: parse::Term) -> expr::Term {
     match t {
-        parse::Term::Var(ident) => expr::Term::Var(ident.to_string()),
-        parse::Term::Float(lit_float) => expr::Term::Real(lit_float.into_inner().to_f64().unwrap()),
-        parse::Term::Int(lit_int) => expr::Term::Real(lit_int.into_inner().to_f64().unwrap()),
+        parse::Term::Suffixed(_) => unreachable!("suffixed numbers are multiplications, see `convert_node`"),
+        parse::Term::Var(path) => expr::Term::Var(path.to_string()),
+        parse::Term::Number(n) => expr::Term::Rational(n.value()),
     }
 }
\ No newline at end of file
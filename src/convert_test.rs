@@ -0,0 +1,91 @@
+use crate::convert;
+use crate::expr::Context;
+use crate::test_util::{int, out, program};
+
+
+#[test]
+fn programs_see_the_constants() {
+    let tau = out("let out = 2 pi;").evaluate(&Default::default(), &Default::default());
+    assert_eq!(tau.re, std::f64::consts::TAU);
+    assert_eq!(out("let out = e^(i*pi);"), int(-1));
+    assert_eq!(out("let out = sin(pi);"), int(0));
+    assert_eq!(out("let out = floor(pi);"), int(3));
+}
+
+/// Writes `files` into a new directory under the system's temporary one and converts the first.
+fn files(test: &str, files: &[(&str, &str)]) -> Result<Context, Vec<String>> {
+    let dir = std::env::temp_dir().join(format!("kesmos-{test}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, src) in files {
+        std::fs::write(dir.join(name), src).unwrap();
+    }
+    let r = convert::convert_file(dir.join(files[0].0));
+    std::fs::remove_dir_all(&dir).unwrap();
+    r
+}
+
+#[test]
+fn syntax_errors_in_includes() {
+    let errs = files("syntax", &[("main.ksm", "include \"lib.ksm\";"), ("lib.ksm", "let a = 1;\nlet b = ;")]).unwrap_err();
+    assert_eq!(errs.len(), 1);
+    assert!(errs[0].contains("couldn't parse") && errs[0].contains("lib.ksm") && errs[0].contains("line 2"), "{errs:?}");
+}
+
+#[test]
+fn includes_inside_named_scopes() {
+    let ctx = files("scopes", &[
+        ("main.ksm", "include \"lib.ksm\" as geom; let out = geom::area(3);"),
+        ("lib.ksm", "include \"helpers.ksm\"; fn area(r) = helper(r);"),
+        ("helpers.ksm", "fn helper(r) = 2 r;"),
+    ]).unwrap();
+    assert_eq!(ctx.simplify_for_var("out").0, int(6));
+}
+
+#[test]
+fn ranges_need_constant_bounds() {
+    assert_eq!(out("let n = 4; let out = total([1..n]);"), int(10));
+    assert_eq!(out("fn f(n) = total([1..n]); let out = f(4);"), int(10));
+    for src in ["let out = [1..x];", "fn f(n) = [1..n]; let out = f(x);", "let out = sum(k, 1, 3, total([1..k]));", "curve c(t) = (t, total([0..t])) for t in 0..1;"] {
+        let errs = program(src).unwrap_err();
+        assert!(errs[0].contains("bounds of a range"), "{src}: {errs:?}");
+    }
+    // Ranges are written out, so they can't be too long.
+    assert_eq!(out("let out = len([1..1000]);"), int(1000));
+    for src in ["let out = total([1..1000000]);", "fn f(n) = [1..n]; let out = f(10^9);", "let out = [[0, -1e30]..2];", "let out = [1..1/0];"] {
+        let errs = program(src).unwrap_err();
+        assert!(errs[0].contains("too long"), "{src}: {errs:?}");
+    }
+}
+
+#[test]
+fn reducers_of_empty_lists() {
+    assert_eq!(out("let out = total([]);"), int(0));
+    assert_eq!(out("let out = len([5..1]);"), int(0));
+    for src in ["let out = mean([5..1]);", "let out = max([]);", "let out = gcd([1, 2] + []);", "fn f(a) = lcm(a); let out = f([]);"] {
+        let errs = program(src).unwrap_err();
+        assert!(errs[0].contains("empty list"), "{src}: {errs:?}");
+    }
+}
+
+#[test]
+fn undefined_functions() {
+    for src in ["let out = g(1, 2);", "fn f(x) = g(x, x); let out = 1;", "curve c(t) = (t, g(t, 1)) for t in 0..1;"] {
+        let errs = program(src).unwrap_err();
+        assert_eq!(errs, ["ERROR: function g is not defined!"], "{src}");
+    }
+    // With one input it's a multiplication.
+    assert_eq!(out("let g = 2; let out = g(3);"), int(6));
+}
+
+#[test]
+fn numbers_in_front_of_functions() {
+    assert_eq!(out("let out = 2sin(pi/2);"), int(2));
+    assert_eq!(out("fn f(x) = x^2; let out = 2f(3);"), int(18));
+    assert_eq!(out("fn f(x) = x^2; let out = 2f(3)^2;"), int(162));
+    // An input named like a function hides it.
+    assert_eq!(out("fn f(x) = x^2; fn g(f) = 2f; let out = g(3);"), int(6));
+    for src in ["fn f(x) = x; let out = 2f;", "fn f(x) = x; curve c(t) = (t, f) for t in 0..1;"] {
+        let errs = program(src).unwrap_err();
+        assert_eq!(errs, ["ERROR: function f is used without inputs!"], "{src}");
+    }
+}
@@ -0,0 +1,205 @@
+/*
+This is where data tables are read and regressions are fitted to them.
+
+A table is a few columns of numbers, either written out as lists or read from a CSV
+file. A regression like `y1 ~ m x1 + b` picks the parameters (`m` and `b`) that make
+the sum of the squared residuals `y1 - (m x1 + b)` as small as it can be.
+
+Fitting uses the Levenberg-Marquardt method, which starts with every parameter at 1
+and takes steps somewhere between gradient descent (when far away) and Gauss-Newton
+(when close). The derivatives come from `Expr::derivative`, with finite differences
+for the ones that can't be differentiated. Models that are linear in the parameters
+are solved in a few steps, but others can find a local minimum instead of the best
+one, just like other graphing calculators.
+*/
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use num_complex::Complex64;
+
+use crate::expr::{Context, Expr, Func};
+
+/// Where every parameter starts.
+const START: f64 = 1.0;
+/// The most steps fitting takes.
+const MAX_STEPS: usize = 500;
+/// How much damping the first step has, relative to the curvature.
+const INITIAL_DAMPING: f64 = 1e-3;
+/// Past this much damping the steps are too small to make a difference.
+const MAX_DAMPING: f64 = 1e16;
+/// How small a step has to be (relative to the parameters) for the fit to be done.
+const STEP_TOLERANCE: f64 = 1e-13;
+/// The step used for finite differences, relative to the parameter.
+const DIFF_STEP: f64 = 1e-7;
+
+/// The columns of a CSV file with `width` columns. The first line is skipped when it
+/// isn't all numbers, since it's probably the names of the columns.
+pub fn read_csv(src: &str, width: usize, path: &str) -> Result<Vec<Vec<f64>>, String> {
+    let mut columns = vec![Vec::new(); width];
+    let first = src.lines().position(|l| !l.trim().is_empty());
+    for (i, line) in src.lines().enumerate() {
+        if line.trim().is_empty() { continue }
+        let cells: Vec<&str> = line.split(',').map(|c| c.trim()).collect();
+        let values: Result<Vec<f64>, &str> = cells.iter().map(|c| c.parse().map_err(|_| *c)).collect();
+        let values = match values {
+            Ok(v) => v,
+            Err(_) if Some(i) == first => continue,
+            Err(c) => return Err(format!("ERROR: line {} of {path} has {c:?}, which isn't a number!", i + 1)),
+        };
+        if values.len() != width {
+            return Err(format!("ERROR: line {} of {path} has {} values but the table has {width} columns!", i + 1, values.len()));
+        }
+        for (c, v) in columns.iter_mut().zip(values) {
+            c.push(v);
+        }
+    }
+    Ok(columns)
+}
+
+/// A fitted regression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    /// The fitted value of each parameter, in the order they were written.
+    pub params: Vec<(String, f64)>,
+    /// The value of the model at each point.
+    pub fitted: Vec<f64>,
+    /// How far each point is above the model.
+    pub residuals: Vec<f64>,
+    /// The coefficient of determination, how much of the spread of the data the model explains.
+    pub r_squared: f64,
+}
+impl Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (p, v) in &self.params {
+            writeln!(f, "{p} = {v}")?;
+        }
+        writeln!(f, "R^2 = {}", self.r_squared)?;
+        writeln!(f, "fitted = {:?}", self.fitted)?;
+        write!(f, "residuals = {:?}", self.residuals)
+    }
+}
+
+/// Fits the regression `name` in `ctx`. Only real parts are used.
+pub fn regression(ctx: &Context, name: &str) -> Result<Regression, String> {
+    let fit = ctx.get_fit(name).ok_or_else(|| format!("ERROR: fit {name} is not defined!"))?;
+    let params = fit.params();
+    let (lhs, residuals, funcs) = ctx.simplify_fit(name);
+    if let Some(v) = residuals.free_vars().into_iter().find(|v| !params.contains(v)) {
+        return Err(format!("ERROR: fit {name} uses {v}, which isn't defined!"));
+    }
+    let residuals = residuals.items();
+    if residuals.len() < params.len() {
+        return Err(format!("ERROR: fit {name} has {} parameters but only {} points!", params.len(), residuals.len()));
+    }
+
+    let values = least_squares(&residuals, params, &funcs).ok_or_else(|| format!("ERROR: fit {name} isn't a number with its parameters at {START}!"))?;
+    let vars: HashMap<String, Complex64> = params.iter().cloned().zip(values.iter().map(|v| Complex64::from(*v))).collect();
+    let r: Vec<f64> = residuals.iter().map(|e| e.evaluate(&vars, &funcs).re).collect();
+    // * a single value on the left is compared with every point
+    let y: Vec<f64> = lhs.items().iter().map(|e| e.evaluate(&vars, &funcs).re).cycle().take(r.len()).collect();
+
+    let mean = y.iter().sum::<f64>() / y.len() as f64;
+    let total: f64 = y.iter().map(|y| (y - mean).powi(2)).sum();
+    let unexplained: f64 = r.iter().map(|r| r * r).sum();
+    Ok(Regression {
+        params: params.iter().cloned().zip(values).collect(),
+        fitted: y.iter().zip(&r).map(|(y, r)| y - r).collect(),
+        residuals: r,
+        r_squared: 1.0 - unexplained / total,
+    })
+}
+
+/// The values of `params` that make the sum of the squares of `residuals` smallest, or
+/// `None` if they aren't numbers where fitting starts.
+pub fn least_squares(residuals: &[Expr], params: &[String], funcs: &HashMap<String, Func>) -> Option<Vec<f64>> {
+    let slopes: Vec<Vec<Option<Expr>>> = residuals.iter().map(|r| params.iter().map(|p| r.derivative(p)).collect()).collect();
+    let at = |p: &[f64]| -> HashMap<String, Complex64> {
+        params.iter().cloned().zip(p.iter().map(|v| Complex64::from(*v))).collect()
+    };
+    let eval = |p: &[f64]| -> Vec<f64> {
+        let vars = at(p);
+        residuals.iter().map(|e| e.evaluate(&vars, funcs).re).collect()
+    };
+    let cost = |r: &[f64]| r.iter().map(|r| r * r).sum::<f64>();
+
+    let mut p = vec![START; params.len()];
+    let mut r = eval(&p);
+    let mut c = cost(&r);
+    if !c.is_finite() { return None }
+
+    let mut damping = INITIAL_DAMPING;
+    for _ in 0..MAX_STEPS {
+        // The slope of each residual along each parameter.
+        let vars = at(&p);
+        let mut j = vec![vec![0.0; p.len()]; r.len()];
+        for (i, row) in slopes.iter().enumerate() {
+            for (k, slope) in row.iter().enumerate() {
+                j[i][k] = match slope {
+                    Some(d) => d.evaluate(&vars, funcs).re,
+                    None => {
+                        let h = DIFF_STEP * p[k].abs().max(1.0);
+                        let mut q = p.clone();
+                        q[k] += h;
+                        (residuals[i].evaluate(&at(&q), funcs).re - r[i]) / h
+                    },
+                };
+            }
+        }
+
+        // The normal equations, J^T J step = -J^T r.
+        let n = p.len();
+        let a: Vec<Vec<f64>> = (0..n).map(|k| (0..n).map(|l| j.iter().map(|row| row[k] * row[l]).sum()).collect()).collect();
+        let g: Vec<f64> = (0..n).map(|k| -j.iter().zip(&r).map(|(row, r)| row[k] * r).sum::<f64>()).collect();
+
+        // Damp the step until it makes things better.
+        let better = loop {
+            let mut damped = a.clone();
+            for k in 0..n {
+                // * Marquardt's scaling, so parameters of different sizes are damped alike
+                damped[k][k] += damping * a[k][k].max(f64::MIN_POSITIVE);
+            }
+            if let Some(step) = solve(damped, g.clone()) {
+                let q: Vec<f64> = p.iter().zip(&step).map(|(p, s)| p + s).collect();
+                let rq = eval(&q);
+                let cq = cost(&rq);
+                if cq <= c {
+                    damping = (damping / 10.0).max(f64::EPSILON);
+                    break Some((q, rq, cq, step));
+                }
+            }
+            damping *= 10.0;
+            if damping > MAX_DAMPING { break None }
+        };
+        let Some((q, rq, cq, step)) = better else { break };
+        let small = step.iter().zip(&p).all(|(s, p)| s.abs() <= STEP_TOLERANCE * (p.abs() + STEP_TOLERANCE));
+        (p, r, c) = (q, rq, cq);
+        if small | (c == 0.0) { break }
+    }
+    Some(p)
+}
+
+/// Solves `a x = b` by Gaussian elimination, or `None` if `a` is singular.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col] == 0.0 || !a[pivot][col].is_finite() { return None }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let top = a[col].clone();
+        for row in col + 1..n {
+            let factor = a[row][col] / top[col];
+            for (x, t) in a[row][col..].iter_mut().zip(&top[col..]) {
+                *x -= factor * t;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let rest: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+    Some(x)
+}
@@ -0,0 +1,114 @@
+use crate::data;
+use crate::expr::{f::*, Context, Expr};
+use crate::test_util::var;
+
+
+fn column(v: &[f64]) -> Expr {
+    *list(v.iter().map(|v| num(*v)).collect())
+}
+
+/// A context with the table `t(x1, y1)`.
+fn table(x: &[f64], y: &[f64]) -> Context {
+    let mut ctx = Context::new();
+    ctx.def_table("t", vec!["x1".to_string(), "y1".to_string()], vec![column(x), column(y)]).unwrap();
+    ctx
+}
+
+fn value(ctx: &Context, var: &str) -> f64 {
+    match ctx.get_var(var) {
+        Some(Expr::Term(t)) => t.as_complex().re,
+        v => panic!("{var} is {v:?}"),
+    }
+}
+
+
+#[test]
+fn reading_csv() {
+    let src = "x, y\n1, 2\n\n3,4.5\n-1e2 , 0\n";
+    assert_eq!(data::read_csv(src, 2, "a.csv"), Ok(vec![vec![1.0, 3.0, -100.0], vec![2.0, 4.5, 0.0]]));
+    // Without names.
+    assert_eq!(data::read_csv("1,2\n3,4", 2, "a.csv"), Ok(vec![vec![1.0, 3.0], vec![2.0, 4.0]]));
+    assert!(data::read_csv("1,2\n3,four", 2, "a.csv").unwrap_err().contains("line 2"));
+    assert!(data::read_csv("1,2\n3,4,5", 2, "a.csv").unwrap_err().contains("3 values"));
+}
+
+#[test]
+fn straight_lines() {
+    // Exactly on a line.
+    let mut ctx = table(&[0.0, 1.0, 2.0, 3.0], &[1.0, 3.0, 5.0, 7.0]);
+    ctx.def_fit("line", vec!["m".to_string(), "b".to_string()], *var("y1"), *add(mul(var("m"), var("x1")), var("b"))).unwrap();
+    ctx.fit().unwrap();
+    assert!((value(&ctx, "m") - 2.0).abs() < 1e-12);
+    assert!((value(&ctx, "b") - 1.0).abs() < 1e-12);
+    let r = ctx.get_fit("line").unwrap().regression().unwrap();
+    assert!((r.r_squared - 1.0).abs() < 1e-12);
+    assert!(r.residuals.iter().all(|r| r.abs() < 1e-12));
+
+    // Off the line, where the answer is known from the usual formulas.
+    let x = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+    let y = [1.1, 2.3, 2.8, 4.4, 4.9, 6.2];
+    let mut ctx = table(&x, &y);
+    ctx.def_fit("line", vec!["m".to_string(), "b".to_string()], *var("y1"), *add(mul(var("m"), var("x1")), var("b"))).unwrap();
+    ctx.fit().unwrap();
+    let n = x.len() as f64;
+    let (mx, my) = (x.iter().sum::<f64>() / n, y.iter().sum::<f64>() / n);
+    let sxy: f64 = x.iter().zip(&y).map(|(x, y)| (x - mx) * (y - my)).sum();
+    let sxx: f64 = x.iter().map(|x| (x - mx).powi(2)).sum();
+    let syy: f64 = y.iter().map(|y| (y - my).powi(2)).sum();
+    assert!((value(&ctx, "m") - sxy / sxx).abs() < 1e-10);
+    assert!((value(&ctx, "b") - (my - sxy / sxx * mx)).abs() < 1e-10);
+    let r = ctx.get_fit("line").unwrap().regression().unwrap();
+    assert!((r.r_squared - sxy * sxy / (sxx * syy)).abs() < 1e-10);
+    for ((y, f), r) in y.iter().zip(&r.fitted).zip(&r.residuals) {
+        assert!((y - f - r).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn curves() {
+    // y = 3 e^(x/2), which isn't linear in the parameters.
+    let x: Vec<f64> = (0..10).map(|i| i as f64 / 2.0).collect();
+    let y: Vec<f64> = x.iter().map(|x| 3.0 * (x / 2.0).exp()).collect();
+    let mut ctx = table(&x, &y);
+    let model = mul(var("a"), pow(num(std::f64::consts::E), mul(var("k"), var("x1"))));
+    ctx.def_fit("growth", vec!["a".to_string(), "k".to_string()], *var("y1"), *model).unwrap();
+    ctx.fit().unwrap();
+    assert!((value(&ctx, "a") - 3.0).abs() < 1e-8);
+    assert!((value(&ctx, "k") - 0.5).abs() < 1e-8);
+
+    // Steps can't be differentiated, so their slopes are estimated.
+    let mut ctx = table(&[0.5, 1.5, 2.5, 3.5], &[2.0, 3.0, 4.0, 5.0]);
+    ctx.def_fit("steps", vec!["c".to_string()], *var("y1"), *add(floor(var("x1")), var("c"))).unwrap();
+    ctx.fit().unwrap();
+    assert!((value(&ctx, "c") - 2.0).abs() < 1e-8);
+}
+
+#[test]
+fn fits_use_each_other() {
+    let mut ctx = table(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0]);
+    // `second` needs `m` from `first`, even though it comes first alphabetically.
+    ctx.def_fit("second", vec!["c".to_string()], *var("y1"), *add(mul(var("m"), var("x1")), var("c"))).unwrap();
+    ctx.def_fit("first", vec!["m".to_string()], *var("y1"), *mul(var("m"), var("x1"))).unwrap();
+    ctx.fit().unwrap();
+    assert!((value(&ctx, "m") - 2.0).abs() < 1e-12);
+    assert!(value(&ctx, "c").abs() < 1e-12);
+}
+
+#[test]
+fn bad_fits() {
+    let fit = |params: &[&str], rhs: Box<Expr>| {
+        let mut ctx = table(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0]);
+        ctx.def_fit("f", params.iter().map(|p| p.to_string()).collect(), *var("y1"), *rhs)?;
+        ctx.fit().map_err(|e| e.join("\n"))
+    };
+    assert!(fit(&["m"], mul(var("m"), var("x"))).unwrap_err().contains("uses x"));
+    assert!(fit(&["a", "b", "c", "d"], add(add(var("a"), var("b")), add(var("c"), var("d")))).unwrap_err().contains("only 3 points"));
+    assert!(fit(&[], var("x1")).unwrap_err().contains("no parameters"));
+    // Parameters are names like any other.
+    assert!(fit(&["x1"], var("x1")).unwrap_err().contains("more than once"));
+
+    let mut ctx = table(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0]);
+    ctx.def_fit("f", vec!["a".to_string()], *var("y1"), *mul(var("a"), var("b"))).unwrap();
+    ctx.def_fit("g", vec!["b".to_string()], *var("y1"), *mul(var("a"), var("b"))).unwrap();
+    assert!(ctx.fit().unwrap_err()[0].contains("each other"));
+}
@@ -0,0 +1,185 @@
+/*
+This is where expressions are differentiated symbolically.
+
+`derivative` expects a simplified expression, where the only function calls left
+are recursive ones. Those can't be written out, so they can't be differentiated.
+Builtins that aren't complex differentiable (`abs`, `re`, `im`, `arg` and `conj`)
+are differentiated along the real line, which is what graphs are drawn over.
+*/
+
+use num_complex::Complex64;
+
+use crate::constant::Constant;
+use crate::expr::{f, Builtin, Expr, Series, Term};
+
+impl Expr {
+    /// The derivative of `self` with respect to `var`, or `None` if some part of it can't be
+    /// differentiated (recursive functions, `max`, `min`, `digamma`, `zeta`, the functions of
+    /// whole numbers like `floor` and series with bounds using `var`).
+    pub fn derivative(&self, var: &str) -> Option<Expr> {
+        Some(self.d(var)?.flatten().reduce_const())
+    }
+
+    fn d(&self, var: &str) -> Option<Expr> {
+        if !self.mentions(var) { return Some(zero()) }
+        match self {
+            // * the only term that mentions `var` is `var`
+            Self::Term(_) => Some(one()),
+            Self::Add(n) => Some(sum(n.iter().map(|a| a.d(var)).collect::<Option<Vec<Expr>>>()?)),
+            Self::Mul(n) => {
+                let mut terms = Vec::new();
+                for i in 0..n.len() {
+                    let mut factors: Vec<Expr> = n.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, a)| a.clone()).collect();
+                    factors.push(n[i].d(var)?);
+                    terms.push(product(factors));
+                }
+                Some(sum(terms))
+            },
+            Self::Pow(a, b) => {
+                // b * a^(b-1) * a'
+                if !b.mentions(var) {
+                    return Some(product(vec![*b.clone(), *f::pow(a.clone(), f::sub(b.clone(), f::num(1.0))), a.d(var)?]));
+                }
+                // a^b * (b' * ln(a) + b * a' / a)
+                Some(product(vec![self.clone(), sum(vec![
+                    product(vec![b.d(var)?, *f::ln(a.clone())]),
+                    product(vec![*b.clone(), a.d(var)?, *f::inv(a.clone())]),
+                ])]))
+            },
+            Self::Fn(_, _) => None,
+            Self::Builtin(b, n) => Self::d_builtin(*b, n, var),
+            Self::Series(s, k, a, b, n) => {
+                if a.mentions(var) | b.mentions(var) { return None }
+                let sum_of = |body: Expr| Self::Series(Series::Sum, k.clone(), a.clone(), b.clone(), body.r#box());
+                match s {
+                    Series::Sum => Some(sum_of(n.d(var)?)),
+                    // prod(n) * sum(n' / n)
+                    Series::Prod => Some(product(vec![self.clone(), sum_of(product(vec![n.d(var)?, *f::inv(n.clone())]))])),
+                }
+            },
+            Self::List(n) => Some(Self::List(n.iter().map(|a| a.d(var)).collect::<Option<Vec<Expr>>>()?)),
+            Self::Vector(n) => Some(Self::Vector(n.iter().map(|a| a.d(var)).collect::<Option<Vec<Expr>>>()?)),
+            Self::Matrix(m) => Some(Self::Matrix(
+                m.iter().map(|r| r.iter().map(|a| a.d(var)).collect::<Option<Vec<Expr>>>()).collect::<Option<Vec<Vec<Expr>>>>()?,
+            )),
+            Self::Range(_, _) | Self::Reduce(_, _) => None,
+        }
+    }
+
+    fn d_builtin(b: Builtin, n: &[Box<Expr>], var: &str) -> Option<Expr> {
+        let a = n[0].clone();
+        // The derivative of the builtin at `a`, to be multiplied by `a'`.
+        let outer = match b {
+            Builtin::Ln => f::inv(a),
+            Builtin::Sqrt => f::inv(f::mul(f::num(2.0), f::sqrt(a))),
+            Builtin::Cbrt => f::inv(f::mul(f::num(3.0), f::pow(f::cbrt(a), f::num(2.0)))),
+
+            Builtin::Sin => f::cos(a),
+            Builtin::Cos => f::neg(f::sin(a)),
+            Builtin::Tan => f::pow(f::cos(a), f::num(-2.0)),
+            Builtin::Sinh => f::cosh(a),
+            Builtin::Cosh => f::sinh(a),
+            Builtin::Tanh => f::pow(f::cosh(a), f::num(-2.0)),
+            Builtin::Asin => f::pow(f::sub(f::num(1.0), f::pow(a, f::num(2.0))), f::num(-0.5)),
+            Builtin::Acos => f::neg(f::pow(f::sub(f::num(1.0), f::pow(a, f::num(2.0))), f::num(-0.5))),
+            Builtin::Atan => f::inv(f::add(f::num(1.0), f::pow(a, f::num(2.0)))),
+            Builtin::Asinh => f::pow(f::add(f::pow(a, f::num(2.0)), f::num(1.0)), f::num(-0.5)),
+            Builtin::Acosh => f::inv(f::mul(f::sqrt(f::sub(a.clone(), f::num(1.0))), f::sqrt(f::add(a, f::num(1.0))))),
+            Builtin::Atanh => f::inv(f::sub(f::num(1.0), f::pow(a, f::num(2.0)))),
+
+            // These are written with `ln` and powers, which are differentiated above.
+            Builtin::Log => return f::div(f::ln(n[1].clone()), f::ln(a)).d(var),
+            // root{n}(x) / (n x), which stays real for odd roots of negative numbers
+            Builtin::Root if !a.mentions(var) => {
+                let x = n[1].clone();
+                return Some(product(vec![*f::div(f::root(a.clone(), x.clone()), f::mul(a, x)), n[1].d(var)?]));
+            },
+            Builtin::Root => return f::pow(n[1].clone(), f::inv(a)).d(var),
+
+            // re(conj(a) * a') / |a|
+            Builtin::Abs => return Some(product(vec![*f::re(f::mul(f::conj(a.clone()), n[0].d(var)?.r#box())), *f::inv(f::abs(a))])),
+            Builtin::Re => return Some(*f::re(n[0].d(var)?.r#box())),
+            Builtin::Im => return Some(*f::im(n[0].d(var)?.r#box())),
+            Builtin::Conj => return Some(*f::conj(n[0].d(var)?.r#box())),
+            // im(a' / a)
+            Builtin::Arg => return Some(*f::im(f::div(n[0].d(var)?.r#box(), a))),
+            // polar(r, t) = r * e^(i*t), so the derivative is (r' + i*r*t') * e^(i*t).
+            Builtin::Polar => {
+                let (r, t) = (&n[0], &n[1]);
+                let i = f::term(Complex64::I.into());
+                return Some(product(vec![
+                    sum(vec![r.d(var)?, product(vec![*i, *r.clone(), t.d(var)?])]),
+                    *f::polar(f::num(1.0), t.clone()),
+                ]));
+            },
+
+            Builtin::Gamma => f::mul(f::gamma(a.clone()), f::digamma(a)),
+            Builtin::Lgamma => f::digamma(a),
+            Builtin::Factorial => f::mul(f::factorial(a.clone()), f::digamma(f::add(a, f::num(1.0)))),
+            // beta(a, b) * (digamma(a) a' + digamma(b) b' - digamma(a + b) (a' + b'))
+            Builtin::Beta => {
+                let (a, b) = (&n[0], &n[1]);
+                let (da, db) = (a.d(var)?, b.d(var)?);
+                return Some(product(vec![*f::beta(a.clone(), b.clone()), sum(vec![
+                    product(vec![*f::digamma(a.clone()), da.clone()]),
+                    product(vec![*f::digamma(b.clone()), db.clone()]),
+                    product(vec![*f::num(-1.0), *f::digamma(f::add(a.clone(), b.clone())), sum(vec![da, db])]),
+                ])]));
+            },
+            // 2 / sqrt(pi) * e^(-a^2)
+            Builtin::Erf => erf_slope(a),
+            Builtin::Erfc => f::neg(erf_slope(a)),
+            // (J_(n-1)(x) - J_(n+1)(x)) / 2, and the same for Y. The order is a whole number,
+            // so nothing can be differentiated with respect to it.
+            Builtin::BesselJ | Builtin::BesselY => {
+                if a.mentions(var) { return None }
+                let (order, x) = (&n[0], &n[1]);
+                let at = |k: f64| Expr::Builtin(b, vec![f::add(order.clone(), f::num(k)), x.clone()]).r#box();
+                return Some(product(vec![*f::div(f::sub(at(-1.0), at(1.0)), f::num(2.0)), x.d(var)?]));
+            },
+            // There's nothing to write these with.
+            Builtin::Digamma | Builtin::Zeta => return None,
+
+            // Which input is the largest can change, and there's nothing to write that with.
+            Builtin::Max | Builtin::Min => return None,
+            // These jump, or are only defined for whole numbers.
+            Builtin::Floor | Builtin::Ceil | Builtin::Round | Builtin::Sign | Builtin::Mod
+            | Builtin::Gcd | Builtin::Lcm | Builtin::NCr | Builtin::NPr => return None,
+            Builtin::Dot | Builtin::Cross | Builtin::Det | Builtin::Inv | Builtin::Transpose => return None,
+        };
+        Some(product(vec![*outer, n[0].d(var)?]))
+    }
+}
+
+fn erf_slope(a: Box<Expr>) -> Box<Expr> {
+    let pi = f::term(Term::Const(Constant::Pi));
+    f::mul(f::mul(f::num(2.0), f::pow(pi, f::num(-0.5))), f::pow(f::term(Term::Const(Constant::E)), f::neg(f::pow(a, f::num(2.0)))))
+}
+
+fn zero() -> Expr {
+    *f::num(0.0)
+}
+fn one() -> Expr {
+    *f::num(1.0)
+}
+
+/// Adds up `n`, leaving out zeros so derivatives of constants don't pile up.
+fn sum(mut n: Vec<Expr>) -> Expr {
+    n.retain(|a| !a.is_zero());
+    match n.len() {
+        0 => zero(),
+        1 => n.pop().unwrap(),
+        _ => Expr::Add(n),
+    }
+}
+
+/// Multiplies `n`, leaving out ones and giving 0 if any of them are 0.
+fn product(mut n: Vec<Expr>) -> Expr {
+    if n.iter().any(|a| a.is_zero()) { return zero() }
+    n.retain(|a| !a.is_one());
+    match n.len() {
+        0 => one(),
+        1 => n.pop().unwrap(),
+        _ => Expr::Mul(n),
+    }
+}
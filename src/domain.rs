@@ -0,0 +1,169 @@
+/*
+This is where complex functions are drawn with domain colouring.
+
+Every pixel is a point `x` in the complex plane, coloured by the value of the
+function there: the argument picks the hue and the modulus the lightness, going
+from black at zero to white at infinity. Nothing in here needs a window.
+*/
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use num_complex::Complex64;
+
+use crate::expr::{Context, Expr, Func};
+
+/// An RGB image, stored row by row from the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 3]>,
+}
+impl Image {
+    /// Writes a binary PPM (`P6`) file.
+    pub fn write_ppm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        data.extend(self.pixels.iter().flatten());
+        fs::write(path, data)
+    }
+
+    /// Writes a PNG file.
+    pub fn write_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.png())
+    }
+
+    /// Encodes the image as a PNG. The image data is stored without compression.
+    pub fn png(&self) -> Vec<u8> {
+        // Every row starts with filter type 0 (none).
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            raw.push(0);
+            raw.extend(row.iter().flatten());
+        }
+
+        let mut header = Vec::new();
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bits per channel, truecolour, default compression, filter and no interlacing.
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+        chunk(&mut png, b"IHDR", &header);
+        chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+/// Appends a PNG chunk.
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(b) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8);
+        out.extend((b.len() as u16).to_le_bytes());
+        out.extend((!(b.len() as u16)).to_le_bytes());
+        out.extend(b);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// The CRC-32 PNG chunks end with.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// The Adler-32 checksum zlib streams end with.
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for d in data {
+        a = (a + *d as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Simplifies `var` and colours it over the rectangle `re` by `im` of the complex plane,
+/// with `x` going over the plane. `contours` adds bands where the modulus doubles.
+pub fn colour_var(ctx: &Context, var: &str, re: RangeInclusive<f64>, im: RangeInclusive<f64>, width: usize, height: usize, contours: bool) -> Image {
+    let (e, funcs) = ctx.simplify_for_var(var);
+    colour(&e, &funcs, re, im, width, height, contours)
+}
+
+/// Colours `e` as a function of `x` over the rectangle `re` by `im` of the complex plane.
+pub fn colour(e: &Expr, funcs: &HashMap<String, Func>, re: RangeInclusive<f64>, im: RangeInclusive<f64>, width: usize, height: usize, contours: bool) -> Image {
+    let mut vars = HashMap::new();
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in 0..height {
+        // * the top row is the largest imaginary part
+        let y = im.end() - (im.end() - im.start()) * (row as f64 + 0.5) / height as f64;
+        for col in 0..width {
+            let x = re.start() + (re.end() - re.start()) * (col as f64 + 0.5) / width as f64;
+            vars.insert("x".to_string(), Complex64::new(x, y));
+            pixels.push(pixel(e.evaluate(&vars, funcs), contours));
+        }
+    }
+    Image { width, height, pixels }
+}
+
+/// The colour of a single value.
+pub fn pixel(z: Complex64, contours: bool) -> [u8; 3] {
+    let r = z.norm();
+    if r.is_nan() || z.arg().is_nan() {
+        return [128, 128, 128];
+    }
+    if r.is_infinite() {
+        return [255, 255, 255];
+    }
+
+    let hue = z.arg().rem_euclid(2.0 * PI) / (2.0 * PI);
+    let mut lightness = 2.0 / PI * r.atan();
+    if contours && r > 0.0 {
+        // Darken towards the end of each band, so the edges are where `|z|` is a power of 2.
+        lightness *= 0.8 + 0.2 * (1.0 - r.log2().rem_euclid(1.0));
+    }
+    hsl(hue, 1.0, lightness)
+}
+
+/// Converts a colour from HSL, with every part between 0 and 1.
+fn hsl(h: f64, s: f64, l: f64) -> [u8; 3] {
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let h = h * 6.0;
+    let x = c * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = l - c / 2.0;
+    [r, g, b].map(|v| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8)
+}
@@ -0,0 +1,90 @@
+use num_complex::Complex64;
+
+use crate::domain::{self, Image};
+
+
+/// Splits a PNG into its chunks, checking the signature and every CRC.
+fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    assert_eq!(png[..8], [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']);
+    let mut rest = &png[8..];
+    let mut chunks = Vec::new();
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let (kind, data) = (&rest[4..8], &rest[8..8 + len]);
+        let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+        assert_eq!(crc, domain::crc32(&rest[4..8 + len]), "bad CRC on {:?}", std::str::from_utf8(kind));
+        chunks.push((kind.try_into().unwrap(), data.to_vec()));
+        rest = &rest[12 + len..];
+    }
+    chunks
+}
+
+/// Reads a zlib stream of uncompressed blocks, checking the header and checksum.
+fn inflate_stored(z: &[u8]) -> Vec<u8> {
+    assert_eq!(u16::from_be_bytes([z[0], z[1]]) % 31, 0, "bad zlib header");
+    let mut out = Vec::new();
+    let mut i = 2;
+    loop {
+        let last = z[i] & 1 == 1;
+        assert_eq!(z[i] >> 1, 0, "not a stored block");
+        let len = u16::from_le_bytes([z[i + 1], z[i + 2]]);
+        assert_eq!(!len, u16::from_le_bytes([z[i + 3], z[i + 4]]));
+        out.extend(&z[i + 5..i + 5 + len as usize]);
+        i += 5 + len as usize;
+        if last { break }
+    }
+    assert_eq!(z[i..], domain::adler32(&out).to_be_bytes());
+    out
+}
+
+fn colour(re: f64, im: f64) -> [u8; 3] {
+    domain::pixel(Complex64::new(re, im), false)
+}
+
+/// An image where every pixel is different.
+fn gradient(width: usize, height: usize) -> Image {
+    let pixels = (0..width * height).map(|i| [i as u8, (i / 256) as u8, 7]).collect();
+    Image { width, height, pixels }
+}
+
+
+#[test]
+fn checksums() {
+    assert_eq!(domain::crc32(b"123456789"), 0xcbf43926);
+    assert_eq!(domain::crc32(b""), 0);
+    assert_eq!(domain::adler32(b"123456789"), 0x091e01de);
+    assert_eq!(domain::adler32(b"Wikipedia"), 0x11e60398);
+    assert_eq!(domain::adler32(b""), 1);
+}
+
+#[test]
+fn pngs_decode() {
+    // * the bigger one needs more than one stored block
+    for (width, height) in [(3, 2), (200, 200), (0, 0)] {
+        let img = gradient(width, height);
+        let chunks = chunks(&img.png());
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(k, _)| k).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+
+        let header = &chunks[0].1;
+        assert_eq!(u32::from_be_bytes(header[..4].try_into().unwrap()), width as u32);
+        assert_eq!(u32::from_be_bytes(header[4..8].try_into().unwrap()), height as u32);
+        assert_eq!(header[8..], [8, 2, 0, 0, 0]);
+        assert!(chunks[2].1.is_empty());
+
+        // Every row is a 0 (no filter) and then the pixels.
+        let raw = inflate_stored(&chunks[1].1);
+        let rows: Vec<u8> = img.pixels.chunks(width.max(1)).flat_map(|r| [0].into_iter().chain(r.iter().flatten().copied())).collect();
+        assert_eq!(raw, rows);
+    }
+}
+
+#[test]
+fn colours() {
+    assert_eq!(colour(0.0, 0.0), [0, 0, 0]);
+    assert_eq!(colour(f64::INFINITY, 0.0), [255, 255, 255]);
+    assert_eq!(colour(f64::NAN, 0.0), [128, 128, 128]);
+    // Modulus 1 is halfway to white, and the argument picks the hue.
+    assert_eq!(colour(1.0, 0.0), [255, 0, 0]);
+    assert_eq!(colour(-1.0, 0.0), [0, 255, 255]);
+}
@@ -24,17 +24,22 @@ are expanded to be inline so they can simplify.
 
 
 
-use std::{collections::HashMap, ops::{Add, Mul}, str::FromStr, usize};
-use num::{pow::Pow, One, Zero};
+use std::{collections::{HashMap, HashSet}, ops::{Add, Mul, RangeInclusive}, str::FromStr};
+use num::{pow::Pow, BigInt, BigRational, Integer, One, Signed, ToPrimitive, Zero};
 use num_complex::{Complex64, ComplexFloat};
+use crate::constant::{self, Constant};
+use crate::{data, integer, special};
+use crate::linalg::Shape;
+use crate::number::F64_PRECISION;
 
 pub type Exp = Box<Expr>;
 
 
 pub mod f {
-    use super::{Exp, Expr, Term};
+    use super::{Builtin, Exp, Expr, Reducer, Series, Term};
 
-    pub fn num(n: f64) -> Exp { Expr::from(Term::from(n)).r#box() }
+    /// Whole numbers are kept exact, see `Term::Rational`.
+    pub fn num(n: f64) -> Exp { Expr::from(Term::number(n)).r#box() }
     pub fn term(t: Term) -> Exp { Expr::from(t).r#box() }
 
     pub fn neg(a: Exp) -> Exp { mul(num(-1.0), a) }
@@ -48,28 +53,265 @@ pub mod f {
     pub fn div(a: Exp, b: Exp) -> Exp { mul(a, inv(b)) }
 
     pub fn func(name: String, args: Vec<Exp>) -> Exp { Expr::Fn(name, args).r#box() }
+    pub fn builtin(b: Builtin, args: Vec<Exp>) -> Exp { Expr::Builtin(b, args).r#box() }
+
+    pub fn ln(a: Exp) -> Exp { builtin(Builtin::Ln, vec![a]) }
+    pub fn log(base: Exp, a: Exp) -> Exp { builtin(Builtin::Log, vec![base, a]) }
+    pub fn root(n: Exp, a: Exp) -> Exp { builtin(Builtin::Root, vec![n, a]) }
+    pub fn sqrt(a: Exp) -> Exp { builtin(Builtin::Sqrt, vec![a]) }
+    pub fn cbrt(a: Exp) -> Exp { builtin(Builtin::Cbrt, vec![a]) }
+
+    pub fn sin(a: Exp) -> Exp { builtin(Builtin::Sin, vec![a]) }
+    pub fn cos(a: Exp) -> Exp { builtin(Builtin::Cos, vec![a]) }
+    pub fn tan(a: Exp) -> Exp { builtin(Builtin::Tan, vec![a]) }
+    pub fn sinh(a: Exp) -> Exp { builtin(Builtin::Sinh, vec![a]) }
+    pub fn cosh(a: Exp) -> Exp { builtin(Builtin::Cosh, vec![a]) }
+    pub fn tanh(a: Exp) -> Exp { builtin(Builtin::Tanh, vec![a]) }
+    pub fn asin(a: Exp) -> Exp { builtin(Builtin::Asin, vec![a]) }
+    pub fn acos(a: Exp) -> Exp { builtin(Builtin::Acos, vec![a]) }
+    pub fn atan(a: Exp) -> Exp { builtin(Builtin::Atan, vec![a]) }
+    pub fn asinh(a: Exp) -> Exp { builtin(Builtin::Asinh, vec![a]) }
+    pub fn acosh(a: Exp) -> Exp { builtin(Builtin::Acosh, vec![a]) }
+    pub fn atanh(a: Exp) -> Exp { builtin(Builtin::Atanh, vec![a]) }
+
+    pub fn abs(a: Exp) -> Exp { builtin(Builtin::Abs, vec![a]) }
+    pub fn re(a: Exp) -> Exp { builtin(Builtin::Re, vec![a]) }
+    pub fn im(a: Exp) -> Exp { builtin(Builtin::Im, vec![a]) }
+    pub fn arg(a: Exp) -> Exp { builtin(Builtin::Arg, vec![a]) }
+    pub fn conj(a: Exp) -> Exp { builtin(Builtin::Conj, vec![a]) }
+    pub fn polar(r: Exp, theta: Exp) -> Exp { builtin(Builtin::Polar, vec![r, theta]) }
+
+    pub fn gamma(a: Exp) -> Exp { builtin(Builtin::Gamma, vec![a]) }
+    pub fn lgamma(a: Exp) -> Exp { builtin(Builtin::Lgamma, vec![a]) }
+    pub fn digamma(a: Exp) -> Exp { builtin(Builtin::Digamma, vec![a]) }
+    pub fn factorial(a: Exp) -> Exp { builtin(Builtin::Factorial, vec![a]) }
+    pub fn beta(a: Exp, b: Exp) -> Exp { builtin(Builtin::Beta, vec![a, b]) }
+    pub fn erf(a: Exp) -> Exp { builtin(Builtin::Erf, vec![a]) }
+    pub fn erfc(a: Exp) -> Exp { builtin(Builtin::Erfc, vec![a]) }
+    pub fn besselj(n: Exp, a: Exp) -> Exp { builtin(Builtin::BesselJ, vec![n, a]) }
+    pub fn bessely(n: Exp, a: Exp) -> Exp { builtin(Builtin::BesselY, vec![n, a]) }
+    pub fn zeta(a: Exp) -> Exp { builtin(Builtin::Zeta, vec![a]) }
+
+    pub fn floor(a: Exp) -> Exp { builtin(Builtin::Floor, vec![a]) }
+    pub fn ceil(a: Exp) -> Exp { builtin(Builtin::Ceil, vec![a]) }
+    pub fn round(a: Exp) -> Exp { builtin(Builtin::Round, vec![a]) }
+    pub fn sign(a: Exp) -> Exp { builtin(Builtin::Sign, vec![a]) }
+    pub fn modulo(a: Exp, b: Exp) -> Exp { builtin(Builtin::Mod, vec![a, b]) }
+    pub fn gcd(a: Exp, b: Exp) -> Exp { builtin(Builtin::Gcd, vec![a, b]) }
+    pub fn lcm(a: Exp, b: Exp) -> Exp { builtin(Builtin::Lcm, vec![a, b]) }
+    pub fn ncr(n: Exp, k: Exp) -> Exp { builtin(Builtin::NCr, vec![n, k]) }
+    pub fn npr(n: Exp, k: Exp) -> Exp { builtin(Builtin::NPr, vec![n, k]) }
+
+    pub fn sum(k: String, from: Exp, to: Exp, body: Exp) -> Exp { Expr::Series(Series::Sum, k, from, to, body).r#box() }
+    pub fn prod(k: String, from: Exp, to: Exp, body: Exp) -> Exp { Expr::Series(Series::Prod, k, from, to, body).r#box() }
+
+    pub fn list(n: Vec<Exp>) -> Exp { Expr::List(n.into_iter().map(|a| *a).collect()).r#box() }
+    pub fn range(from: Exp, to: Exp) -> Exp { Expr::Range(from, to).r#box() }
+    pub fn reduce(r: Reducer, a: Exp) -> Exp { Expr::Reduce(r, a).r#box() }
+
+    pub fn vector(n: Vec<Exp>) -> Exp { Expr::Vector(n.into_iter().map(|a| *a).collect()).r#box() }
+    pub fn matrix(m: Vec<Vec<Exp>>) -> Exp { Expr::Matrix(m.into_iter().map(|r| r.into_iter().map(|a| *a).collect()).collect()).r#box() }
+    pub fn dot(a: Exp, b: Exp) -> Exp { builtin(Builtin::Dot, vec![a, b]) }
+    pub fn cross(a: Exp, b: Exp) -> Exp { builtin(Builtin::Cross, vec![a, b]) }
+    pub fn det(a: Exp) -> Exp { builtin(Builtin::Det, vec![a]) }
+    pub fn inverse(a: Exp) -> Exp { builtin(Builtin::Inv, vec![a]) }
+    pub fn transpose(a: Exp) -> Exp { builtin(Builtin::Transpose, vec![a]) }
 }
 
 
 /// The context of the input. This is essentially just the whole parsed input.
+/// 
+/// Names are stored fully qualified (`geom::area`), and every namespace keeps
+/// track of the names defined directly in it. The root namespace is `""`.
 #[derive(Debug, Default)]
 pub struct Context {
     vars: HashMap<String, Exp>,
     fns: HashMap<String, Func>,
+    curves: HashMap<String, Curve>,
+    surfaces: HashMap<String, Surface>,
+    implicits: HashMap<String, Implicit>,
+    tables: HashMap<String, Table>,
+    fits: HashMap<String, Fit>,
+    namespaces: HashMap<String, HashSet<String>>,
 }
 impl Context {
     pub fn new() -> Self {
         let mut s = Self::default();
-        s.def_var("e", *f::num(std::f64::consts::E));
-        s.def_var("pi", *f::num(std::f64::consts::PI));
-        s.def_var("i", *f::term(Complex64::I.into()));
+        s.def_var("e", *f::term(Term::Const(Constant::E))).unwrap();
+        s.def_var("pi", *f::term(Term::Const(Constant::Pi))).unwrap();
+        s.def_var("i", *f::term(Term::Const(Constant::I))).unwrap();
         return s;
     }
-    pub fn def_var(&mut self, var: &str, val: Expr) {
+    pub fn def_var(&mut self, var: &str, val: Expr) -> Result<(), String> {
+        self.claim(var)?;
         self.vars.insert(var.to_string(), Box::new(val));
+        Ok(())
     }
-    pub fn def_func(&mut self, name: &str, recursive: bool, args: Vec<String>, body: Expr) {
+    pub fn def_func(&mut self, name: &str, recursive: bool, args: Vec<String>, body: Expr) -> Result<(), String> {
+        self.claim(name)?;
         self.fns.insert(name.to_string(), Func { recursive, args, body });
+        Ok(())
+    }
+    pub fn def_curve(&mut self, name: &str, param: String, components: Vec<Expr>, from: Expr, to: Expr) -> Result<(), String> {
+        if !(2..=3).contains(&components.len()) {
+            return Err(format!("ERROR: curve {name} has {} components! curves need 2 or 3.", components.len()));
+        }
+        self.claim(name)?;
+        self.curves.insert(name.to_string(), Curve { param, components, from, to });
+        Ok(())
+    }
+    pub fn def_surface(&mut self, name: &str, params: [String; 2], body: Expr, x: (Expr, Expr), y: (Expr, Expr)) -> Result<(), String> {
+        self.claim(name)?;
+        self.surfaces.insert(name.to_string(), Surface { params, body, x, y });
+        Ok(())
+    }
+    pub fn def_implicit(&mut self, name: &str, params: Vec<String>, lhs: Expr, rhs: Expr, ranges: Vec<(Expr, Expr)>) -> Result<(), String> {
+        if !(2..=3).contains(&params.len()) {
+            return Err(format!("ERROR: implicit {name} has {} variables! implicit equations need 2 or 3.", params.len()));
+        }
+        self.claim(name)?;
+        self.implicits.insert(name.to_string(), Implicit { params, lhs, rhs, ranges });
+        Ok(())
+    }
+    /// Defines a data table. Each column is a variable holding a list.
+    pub fn def_table(&mut self, name: &str, columns: Vec<String>, values: Vec<Expr>) -> Result<(), String> {
+        if columns.len() != values.len() {
+            return Err(format!("ERROR: table {name} has {} columns but {} names!", values.len(), columns.len()));
+        }
+        self.claim(name)?;
+        for (c, v) in columns.iter().zip(values) {
+            self.def_var(c, v)?;
+        }
+        self.tables.insert(name.to_string(), Table { columns });
+        Ok(())
+    }
+    /// Defines a regression. The parameters are claimed now, but only become variables
+    /// once they're fitted by `fit`.
+    pub fn def_fit(&mut self, name: &str, params: Vec<String>, lhs: Expr, rhs: Expr) -> Result<(), String> {
+        if params.is_empty() {
+            return Err(format!("ERROR: fit {name} has no parameters to fit!"));
+        }
+        self.claim(name)?;
+        for p in &params {
+            self.claim(p)?;
+        }
+        self.fits.insert(name.to_string(), Fit { params, lhs, rhs, regression: None });
+        Ok(())
+    }
+
+    /// Adds a qualified name to its namespace. Variables and functions share names, so
+    /// defining either twice in the same namespace is an error.
+    fn claim(&mut self, name: &str) -> Result<(), String> {
+        let (ns, short) = name.rsplit_once("::").unwrap_or(("", name));
+        if !self.namespaces.entry(ns.to_string()).or_default().insert(short.to_string()) {
+            return Err(format!("ERROR: {name} is defined more than once!"));
+        }
+        Ok(())
+    }
+
+    /// Gets the definition of a variable.
+    pub fn get_var(&self, name: &str) -> Option<&Expr> {
+        self.vars.get(name).map(|v| v.as_ref())
+    }
+    /// Gets the definition of a function.
+    pub fn get_func(&self, name: &str) -> Option<&Func> {
+        self.fns.get(name)
+    }
+    /// Gets the definition of a curve.
+    pub fn get_curve(&self, name: &str) -> Option<&Curve> {
+        self.curves.get(name)
+    }
+    /// The names of every curve, in no particular order.
+    pub fn curve_names(&self) -> impl Iterator<Item = &String> {
+        self.curves.keys()
+    }
+    /// Gets the definition of a surface.
+    pub fn get_surface(&self, name: &str) -> Option<&Surface> {
+        self.surfaces.get(name)
+    }
+    /// The names of every surface, in no particular order.
+    pub fn surface_names(&self) -> impl Iterator<Item = &String> {
+        self.surfaces.keys()
+    }
+    /// Gets the definition of an implicit equation.
+    pub fn get_implicit(&self, name: &str) -> Option<&Implicit> {
+        self.implicits.get(name)
+    }
+    /// The names of every implicit equation, in no particular order.
+    pub fn implicit_names(&self) -> impl Iterator<Item = &String> {
+        self.implicits.keys()
+    }
+    /// Gets the definition of a data table.
+    pub fn get_table(&self, name: &str) -> Option<&Table> {
+        self.tables.get(name)
+    }
+    /// The names of every data table, in no particular order.
+    pub fn table_names(&self) -> impl Iterator<Item = &String> {
+        self.tables.keys()
+    }
+    /// Gets the definition of a regression.
+    pub fn get_fit(&self, name: &str) -> Option<&Fit> {
+        self.fits.get(name)
+    }
+    /// The names of every regression, in no particular order.
+    pub fn fit_names(&self) -> impl Iterator<Item = &String> {
+        self.fits.keys()
+    }
+
+    /// Checks that every variable and function is used with the right vector and matrix shapes.
+    pub fn check_shapes(&self) -> Result<(), Vec<String>> {
+        let errs: Vec<String> = self.vars.iter()
+            .filter_map(|(var, val)| val.shape(self, &HashMap::new(), &mut vec![var.clone()]).err())
+            .chain(self.curves.iter().flat_map(|(name, c)| {
+                let args = HashMap::from([(c.param.clone(), Shape::Scalar)]);
+                c.components.iter().filter_map(move |e| match e.shape(self, &args, &mut vec![name.clone()]) {
+                    Ok(Shape::Scalar) => None,
+                    Ok(s) => Some(format!("ERROR: curve {name} has a {s} component! components must be numbers.")),
+                    Err(e) => Some(e),
+                })
+            }))
+            .chain(self.surfaces.iter().filter_map(|(name, s)| {
+                let args = s.params.iter().map(|p| (p.clone(), Shape::Scalar)).collect();
+                match s.body.shape(self, &args, &mut vec![name.clone()]) {
+                    Ok(Shape::Scalar) => None,
+                    Ok(sh) => Some(format!("ERROR: surface {name} is a {sh}! surfaces must be numbers.")),
+                    Err(e) => Some(e),
+                }
+            }))
+            .chain(self.implicits.iter().flat_map(|(name, i)| {
+                let args: HashMap<String, Shape> = i.params.iter().map(|p| (p.clone(), Shape::Scalar)).collect();
+                [&i.lhs, &i.rhs].into_iter().filter_map(move |e| match e.shape(self, &args, &mut vec![name.clone()]) {
+                    Ok(Shape::Scalar) => None,
+                    Ok(sh) => Some(format!("ERROR: implicit {name} has a {sh} side! both sides must be numbers.")),
+                    Err(e) => Some(e),
+                })
+            }))
+            .collect();
+        if !errs.is_empty() { return Err(errs) }
+
+        // Ranges and matrix powers are worked out before simplifying, so they have to be constant
+        // once everything is filled in.
+        let check = |e: &Expr, bound: &[&String]| {
+            let args = bound.iter().map(|p| (p.to_string(), Shape::Scalar)).collect();
+            let bound: Vec<&str> = bound.iter().map(|p| p.as_str()).collect();
+            self.expand(e.clone(), &bound).check_constant(self, &args).err()
+        };
+        let errs: Vec<String> = self.vars.values().filter_map(|val| check(val, &[]))
+            .chain(self.curves.values().flat_map(|c| c.components.iter().filter_map(move |e| check(e, &[&c.param]))))
+            .chain(self.surfaces.values().filter_map(|s| check(&s.body, &[&s.params[0], &s.params[1]])))
+            .chain(self.implicits.values().flat_map(|i| {
+                let bound: Vec<&String> = i.params.iter().collect();
+                [check(&i.lhs, &bound), check(&i.rhs, &bound)].into_iter().flatten()
+            }))
+            .collect();
+
+        if errs.is_empty() { return Ok(()) }
+        Err(errs)
+    }
+
+    /// The names defined directly in a namespace, if it exists.
+    pub fn namespace(&self, ns: &str) -> Option<&HashSet<String>> {
+        self.namespaces.get(ns)
     }
 
     /// Checks for illigal recursion. This includes:
@@ -98,16 +340,128 @@ impl Context {
         return Err(errs); // ono! errors!
     }
 
+    /// Checks that every function that's called is defined, and that functions are only used
+    /// with inputs (`2f` isn't anything). A call to a name that isn't a function is a
+    /// multiplication (`x(y)`), so that's only an error with more than one input.
+    pub fn check_calls(&self) -> Result<(), Vec<String>> {
+        // Each expression with the names bound in it, which hide functions with the same name.
+        let exprs = self.vars.values().map(|v| (&**v, &[][..]))
+            .chain(self.fns.values().map(|f| (&f.body, &f.args[..])))
+            .chain(self.curves.values().flat_map(|c| c.components.iter().map(|e| (e, std::slice::from_ref(&c.param)))))
+            .chain(self.surfaces.values().map(|s| (&s.body, &s.params[..])))
+            .chain(self.implicits.values().flat_map(|i| [(&i.lhs, &i.params[..]), (&i.rhs, &i.params[..])]))
+            .chain(self.fits.values().flat_map(|f| [(&f.lhs, &[][..]), (&f.rhs, &[][..])]));
+
+        let mut errs: Vec<String> = exprs.flat_map(|(e, bound)| {
+            let undefined = e.calls().into_iter()
+                .filter(|(name, inputs)| (*inputs != 1) && !self.fns.contains_key(name))
+                .map(|(name, _)| format!("ERROR: function {name} is not defined!"));
+            let uncalled = e.free_vars().into_iter()
+                .filter(|v| self.fns.contains_key(v) && !bound.contains(v))
+                .map(|name| format!("ERROR: function {name} is used without inputs!"));
+            undefined.chain(uncalled).collect::<Vec<String>>()
+        }).collect();
+        errs.sort();
+        errs.dedup();
+
+        if errs.is_empty() { return Ok(()) }
+        Err(errs)
+    }
+
     /// Simplifies a specific variable into an expression and recursive functions.
     pub fn simplify_for_var(&self, var: &str) -> (Expr, HashMap<String, Func>) {
-        let mut e = *self.vars.get(&var.to_string()).unwrap().clone();
+        self.simplify(*self.vars.get(&var.to_string()).unwrap().clone(), &[])
+    }
+
+    /// Simplifies each component of a curve. The parameter shadows any variable with the same name.
+    pub fn simplify_curve(&self, name: &str) -> (Vec<Expr>, HashMap<String, Func>) {
+        let c = self.curves.get(name).unwrap();
+        let (e, funcs) = self.simplify(Expr::Vector(c.components.clone()), &[&c.param]);
+        match e {
+            Expr::Vector(n) => (n, funcs),
+            _ => unreachable!("a vector stays a vector"),
+        }
+    }
+
+    /// Simplifies the height of a surface. The parameters shadow any variables with the same names.
+    pub fn simplify_surface(&self, name: &str) -> (Expr, HashMap<String, Func>) {
+        let s = self.surfaces.get(name).unwrap();
+        self.simplify(s.body.clone(), &[&s.params[0], &s.params[1]])
+    }
+
+    /// Simplifies both sides of a regression, `lhs` and `lhs - rhs`.
+    pub fn simplify_fit(&self, name: &str) -> (Expr, Expr, HashMap<String, Func>) {
+        let fit = self.fits.get(name).unwrap();
+        let (lhs, mut funcs) = self.simplify(fit.lhs.clone(), &[]);
+        let (residuals, f) = self.simplify(*f::sub(fit.lhs.clone().r#box(), fit.rhs.clone().r#box()), &[]);
+        funcs.extend(f);
+        (lhs, residuals, funcs)
+    }
+
+    /// Fits every regression and defines its parameters as variables. A fit that uses the
+    /// parameters of another one waits for that one to be fitted first.
+    pub fn fit(&mut self) -> Result<(), Vec<String>> {
+        let mut errs = Vec::new();
+        let mut pending: Vec<String> = self.fits.keys().cloned().collect();
+        pending.sort();
+        while !pending.is_empty() {
+            let unfitted: Vec<String> = pending.iter().flat_map(|n| self.fits[n].params.clone()).collect();
+            let ready = pending.iter().position(|n| {
+                let fit = &self.fits[n];
+                let uses = |p: &String| fit.lhs.has_var(p, self) | fit.rhs.has_var(p, self);
+                !unfitted.iter().any(|p| !fit.params.contains(p) && uses(p))
+            });
+            let Some(i) = ready else {
+                errs.push(format!("ERROR: fits {} use each other's parameters!", pending.join(", ")));
+                break;
+            };
+            let name = pending.remove(i);
+            match data::regression(self, &name) {
+                Ok(r) => {
+                    for (p, v) in &r.params {
+                        self.vars.insert(p.clone(), f::num(*v));
+                    }
+                    self.fits.get_mut(&name).unwrap().regression = Some(r);
+                },
+                Err(e) => errs.push(e),
+            }
+        }
 
-        println!(" - expanding vars");
-        e = e.expand_vars(&self.vars.clone().into_iter().collect());
-        println!(" - expanding funcs");
+        if errs.is_empty() { return Ok(()) }
+        Err(errs)
+    }
+
+    /// Simplifies `lhs - rhs` of an implicit equation. The variables shadow any with the same names.
+    pub fn simplify_implicit(&self, name: &str) -> (Expr, HashMap<String, Func>) {
+        let i = self.implicits.get(name).unwrap();
+        let bound: Vec<&str> = i.params.iter().map(|p| p.as_str()).collect();
+        self.simplify(*f::sub(i.lhs.clone().r#box(), i.rhs.clone().r#box()), &bound)
+    }
+
+    /// Simplifies and evaluates a range bound, which can use variables but not `params`.
+    fn bound(&self, e: &Expr, params: &[&String]) -> Result<f64, String> {
+        let (e, funcs) = self.simplify(e.clone(), &[]);
+        if let Some(p) = params.iter().find(|p| e.mentions(p)) {
+            return Err(format!("ERROR: a range can't depend on {p}!"));
+        }
+        Ok(e.evaluate(&HashMap::new(), &funcs).re)
+    }
+
+    /// Fills in every variable and function that isn't recursive. Variables named in `bound`
+    /// are left alone.
+    fn expand(&self, mut e: Expr, bound: &[&str]) -> Expr {
+        let vars: Vec<(String, Exp)> = self.vars.clone().into_iter().filter(|(v, _)| !bound.contains(&v.as_str())).collect();
+        e = e.expand_vars(&vars);
         e = e.expand_funcs(&self.fns.clone().into_iter().collect());
-        println!(" - expanding vars");
-        e = e.expand_vars(&self.vars.clone().into_iter().collect());
+        e.expand_vars(&vars)
+    }
+
+    /// Runs every simplification pass on `e`. Variables named in `bound` are left alone.
+    fn simplify(&self, mut e: Expr, bound: &[&str]) -> (Expr, HashMap<String, Func>) {
+        println!(" - expanding vars and funcs");
+        e = self.expand(e, bound);
+        e = e.broadcast();
+        e = e.resolve_linalg();
         println!(" - flattening");
         e = e.flatten();
         println!(" - reduce consts");
@@ -126,6 +480,98 @@ impl Context {
 
         return (e, funcs);
     }
+
+    /// Simplifies `var` and evaluates it at `n` evenly spaced values of `x` in `bounds`.
+    /// A list-valued `var` gives one curve per item.
+    pub fn evaluate_with_x(&self, var: &str, bounds: RangeInclusive<f64>, n: usize) -> Vec<Vec<(f64, Complex64)>> {
+        let (e, funcs) = self.simplify_for_var(var);
+        e.items().iter().map(|c| c.sample_x(&funcs, bounds.clone(), n)).collect()
+    }
+}
+
+/// A parametric curve, `curve c(t) = (x, y, z) for t in from..to;`.
+#[derive(Debug, Clone)]
+pub struct Curve {
+    param: String,
+    components: Vec<Expr>,
+    from: Expr,
+    to: Expr,
+}
+impl Curve {
+    pub fn param(&self) -> &String { &self.param }
+    pub fn components(&self) -> &Vec<Expr> { &self.components }
+
+    /// The range of the parameter. The bounds can use variables but not the parameter itself.
+    pub fn range(&self, ctx: &Context) -> Result<RangeInclusive<f64>, String> {
+        Ok(ctx.bound(&self.from, &[&self.param])?..=ctx.bound(&self.to, &[&self.param])?)
+    }
+}
+
+/// A surface, `surface s(x, y) = z for x in a..b, y in c..d;`.
+#[derive(Debug, Clone)]
+pub struct Surface {
+    params: [String; 2],
+    body: Expr,
+    x: (Expr, Expr),
+    y: (Expr, Expr),
+}
+impl Surface {
+    pub fn params(&self) -> &[String; 2] { &self.params }
+    pub fn body(&self) -> &Expr { &self.body }
+
+    /// The ranges of both parameters. The bounds can use variables but not the parameters.
+    pub fn ranges(&self, ctx: &Context) -> Result<(RangeInclusive<f64>, RangeInclusive<f64>), String> {
+        let [p, q] = &self.params;
+        let range = |(from, to): &(Expr, Expr)| Ok::<_, String>(ctx.bound(from, &[p, q])?..=ctx.bound(to, &[p, q])?);
+        Ok((range(&self.x)?, range(&self.y)?))
+    }
+}
+
+/// An implicit equation, `implicit c(x, y): lhs = rhs for x in a..b, y in c..d;`.
+/// With a third variable it's a surface instead of a curve.
+#[derive(Debug, Clone)]
+pub struct Implicit {
+    params: Vec<String>,
+    lhs: Expr,
+    rhs: Expr,
+    /// The range of each parameter, in the same order.
+    ranges: Vec<(Expr, Expr)>,
+}
+impl Implicit {
+    pub fn params(&self) -> &Vec<String> { &self.params }
+    pub fn lhs(&self) -> &Expr { &self.lhs }
+    pub fn rhs(&self) -> &Expr { &self.rhs }
+
+    /// The range of each parameter. The bounds can use variables but not the parameters.
+    pub fn ranges(&self, ctx: &Context) -> Result<Vec<RangeInclusive<f64>>, String> {
+        let params: Vec<&String> = self.params.iter().collect();
+        self.ranges.iter().map(|(from, to)| Ok(ctx.bound(from, &params)?..=ctx.bound(to, &params)?)).collect()
+    }
+}
+
+/// A data table, `table t(x1, y1) = "data.csv";`. The columns are variables.
+#[derive(Debug, Clone)]
+pub struct Table {
+    columns: Vec<String>,
+}
+impl Table {
+    pub fn columns(&self) -> &Vec<String> { &self.columns }
+}
+
+/// A regression, `fit line(m, b): y1 ~ m x1 + b;`.
+#[derive(Debug, Clone)]
+pub struct Fit {
+    params: Vec<String>,
+    lhs: Expr,
+    rhs: Expr,
+    /// How it was fitted, once it has been.
+    regression: Option<data::Regression>,
+}
+impl Fit {
+    pub fn params(&self) -> &Vec<String> { &self.params }
+    pub fn lhs(&self) -> &Expr { &self.lhs }
+    pub fn rhs(&self) -> &Expr { &self.rhs }
+    pub fn regression(&self) -> Option<&data::Regression> { self.regression.as_ref() }
 }
 
 #[derive(Debug, Clone)]
@@ -134,6 +580,11 @@ pub struct Func {
     args: Vec<String>,
     body: Expr,
 }
+impl Func {
+    pub fn args(&self) -> &Vec<String> { &self.args }
+    pub fn body(&self) -> &Expr { &self.body }
+    pub fn is_recursive(&self) -> bool { self.recursive }
+}
 
 
 /// An expression tree node.
@@ -144,6 +595,19 @@ pub enum Expr {
     Mul(Vec<Expr>),
     Pow(Exp, Exp),
     Fn(String, Vec<Exp>),
+    Builtin(Builtin, Vec<Exp>),
+    /// `sum(k, from, to, body)` or `prod(k, from, to, body)`. `k` is only bound inside the body.
+    Series(Series, String, Exp, Exp, Exp),
+    /// `[a, b, ...]`. Operations on lists work item by item, see `broadcast`.
+    List(Vec<Expr>),
+    /// `[from..to]`, a list of integers. Turned into a `List` by `broadcast`.
+    Range(Exp, Exp),
+    /// A function that turns a list into a single value.
+    Reduce(Reducer, Exp),
+    /// `vec(a, b, ...)`. Operations on vectors are worked out by `resolve_linalg`.
+    Vector(Vec<Expr>),
+    /// `mat[[a, b], [c, d]]`, stored as rows.
+    Matrix(Vec<Vec<Expr>>),
 }
 impl Expr {
     
@@ -153,9 +617,16 @@ impl Expr {
         match self {
             Self::Term(_) => self.clone(),
             Self::Fn(_, _) => self.clone(),
+            Self::Builtin(b, n) => Self::Builtin(*b, n.iter().map(|a| a.flatten().r#box()).collect()),
             Self::Add(n) => Self::Add(n.iter().flat_map(|a| {a.flatten_add()}).collect()),
             Self::Mul(n) => Self::Mul(n.iter().flat_map(|a| {a.flatten_mul()}).collect()),
             Self::Pow(a, b) => Self::Pow(a.flatten().r#box(), b.flatten().r#box()),
+            Self::Series(s, k, a, b, n) => Self::Series(*s, k.clone(), a.flatten().r#box(), b.flatten().r#box(), n.flatten().r#box()),
+            Self::List(n) => Self::List(n.iter().map(|a| a.flatten()).collect()),
+            Self::Range(a, b) => Self::Range(a.flatten().r#box(), b.flatten().r#box()),
+            Self::Reduce(r, a) => Self::Reduce(*r, a.flatten().r#box()),
+            Self::Vector(n) => Self::Vector(n.iter().map(|a| a.flatten()).collect()),
+            Self::Matrix(m) => Self::Matrix(m.iter().map(|r| r.iter().map(|a| a.flatten()).collect()).collect()),
         }
     }
     fn flatten_mul(&self) -> Vec<Expr> {
@@ -183,6 +654,47 @@ impl Expr {
             Self::Mul(n) => Self::Mul(n.iter().map(|a| a.expand_vars(vars)).collect()),
             Self::Pow(a, b) => Self::Pow((*a).expand_vars(vars).r#box(), (*b).expand_vars(vars).r#box()),
             Self::Fn(s, n) => Self::Fn(s.clone(), n.iter().map(|a| a.expand_vars(vars).r#box()).collect()),
+            Self::Builtin(b, n) => Self::Builtin(*b, n.iter().map(|a| a.expand_vars(vars).r#box()).collect()),
+            Self::Series(s, k, a, b, n) => {
+                // `k` is bound inside the body, so it's never expanded there.
+                let inner: Vec<(String, Exp)> = vars.iter().filter(|(v, _)| v != k).cloned().collect();
+
+                // Rename `k` if it would capture a variable in one of the expanded values.
+                let mut fresh = k.clone();
+                while inner.iter().any(|(_, e)| e.mentions(&fresh)) {
+                    fresh.push('\'');
+                }
+                let n = n.rename(&|v| (v == k).then(|| fresh.clone()));
+
+                Self::Series(*s, fresh, a.expand_vars(vars).r#box(), b.expand_vars(vars).r#box(), n.expand_vars(&inner).r#box())
+            },
+            Self::List(n) => Self::List(n.iter().map(|a| a.expand_vars(vars)).collect()),
+            Self::Range(a, b) => Self::Range(a.expand_vars(vars).r#box(), b.expand_vars(vars).r#box()),
+            Self::Reduce(r, a) => Self::Reduce(*r, a.expand_vars(vars).r#box()),
+            Self::Vector(n) => Self::Vector(n.iter().map(|a| a.expand_vars(vars)).collect()),
+            Self::Matrix(m) => Self::Matrix(m.iter().map(|r| r.iter().map(|a| a.expand_vars(vars)).collect()).collect()),
+        }
+    }
+
+    /// Renames variables and functions. `f` returns the new name, or `None` to keep the old one.
+    pub fn rename(&self, f: &dyn Fn(&str) -> Option<String>) -> Self {
+        match self {
+            Self::Term(Term::Var(v)) => Self::Term(Term::Var(f(v).unwrap_or(v.clone()))),
+            Self::Term(_) => self.clone(),
+            Self::Add(n) => Self::Add(n.iter().map(|a| a.rename(f)).collect()),
+            Self::Mul(n) => Self::Mul(n.iter().map(|a| a.rename(f)).collect()),
+            Self::Pow(a, b) => Self::Pow(a.rename(f).r#box(), b.rename(f).r#box()),
+            Self::Fn(s, n) => Self::Fn(f(s).unwrap_or(s.clone()), n.iter().map(|a| a.rename(f).r#box()).collect()),
+            Self::Builtin(b, n) => Self::Builtin(*b, n.iter().map(|a| a.rename(f).r#box()).collect()),
+            Self::Series(s, k, a, b, n) => {
+                let n = n.rename(&|v| if v == k { None } else { f(v) });
+                Self::Series(*s, k.clone(), a.rename(f).r#box(), b.rename(f).r#box(), n.r#box())
+            },
+            Self::List(n) => Self::List(n.iter().map(|a| a.rename(f)).collect()),
+            Self::Range(a, b) => Self::Range(a.rename(f).r#box(), b.rename(f).r#box()),
+            Self::Reduce(r, a) => Self::Reduce(*r, a.rename(f).r#box()),
+            Self::Vector(n) => Self::Vector(n.iter().map(|a| a.rename(f)).collect()),
+            Self::Matrix(m) => Self::Matrix(m.iter().map(|r| r.iter().map(|a| a.rename(f)).collect()).collect()),
         }
     }
 
@@ -191,7 +703,15 @@ impl Expr {
         match self {
             Self::Term(_) => self.clone(),
             Self::Fn(name, args) => {
-                let f = funcs.get(name).unwrap();
+                // `x(y)` is a multiplication when there isn't a function named `x`.
+                let Some(f) = funcs.get(name) else {
+                    // * `Context::check_calls` turns down calls to undefined functions with
+                    // * more than one input when converting
+                    if args.len() != 1 {
+                        unreachable!("function {name} is not defined");
+                    }
+                    return Self::Mul(vec![*f::term(name.clone().into()), *args[0].clone()]).expand_funcs(funcs);
+                };
                 if f.recursive {
                     return self.clone();
                 }
@@ -201,6 +721,88 @@ impl Expr {
             Self::Add(n) => Self::Add(n.iter().map(|a| a.expand_funcs(funcs)).collect()),
             Self::Mul(n) => Self::Mul(n.iter().map(|a| a.expand_funcs(funcs)).collect()),
             Self::Pow(a,b) => Self::Pow(a.expand_funcs(funcs).r#box(), b.expand_funcs(funcs).r#box()),
+            Self::Builtin(b, n) => Self::Builtin(*b, n.iter().map(|a| a.expand_funcs(funcs).r#box()).collect()),
+            Self::Series(s, k, a, b, n) => Self::Series(*s, k.clone(), a.expand_funcs(funcs).r#box(), b.expand_funcs(funcs).r#box(), n.expand_funcs(funcs).r#box()),
+            Self::List(n) => Self::List(n.iter().map(|a| a.expand_funcs(funcs)).collect()),
+            Self::Range(a, b) => Self::Range(a.expand_funcs(funcs).r#box(), b.expand_funcs(funcs).r#box()),
+            Self::Reduce(r, a) => Self::Reduce(*r, a.expand_funcs(funcs).r#box()),
+            Self::Vector(n) => Self::Vector(n.iter().map(|a| a.expand_funcs(funcs)).collect()),
+            Self::Matrix(m) => Self::Matrix(m.iter().map(|r| r.iter().map(|a| a.expand_funcs(funcs)).collect()).collect()),
+        }
+    }
+
+    /// The most items a range can have, since it's written out as a list.
+    pub const RANGE_LIMIT: i64 = 100_000;
+
+    /// Distributes lists outwards so every operation works item by item (`x^[1, 2]` becomes
+    /// `[x^1, x^2]`), and applies list reducers. Afterwards a list can only be at the top of
+    /// the tree. Operations on lists of different lengths stop at the shortest one.
+    pub fn broadcast(&self) -> Self {
+        match self {
+            Self::Term(_) => self.clone(),
+            // Nested lists are flattened into one.
+            Self::List(n) => Self::List(n.iter().flat_map(|a| a.broadcast().items()).collect()),
+            Self::Range(a, b) => {
+                // * `check_constant` turns down other bounds, and ranges past `RANGE_LIMIT`, when converting
+                let (Some(a), Some(b)) = (a.broadcast().as_number(), b.broadcast().as_number()) else {
+                    unreachable!("the bounds of a range have to be constant");
+                };
+                let (a, b) = Series::bounds(a.as_complex().re, b.as_complex().re);
+                Self::List((a..=b).map(|i| *f::num(i as f64)).collect())
+            },
+            Self::Add(n) => Self::zip(n.iter().map(|a| a.broadcast()).collect(), &|n| Self::Add(n)),
+            Self::Mul(n) => Self::zip(n.iter().map(|a| a.broadcast()).collect(), &|n| Self::Mul(n)),
+            Self::Pow(a, b) => Self::zip(vec![a.broadcast(), b.broadcast()], &|n| *f::pow(n[0].clone().r#box(), n[1].clone().r#box())),
+            Self::Fn(s, n) => Self::zip(n.iter().map(|a| a.broadcast()).collect(), &|n| Self::Fn(s.clone(), n.into_iter().map(|a| a.r#box()).collect())),
+            Self::Builtin(b, n) => Self::zip(n.iter().map(|a| a.broadcast()).collect(), &|n| Self::Builtin(*b, n.into_iter().map(|a| a.r#box()).collect())),
+            Self::Series(s, k, a, b, n) => Self::zip(vec![a.broadcast(), b.broadcast(), n.broadcast()], &|n| {
+                Self::Series(*s, k.clone(), n[0].clone().r#box(), n[1].clone().r#box(), n[2].clone().r#box())
+            }),
+            Self::Reduce(r, a) => r.apply(a.broadcast().items()),
+            Self::Vector(n) => Self::zip(n.iter().map(|a| a.broadcast()).collect(), &|n| Self::Vector(n)),
+            Self::Matrix(m) => {
+                // Broadcast over all of the items, then put them back into rows.
+                let cols = m.first().map_or(0, |r| r.len());
+                Self::zip(m.iter().flatten().map(|a| a.broadcast()).collect(), &|n| {
+                    Self::Matrix(n.chunks(cols.max(1)).map(|r| r.to_vec()).collect())
+                })
+            },
+        }
+    }
+
+    /// Builds a node from broadcast inputs. If any of them are lists, the node is built once
+    /// per item.
+    fn zip(n: Vec<Expr>, build: &dyn Fn(Vec<Expr>) -> Expr) -> Self {
+        let len = n.iter().filter_map(|a| match a {
+            Self::List(l) => Some(l.len()),
+            _ => None,
+        }).min();
+
+        let Some(len) = len else { return build(n) };
+        Self::List((0..len).map(|i| build(n.iter().map(|a| match a {
+            Self::List(l) => l[i].clone(),
+            a => a.clone(),
+        }).collect())).collect())
+    }
+
+    /// Evaluates `self` at `n` evenly spaced values of `x` in `bounds`.
+    pub fn sample_x(&self, funcs: &HashMap<String, Func>, bounds: RangeInclusive<f64>, n: usize) -> Vec<(f64, Complex64)> {
+        let step = (bounds.end() - bounds.start()) / (n.max(2) - 1) as f64;
+        let mut vars = HashMap::new();
+        let mut points = Vec::with_capacity(n);
+        for i in 0..n {
+            let x = bounds.start() + step * i as f64;
+            vars.insert("x".to_string(), Complex64::from(x));
+            points.push((x, self.evaluate(&vars, funcs)));
+        }
+        points
+    }
+
+    /// The items of a list, or just `self` if it isn't one.
+    pub fn items(&self) -> Vec<Expr> {
+        match self {
+            Self::List(n) => n.clone(),
+            o => vec![o.clone()],
         }
     }
 
@@ -217,7 +819,7 @@ impl Expr {
                 n.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
                 let mut cutoff = usize::MAX;
                 for i in 0..n.len() {
-                    if !n[i].is_const() {break}
+                    if !n[i].is_number() {break}
                     cutoff = i+1;
                 }
 
@@ -242,13 +844,14 @@ impl Expr {
             Self::Mul(n) => {
                 // Reduce const for all items
                 let mut n: Vec<Expr> = n.iter().map(|a| a.reduce_const()).collect();
+                constant::combine_i(&mut n);
 
                 // Sort the items so constants are first, then find the cutoff where the items are no longer 
                 // constant.
                 n.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
                 let mut cutoff = usize::MAX;
                 for i in 0..n.len() {
-                    if !n[i].is_const() {break}
+                    if !n[i].is_number() {break}
                     cutoff = i+1;
                 }
 
@@ -266,6 +869,10 @@ impl Expr {
                 if cutoff == n.len() {
                     return *f::term(c);
                 }
+                // * only exact zeros, and only in front of constants, which can't be infinite
+                if matches!(&c, Term::Rational(r) if r.is_zero()) & n[cutoff..].iter().all(|a| a.is_const()) {
+                    return *f::term(c);
+                }
 
                 // Return the same terms with the reduced constant at the front.
                 return Self::Mul(vec![&[Expr::from(c)],n.split_at(cutoff).1].concat());
@@ -273,12 +880,38 @@ impl Expr {
             Self::Pow(a, b) => {
                 let a = a.reduce_const();
                 let b = b.reduce_const();
-                if a.is_const() & b.is_const() {
+                if let Some(e) = constant::symbolic_pow(&a, &b) {
+                    return e;
+                }
+                if a.is_number() & b.is_number() {
                     return (a.force_const().pow(b.force_const())).into();
                 }
                 Self::Pow(a.r#box(), b.r#box())
             },
             Self::Fn(_, _) => self.clone(),
+            Self::Builtin(b, n) => {
+                let n: Vec<Expr> = n.iter().map(|a| a.reduce_const()).collect();
+                if let Some(e) = b.apply_symbolic(&n) {
+                    return e;
+                }
+                if n.iter().all(|a| a.is_number()) {
+                    return b.apply(&n.iter().map(|a| a.force_const()).collect::<Vec<Term>>()).into();
+                }
+                Self::Builtin(*b, n.into_iter().map(|a| a.r#box()).collect())
+            },
+            Self::Series(s, k, a, b, n) => {
+                let (a, b, n) = (a.reduce_const(), b.reduce_const(), n.reduce_const());
+
+                if let Some(e) = s.reduce(k, &a, &b, &n) {
+                    return e.flatten().reduce_const();
+                }
+                Self::Series(*s, k.clone(), a.r#box(), b.r#box(), n.r#box())
+            },
+            Self::List(n) => Self::List(n.iter().map(|a| a.reduce_const()).collect()),
+            Self::Range(a, b) => Self::Range(a.reduce_const().r#box(), b.reduce_const().r#box()),
+            Self::Reduce(r, a) => Self::Reduce(*r, a.reduce_const().r#box()),
+            Self::Vector(n) => Self::Vector(n.iter().map(|a| a.reduce_const()).collect()),
+            Self::Matrix(m) => Self::Matrix(m.iter().map(|r| r.iter().map(|a| a.reduce_const()).collect()).collect()),
         }
     }
 
@@ -315,12 +948,15 @@ impl Expr {
                 return self.clone();
             },
             Self::Pow(a, b) => {
-                if a.is_zero() { return *f::num(0.0) }  // 0^b
+                if a.is_zero() & b.is_positive() { return *f::num(0.0) }  // 0^b, but 0^-1 isn't 0
                 if b.is_zero() { return *f::num(1.0) }  // a^0
                 if a.is_one()  { return *f::num(1.0) }  // 1^b
                 if b.is_one()  { return *a.clone() }    // a^1
                 return self.clone();
             },
+            // Vectors and matrices are simplified one component at a time.
+            Self::Vector(n) => Self::Vector(n.iter().map(|a| a.special_cases()).collect()),
+            Self::Matrix(m) => Self::Matrix(m.iter().map(|r| r.iter().map(|a| a.special_cases()).collect()).collect()),
             _ => return self.clone(),
         }
     }
@@ -346,6 +982,13 @@ impl Expr {
             Self::Add(n) => Self::Add(n.iter().map(|a| a.expand_pow()).collect()),
             Self::Mul(n) => Self::Mul(n.iter().map(|a| a.expand_pow()).collect()),
             Self::Fn(_, _) => self.clone(),
+            Self::Builtin(b, n) => Self::Builtin(*b, n.iter().map(|a| a.expand_pow().r#box()).collect()),
+            Self::Series(s, k, a, b, n) => Self::Series(*s, k.clone(), a.expand_pow().r#box(), b.expand_pow().r#box(), n.expand_pow().r#box()),
+            Self::List(n) => Self::List(n.iter().map(|a| a.expand_pow()).collect()),
+            Self::Range(_, _) => self.clone(),
+            Self::Reduce(r, a) => Self::Reduce(*r, a.expand_pow().r#box()),
+            Self::Vector(n) => Self::Vector(n.iter().map(|a| a.expand_pow()).collect()),
+            Self::Matrix(m) => Self::Matrix(m.iter().map(|r| r.iter().map(|a| a.expand_pow()).collect()).collect()),
             Self::Term(_) => self.clone(),
         }
     }
@@ -366,6 +1009,13 @@ impl Expr {
             Self::Mul(n) => n.iter().map(|a| a.has_var(var, c)).collect::<Vec<bool>>().contains(&true),
             Self::Pow(a, b) => a.has_var(var, c) | b.has_var(var, c),
             Self::Fn(_, a) => a.iter().map(|a| a.has_var(var, c)).collect::<Vec<bool>>().contains(&true),
+            Self::Builtin(_, a) => a.iter().map(|a| a.has_var(var, c)).collect::<Vec<bool>>().contains(&true),
+            Self::Series(_, k, a, b, n) => a.has_var(var, c) | b.has_var(var, c) | ((k != var) & n.has_var(var, c)),
+            Self::List(n) => n.iter().map(|a| a.has_var(var, c)).collect::<Vec<bool>>().contains(&true),
+            Self::Range(a, b) => a.has_var(var, c) | b.has_var(var, c),
+            Self::Reduce(_, a) => a.has_var(var, c),
+            Self::Vector(n) => n.iter().map(|a| a.has_var(var, c)).collect::<Vec<bool>>().contains(&true),
+            Self::Matrix(m) => m.iter().flatten().map(|a| a.has_var(var, c)).collect::<Vec<bool>>().contains(&true),
         }
     }
 
@@ -378,9 +1028,143 @@ impl Expr {
             Self::Mul(n) => n.iter().map(|a| a.has_fn(name, c)).collect::<Vec<bool>>().contains(&true),
             Self::Pow(a, b) => a.has_fn(name, c) | b.has_fn(name, c),
             Self::Fn(_, _) => true,
+            Self::Builtin(_, a) => a.iter().map(|a| a.has_fn(name, c)).collect::<Vec<bool>>().contains(&true),
+            Self::Series(_, _, a, b, n) => a.has_fn(name, c) | b.has_fn(name, c) | n.has_fn(name, c),
+            Self::List(n) => n.iter().map(|a| a.has_fn(name, c)).collect::<Vec<bool>>().contains(&true),
+            Self::Range(a, b) => a.has_fn(name, c) | b.has_fn(name, c),
+            Self::Reduce(_, a) => a.has_fn(name, c),
+            Self::Vector(n) => n.iter().map(|a| a.has_fn(name, c)).collect::<Vec<bool>>().contains(&true),
+            Self::Matrix(m) => m.iter().flatten().map(|a| a.has_fn(name, c)).collect::<Vec<bool>>().contains(&true),
+        }
+    }
+
+    /// Checks if a variable appears (unbound) in `self`. Unlike `has_var`, this doesn't look
+    /// into a `Context`.
+    pub fn mentions(&self, var: &str) -> bool {
+        match self {
+            Self::Term(Term::Var(v)) => v == var,
+            Self::Term(_) => false,
+            Self::Add(n) | Self::Mul(n) => n.iter().any(|a| a.mentions(var)),
+            Self::Pow(a, b) => a.mentions(var) | b.mentions(var),
+            Self::Fn(_, n) | Self::Builtin(_, n) => n.iter().any(|a| a.mentions(var)),
+            Self::Series(_, k, a, b, n) => a.mentions(var) | b.mentions(var) | ((k != var) & n.mentions(var)),
+            Self::List(n) => n.iter().any(|a| a.mentions(var)),
+            Self::Range(a, b) => a.mentions(var) | b.mentions(var),
+            Self::Reduce(_, a) => a.mentions(var),
+            Self::Vector(n) => n.iter().any(|a| a.mentions(var)),
+            Self::Matrix(m) => m.iter().flatten().any(|a| a.mentions(var)),
+        }
+    }
+
+    /// The variables in `self` that aren't bound by a series, in no particular order.
+    pub fn free_vars(&self) -> HashSet<String> {
+        match self {
+            Self::Term(Term::Var(v)) => HashSet::from([v.clone()]),
+            Self::Term(_) => HashSet::new(),
+            Self::Add(n) | Self::Mul(n) | Self::List(n) | Self::Vector(n) => n.iter().flat_map(|a| a.free_vars()).collect(),
+            Self::Fn(_, n) | Self::Builtin(_, n) => n.iter().flat_map(|a| a.free_vars()).collect(),
+            Self::Pow(a, b) | Self::Range(a, b) => a.free_vars().union(&b.free_vars()).cloned().collect(),
+            Self::Series(_, k, a, b, n) => {
+                let mut v: HashSet<String> = n.free_vars().into_iter().filter(|v| v != k).collect();
+                v.extend(a.free_vars());
+                v.extend(b.free_vars());
+                v
+            },
+            Self::Reduce(_, a) => a.free_vars(),
+            Self::Matrix(m) => m.iter().flatten().flat_map(|a| a.free_vars()).collect(),
+        }
+    }
+
+    /// The functions called in `self` with how many inputs they're given.
+    pub fn calls(&self) -> Vec<(String, usize)> {
+        match self {
+            Self::Term(_) => Vec::new(),
+            Self::Fn(name, n) => {
+                let mut c = vec![(name.clone(), n.len())];
+                c.extend(n.iter().flat_map(|a| a.calls()));
+                c
+            },
+            Self::Builtin(_, n) => n.iter().flat_map(|a| a.calls()).collect(),
+            Self::Add(n) | Self::Mul(n) | Self::List(n) | Self::Vector(n) => n.iter().flat_map(|a| a.calls()).collect(),
+            Self::Pow(a, b) | Self::Range(a, b) => [a.calls(), b.calls()].concat(),
+            Self::Series(_, _, a, b, n) => [a.calls(), b.calls(), n.calls()].concat(),
+            Self::Reduce(_, a) => a.calls(),
+            Self::Matrix(m) => m.iter().flatten().flat_map(|a| a.calls()).collect(),
+        }
+    }
+
+    /// The step functions in `self` with their inputs, for finding where it jumps (see
+    /// `Builtin::piece`). Ones in a series that depend on its variable are left out, along
+    /// with ones in functions that haven't been expanded.
+    pub fn steps(&self) -> Vec<(Builtin, Vec<Expr>)> {
+        match self {
+            Self::Term(_) => Vec::new(),
+            Self::Builtin(b, n) if b.is_step() => {
+                let mut s = vec![(*b, n.iter().map(|a| *a.clone()).collect())];
+                s.extend(n.iter().flat_map(|a| a.steps()));
+                s
+            },
+            Self::Fn(_, n) | Self::Builtin(_, n) => n.iter().flat_map(|a| a.steps()).collect(),
+            Self::Add(n) | Self::Mul(n) | Self::List(n) | Self::Vector(n) => n.iter().flat_map(|a| a.steps()).collect(),
+            Self::Pow(a, b) | Self::Range(a, b) => [a.steps(), b.steps()].concat(),
+            Self::Series(_, k, a, b, n) => {
+                let inner = n.steps().into_iter().filter(|(_, args)| !args.iter().any(|a| a.mentions(k)));
+                [a.steps(), b.steps(), inner.collect()].concat()
+            },
+            Self::Reduce(_, a) => a.steps(),
+            Self::Matrix(m) => m.iter().flatten().flat_map(|a| a.steps()).collect(),
+        }
+    }
+
+    /// Splits `self` into `c + d*k`, where `c` and `d` don't contain `k`.
+    fn linear_in(&self, k: &str) -> Option<(Expr, Expr)> {
+        if !self.mentions(k) { return Some((self.clone(), *f::num(0.0))) }
+        match self {
+            Self::Term(Term::Var(_)) => Some((*f::num(0.0), *f::num(1.0))),
+            Self::Add(n) => {
+                let (mut c, mut d) = (Vec::new(), Vec::new());
+                for a in n {
+                    let (ac, ad) = a.linear_in(k)?;
+                    c.push(ac);
+                    d.push(ad);
+                }
+                Some((Self::Add(c), Self::Add(d)))
+            },
+            Self::Mul(n) => {
+                // Only one factor can contain `k`, the rest scale it.
+                let (with, rest): (Vec<Expr>, Vec<Expr>) = n.iter().cloned().partition(|a| a.mentions(k));
+                if with.len() != 1 { return None }
+                let (c, d) = with[0].linear_in(k)?;
+                Some((Self::Mul([rest.clone(), vec![c]].concat()), Self::Mul([rest, vec![d]].concat())))
+            },
+            _ => None,
+        }
+    }
+
+    /// Splits `self` into `c * r^k`, where `c` and `r` don't contain `k`.
+    fn geometric_in(&self, k: &str) -> Option<(Expr, Expr)> {
+        match self {
+            Self::Pow(r, p) if !r.mentions(k) => {
+                // r^(c + d*k) = r^c * (r^d)^k
+                let (c, d) = p.linear_in(k)?;
+                Some((*f::pow(r.clone(), c.r#box()), *f::pow(r.clone(), d.r#box())))
+            },
+            Self::Mul(n) => {
+                let (with, rest): (Vec<Expr>, Vec<Expr>) = n.iter().cloned().partition(|a| a.mentions(k));
+                if with.len() != 1 { return None }
+                let (c, r) = with[0].geometric_in(k)?;
+                Some((Self::Mul([rest, vec![c]].concat()), r))
+            },
+            _ => None,
         }
     }
 
+    /// Evaluates `self`, with `vars` giving values to its free variables. Recursive functions
+    /// that haven't been expanded are called from `funcs`.
+    pub fn evaluate(&self, vars: &HashMap<String, Complex64>, funcs: &HashMap<String, Func>) -> Complex64 {
+        self.evaluate_as(vars, funcs, F64_PRECISION)
+    }
+
     /// Checks if `self` is negative
     pub fn is_neg(&self) -> bool {
         match self {
@@ -435,6 +1219,13 @@ impl Expr {
             _ => false,
         }
     }
+    /// Checks if `self` is a positive real constant.
+    pub fn is_positive(&self) -> bool {
+        match self {
+            Self::Term(t) => t.is_positive(),
+            _ => false,
+        }
+    }
     /// Checks if `self` is a constant term.
     pub fn is_const(&self) -> bool {
         match self {
@@ -443,6 +1234,14 @@ impl Expr {
         }
     }
 
+    /// Checks if `self` is a number, a constant term that isn't symbolic.
+    pub fn is_number(&self) -> bool {
+        match self {
+            Self::Term(t) => t.is_number(),
+            _ => false,
+        }
+    }
+
     /// Force `self` into a const `Term`, panics if it can't.
     pub fn force_const(&self) -> Term {
         match self {
@@ -454,7 +1253,10 @@ impl Expr {
     pub fn order_num(&self) -> u8 {
         match self {
             Self::Term(Term::Real(_)) => 0,
+            Self::Term(Term::Rational(_)) => 0,
             Self::Term(Term::Complex(_)) => 0,
+            // * symbolic constants aren't folded, so they go with the variables
+            Self::Term(Term::Const(_)) => 1,
             Self::Term(Term::Var(_)) => 1,
             _ => 2,
         }
@@ -478,30 +1280,518 @@ impl PartialOrd for Expr {
 }
 
 
+/// A function that's built into the language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Builtin {
+    Ln,
+    /// `log{base}(a)`, takes the base first.
+    Log,
+    /// `root{n}(a)`, takes the degree first.
+    Root,
+    Sqrt,
+    Cbrt,
+
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asin,
+    Acos,
+    Atan,
+    Asinh,
+    Acosh,
+    Atanh,
+
+    Abs,
+    Re,
+    Im,
+    Arg,
+    Conj,
+    /// `polar(r, theta)`, the complex number `r * e^(i*theta)`.
+    Polar,
+
+    // These are worked out in `special`.
+    Gamma,
+    /// The log of `gamma`, see `special::lgamma` for its branches.
+    Lgamma,
+    /// The derivative of `lgamma`.
+    Digamma,
+    /// `n!`, the same as `gamma(n + 1)`.
+    Factorial,
+    /// `beta(a, b) = gamma(a) gamma(b) / gamma(a + b)`
+    Beta,
+    Erf,
+    Erfc,
+    /// `besselj{n}(x)`, the Bessel function of the first kind. Takes the order first, which has
+    /// to be a whole number.
+    BesselJ,
+    /// `bessely{n}(x)`, the Bessel function of the second kind. Takes the order first.
+    BesselY,
+    /// The Riemann zeta function.
+    Zeta,
+
+    // These only take real numbers, and give NaN for anything else.
+    Floor,
+    Ceil,
+    /// Rounds to the closest whole number, with halves rounded away from 0.
+    Round,
+    /// -1, 0 or 1.
+    Sign,
+    /// `mod(a, b)`, which has the sign of `b`.
+    Mod,
+    /// The greatest common divisor of whole numbers. Takes any number of inputs.
+    Gcd,
+    /// The least common multiple of whole numbers. Takes any number of inputs.
+    Lcm,
+    /// `nCr(n, k)`, the number of ways to pick `k` of `n` things.
+    NCr,
+    /// `nPr(n, k)`, the number of ways to line up `k` of `n` things.
+    NPr,
+
+    /// The input with the largest real part. Takes any number of inputs.
+    Max,
+    /// The input with the smallest real part. Takes any number of inputs.
+    Min,
+
+    // These take vectors and matrices, and are worked out symbolically by `resolve_linalg`.
+    Dot,
+    Cross,
+    Det,
+    Inv,
+    Transpose,
+}
+impl Builtin {
+    /// Applies the function to constant inputs.
+    /// Results from real inputs stay real unless they leave the real line (like `ln(-1)`).
+    pub fn apply(&self, args: &[Term]) -> Term {
+        if let Some(t) = self.apply_exact(args) {
+            return t;
+        }
+        let c = self.apply_complex(&args.iter().map(|a| a.as_complex()).collect::<Vec<Complex64>>());
+        // * so `floor(2.5)` is exactly 2
+        if self.is_whole_valued() { return Term::number(c.re) }
+        if self.is_real_valued() { return Term::Real(c.re) }
+        Term::inexact(c)
+    }
+
+    /// Applies the function to rational inputs, if the result is rational too.
+    fn apply_exact(&self, args: &[Term]) -> Option<Term> {
+        let a: Vec<&BigRational> = args.iter().map(|a| match a { Term::Rational(r) => Some(r), _ => None }).collect::<Option<_>>()?;
+        let r = match self {
+            Self::Sqrt => exact_pow(a[0], &BigRational::new(1.into(), 2.into()))?,
+            // * the real cube root, like `apply_complex`
+            Self::Cbrt => {
+                let r = exact_pow(&a[0].abs(), &BigRational::new(1.into(), 3.into()))?;
+                if a[0].is_negative() { -r } else { r }
+            },
+            Self::Root => {
+                if a[0].is_zero() { return None }
+                // * the real root for odd `n`, like `odd_root`
+                let odd = a[0].is_integer() && a[0].numer().is_odd();
+                if a[1].is_negative() & odd { -exact_pow(&-a[1], &a[0].recip())? } else { exact_pow(a[1], &a[0].recip())? }
+            },
+            Self::Abs => a[0].abs(),
+            Self::Re | Self::Conj => a[0].clone(),
+            Self::Im => BigRational::zero(),
+            Self::Arg if !a[0].is_negative() => BigRational::zero(),
+            Self::Max => a.into_iter().max()?.clone(),
+            Self::Min => a.into_iter().min()?.clone(),
+
+            Self::Gamma => factorial(&(a[0] - BigRational::one()))?,
+            Self::Factorial => factorial(a[0])?,
+            // (a-1)! (b-1)! / (a+b-1)!
+            Self::Beta if a[0].is_positive() & a[1].is_positive() => {
+                let one = BigRational::one();
+                factorial(&(a[0] - &one))? * factorial(&(a[1] - &one))? / factorial(&(a[0] + a[1] - &one))?
+            },
+            Self::Lgamma if a[0].is_one() | (a[0] == &BigRational::from_integer(2.into())) => BigRational::zero(),
+            Self::Erf if a[0].is_zero() => BigRational::zero(),
+            Self::Erfc if a[0].is_zero() => BigRational::one(),
+            Self::BesselJ if a[1].is_zero() & a[0].is_integer() => {
+                if a[0].is_zero() { BigRational::one() } else { BigRational::zero() }
+            },
+            // zeta(-n) = -B_(n+1) / (n+1), and zeta(0) = -1/2
+            Self::Zeta if a[0].is_integer() & !a[0].is_positive() => {
+                let n = (-a[0]).to_integer().to_usize().filter(|n| *n < special::BERNOULLI_LIMIT)?;
+                if n == 0 { return Some(Term::Rational(BigRational::new((-1).into(), 2.into()))) }
+                -special::bernoulli(n + 1) / BigRational::from_integer((n + 1).into())
+            },
+
+            Self::Floor => a[0].floor(),
+            Self::Ceil => a[0].ceil(),
+            Self::Round => a[0].round(),
+            Self::Sign => a[0].signum(),
+            Self::Mod if !a[1].is_zero() => a[0] - a[1] * (a[0] / a[1]).floor(),
+            Self::Gcd | Self::Lcm if a.iter().all(|a| a.is_integer()) => {
+                let n = a.iter().map(|a| a.to_integer());
+                let r = if *self == Self::Gcd { n.reduce(|a, b| a.gcd(&b)) } else { n.reduce(|a, b| a.lcm(&b)) };
+                BigRational::from_integer(r?.abs())
+            },
+            Self::NCr | Self::NPr => count(*self, a[0], a[1])?,
+            _ => return None,
+        };
+        Some(Term::Rational(r))
+    }
+
+    /// Checks if the function works on vectors and matrices instead of numbers.
+    pub fn is_linalg(&self) -> bool {
+        matches!(self, Self::Dot | Self::Cross | Self::Det | Self::Inv | Self::Transpose)
+    }
+
+    /// Checks if the function is one of the special functions, which are only worked out
+    /// with floats.
+    pub fn is_special(&self) -> bool {
+        matches!(self, Self::Gamma | Self::Lgamma | Self::Digamma | Self::Factorial | Self::Beta
+            | Self::Erf | Self::Erfc | Self::BesselJ | Self::BesselY | Self::Zeta)
+    }
+
+    /// Checks if the function only ever gives real results.
+    pub fn is_real_valued(&self) -> bool {
+        matches!(self, Self::Abs | Self::Re | Self::Im | Self::Arg) | self.is_real_only()
+    }
+
+    /// Checks if the function only takes real numbers.
+    pub fn is_real_only(&self) -> bool {
+        matches!(self, Self::Floor | Self::Ceil | Self::Round | Self::Sign | Self::Mod
+            | Self::Gcd | Self::Lcm | Self::NCr | Self::NPr)
+    }
+
+    /// Checks if the function only ever gives whole numbers.
+    pub fn is_whole_valued(&self) -> bool {
+        matches!(self, Self::Floor | Self::Ceil | Self::Round | Self::Sign | Self::Gcd | Self::Lcm)
+    }
+
+    /// Checks if the function jumps, so its graph is in pieces. See `piece`.
+    pub fn is_step(&self) -> bool {
+        matches!(self, Self::Floor | Self::Ceil | Self::Round | Self::Sign | Self::Mod)
+    }
+
+    /// Which piece of the graph of a step function its inputs are on. Inputs on the same
+    /// piece have no jumps between them.
+    pub fn piece(&self, a: &[Complex64]) -> f64 {
+        match self {
+            Self::Mod => (a[0].re / a[1].re).floor(),
+            _ => self.apply_complex(a).re,
+        }
+    }
+
+    /// Applies the function to complex inputs, with principal branches. Real inputs that give
+    /// real results stay on the real line (see `on_real_line`).
+    pub fn apply_complex(&self, a: &[Complex64]) -> Complex64 {
+        on_real_line(a, self.apply_principal(a))
+    }
+
+    fn apply_principal(&self, a: &[Complex64]) -> Complex64 {
+        match self {
+            Self::Ln => a[0].ln(),
+            Self::Log => a[1].ln() / a[0].ln(),
+            Self::Root => {
+                if (a[0].im == 0.0) & (a[1].im == 0.0) {
+                    if let Some(r) = odd_root(a[0].re, a[1].re) { return r.into() }
+                }
+                a[1].powc(a[0].inv())
+            },
+            Self::Sqrt => a[0].sqrt(),
+            Self::Cbrt => {
+                // The real cube root is more useful for real inputs than the principal one.
+                if a[0].im == 0.0 { return a[0].re.cbrt().into() }
+                a[0].cbrt()
+            },
+
+            Self::Sin => a[0].sin(),
+            Self::Cos => a[0].cos(),
+            Self::Tan => a[0].tan(),
+            Self::Sinh => a[0].sinh(),
+            Self::Cosh => a[0].cosh(),
+            Self::Tanh => a[0].tanh(),
+            Self::Asin => a[0].asin(),
+            Self::Acos => a[0].acos(),
+            Self::Atan => a[0].atan(),
+            Self::Asinh => a[0].asinh(),
+            Self::Acosh => a[0].acosh(),
+            Self::Atanh => a[0].atanh(),
+
+            Self::Abs => a[0].norm().into(),
+            Self::Re => a[0].re.into(),
+            Self::Im => a[0].im.into(),
+            Self::Arg => a[0].arg().into(),
+
+            Self::Conj => a[0].conj(),
+            Self::Polar => a[0] * (Complex64::I * a[1]).exp(),
+
+            Self::Max => a.iter().copied().reduce(|a, b| if b.re > a.re { b } else { a }).unwrap(),
+            Self::Min => a.iter().copied().reduce(|a, b| if b.re < a.re { b } else { a }).unwrap(),
+
+            Self::Gamma => special::gamma(a[0]),
+            Self::Lgamma => special::lgamma(a[0]),
+            Self::Digamma => special::digamma(a[0]),
+            Self::Factorial => special::gamma(a[0] + 1.0),
+            Self::Beta => special::beta(a[0], a[1]),
+            Self::Erf => special::erf(a[0]),
+            Self::Erfc => special::erfc(a[0]),
+            Self::BesselJ => special::bessel_j(a[0], a[1]),
+            Self::BesselY => special::bessel_y(a[0], a[1]),
+            Self::Zeta => special::zeta(a[0]),
+
+            Self::Floor => real_only(a, |a| a[0].floor()),
+            Self::Ceil => real_only(a, |a| a[0].ceil()),
+            Self::Round => real_only(a, |a| a[0].round()),
+            Self::Sign => real_only(a, |a| integer::sign(a[0])),
+            Self::Mod => real_only(a, |a| integer::modulo(a[0], a[1])),
+            Self::Gcd => real_only(a, |a| a.iter().copied().fold(0.0, integer::gcd)),
+            Self::Lcm => real_only(a, |a| a.iter().copied().fold(1.0, integer::lcm)),
+            Self::NCr => real_only(a, |a| integer::choose(a[0], a[1])),
+            Self::NPr => real_only(a, |a| integer::permute(a[0], a[1])),
+
+            Self::Dot | Self::Cross | Self::Det | Self::Inv | Self::Transpose => {
+                panic!("ERROR: {self:?} has to be resolved before evaluating!")
+            },
+        }
+    }
+}
+
+
+/// A function that turns a list into a single value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reducer {
+    Total,
+    Mean,
+    Max,
+    Min,
+    Gcd,
+    Lcm,
+    Len,
+}
+impl Reducer {
+    /// The value of the reducer on an empty list, if it has one.
+    pub fn empty(&self) -> Option<Expr> {
+        match self {
+            Self::Total | Self::Len => Some(*f::num(0.0)),
+            _ => None,
+        }
+    }
+
+    /// Applies the reducer to the items of a broadcast list. Lists that are empty have to have
+    /// been checked by `check_constant`.
+    pub fn apply(&self, n: Vec<Expr>) -> Expr {
+        if *self == Self::Len {
+            return *f::num(n.len() as f64);
+        }
+        if n.is_empty() {
+            return self.empty().unwrap_or_else(|| panic!("ERROR: can't take the {self:?} of an empty list!"));
+        }
+        let len = n.len() as f64;
+        match self {
+            Self::Total => Expr::Add(n),
+            Self::Mean => Expr::Mul(vec![Expr::Add(n), *f::inv(f::num(len))]),
+            Self::Max => Expr::Builtin(Builtin::Max, n.into_iter().map(|a| a.r#box()).collect()),
+            Self::Min => Expr::Builtin(Builtin::Min, n.into_iter().map(|a| a.r#box()).collect()),
+            Self::Gcd => Expr::Builtin(Builtin::Gcd, n.into_iter().map(|a| a.r#box()).collect()),
+            Self::Lcm => Expr::Builtin(Builtin::Lcm, n.into_iter().map(|a| a.r#box()).collect()),
+            Self::Len => unreachable!(),
+        }
+    }
+}
+
+/// `f` of the real numbers `a`, or NaN if any of them aren't real.
+fn real_only(a: &[Complex64], f: impl Fn(&[f64]) -> f64) -> Complex64 {
+    if a.iter().any(|a| a.im != 0.0) { return f64::NAN.into() }
+    f(&a.iter().map(|a| a.re).collect::<Vec<f64>>()).into()
+}
+
+/// `a + b`, with real numbers added as reals so they stay on the real line.
+pub fn add_complex(a: Complex64, b: Complex64) -> Complex64 {
+    if (a.im == 0.0) & (b.im == 0.0) { return Complex64::from(a.re + b.re) }
+    a + b
+}
+
+/// `a * b`, with real numbers multiplied as reals. As complex numbers `(-2) * (-3)` would be
+/// `6 - 0i`, and `1 * inf` would have a NaN imaginary part.
+pub fn mul_complex(a: Complex64, b: Complex64) -> Complex64 {
+    if (a.im == 0.0) & (b.im == 0.0) { return Complex64::from(a.re * b.re) }
+    a * b
+}
+
+/// `a^b`, keeping real results real where possible. Whole powers are worked out by
+/// repeated multiplication so `i^2` is exactly `-1`, and everything else takes the
+/// principal branch, so `(-8)^(1/3)` is `1 + 1.732i`.
+pub fn pow_complex(a: Complex64, b: Complex64) -> Complex64 {
+    let r = if b.im != 0.0 {
+        a.powc(b)
+    } else if (b.re.fract() == 0.0) & (b.re.abs() <= i32::MAX as f64) {
+        // * real bases stay real, which also makes `0^-1` infinite instead of NaN
+        if a.im == 0.0 { a.re.powi(b.re as i32).into() } else { a.powi(b.re as i32) }
+    } else if (a.im == 0.0) & (a.re >= 0.0) {
+        a.re.powf(b.re).into()
+    } else {
+        a.powf(b.re)
+    };
+    on_real_line(&[a, b], r)
+}
+
+/// Gives `r` a positive zero imaginary part when it and all of its `inputs` are on the real
+/// line. Complex arithmetic on real numbers can leave a negative zero (`cos(2)` is
+/// `-0.416 - 0i`), which puts the next branch cut on the wrong side: `sqrt(cos(2))` would be
+/// `-0.645i` where the real number `-0.416` gives `0.645i`.
+pub fn on_real_line(inputs: &[Complex64], r: Complex64) -> Complex64 {
+    if (r.im == 0.0) & inputs.iter().all(|a| a.im == 0.0) { return Complex64::from(r.re) }
+    r
+}
+
+/// The real `n`th root of a negative `x` when `n` is odd, like `cbrt`, so `root{3}(-8)` is
+/// `-2`. Anything else takes the principal root.
+pub fn odd_root(n: f64, x: f64) -> Option<f64> {
+    if (x < 0.0) & (n.rem_euclid(2.0) == 1.0) { return Some(-(-x).powf(n.recip())) }
+    None
+}
+
+
+/// The kind of an `Expr::Series`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Series {
+    Sum,
+    Prod,
+}
+impl Series {
+    /// Series with constant bounds that have at most this many terms are written out in full.
+    pub const UNROLL_LIMIT: i64 = 64;
+
+    /// The value of an empty series.
+    pub fn identity(&self) -> Term {
+        match self {
+            Self::Sum => Term::zero(),
+            Self::Prod => Term::one(),
+        }
+    }
+
+    /// The integer range a series runs over. Non-integer bounds are rounded inwards.
+    pub fn bounds(from: f64, to: f64) -> (i64, i64) {
+        (from.ceil() as i64, to.floor() as i64)
+    }
+
+    /// Tries to get rid of the series, either by writing it out when the bounds are constant
+    /// or with a closed form. Expects its inputs to have had constants reduced.
+    pub fn reduce(&self, k: &str, from: &Expr, to: &Expr, body: &Expr) -> Option<Expr> {
+        let bounds = from.as_number().zip(to.as_number())
+            .map(|(from, to)| Self::bounds(from.as_complex().re, to.as_complex().re));
+
+        if let Some((a, b)) = bounds {
+            if b < a { return Some(self.identity().into()) }
+            // Writing it out is exact everywhere, where a closed form can have holes.
+            if b - a < Self::UNROLL_LIMIT {
+                let n = (a..=b).map(|i| body.expand_vars(&vec![(k.to_string(), f::num(i as f64))])).collect();
+                return Some(match self {
+                    Self::Sum => Expr::Add(n),
+                    Self::Prod => Expr::Mul(n),
+                });
+            }
+        }
+
+        // The closed forms need whole bounds with `to >= from - 1`, like `evaluate` uses.
+        let (lo, hi) = match bounds {
+            Some((a, b)) => (Expr::from(Term::number(a as f64)), Expr::from(Term::number(b as f64))),
+            None => {
+                let lo = f::ceil(from.clone().r#box());
+                let hi = f::builtin(Builtin::Max, vec![f::floor(to.clone().r#box()), f::sub(lo.clone(), f::num(1.0))]);
+                (*lo, *hi)
+            },
+        };
+        self.closed_form(k, &lo, &hi, body)
+    }
+
+    /// Closed forms for series that don't depend on `k`, arithmetic series, and geometric series
+    /// whose ratio is a number.
+    /// These assume the bounds are integers with `to >= from - 1`.
+    fn closed_form(&self, k: &str, from: &Expr, to: &Expr, body: &Expr) -> Option<Expr> {
+        let (from, to) = (from.clone().r#box(), to.clone().r#box());
+        let count = f::add(f::sub(to.clone(), from.clone()), f::num(1.0));
+        // from + (from+1) + ... + to
+        let k_sum = f::div(f::mul(f::add(from.clone(), to.clone()), count.clone()), f::num(2.0));
+
+        match self {
+            Self::Sum => {
+                if let Some((c, d)) = body.linear_in(k) {
+                    return Some(*f::add(f::mul(count, c.r#box()), f::mul(d.r#box(), k_sum)));
+                }
+                let (c, r) = body.geometric_in(k)?;
+                // * a ratio that isn't a number could be 1, where dividing by `1 - r` gives
+                //   0/0, so that's left to be summed term by term
+                let r = r.as_number()?;
+                if r.is_one() { return Some(*f::mul(count, c.r#box())) }
+                // c * (r^from - r^(to+1)) / (1 - r)
+                let r = f::term(r);
+                Some(*f::div(
+                    f::mul(c.r#box(), f::sub(f::pow(r.clone(), from), f::pow(r.clone(), f::add(to, f::num(1.0))))),
+                    f::sub(f::num(1.0), r),
+                ))
+            },
+            Self::Prod => {
+                if !body.mentions(k) {
+                    return Some(*f::pow(body.clone().r#box(), count));
+                }
+                let (c, r) = body.geometric_in(k)?;
+                Some(*f::mul(f::pow(c.r#box(), count), f::pow(r.r#box(), k_sum)))
+            },
+        }
+    }
+}
+
+
 /// A term in the expression tree.
 #[derive(Debug, Clone)]
 pub enum Term {
     Real(f64),
+    /// An exact number. Arithmetic on these stays exact, and only turns into `Real` when
+    /// it can't (like `sqrt(2)`) or when a `Real` is mixed in.
+    Rational(BigRational),
     Complex(Complex64),
+    /// `pi`, `e` or `i`, kept exact until it's evaluated.
+    Const(Constant),
     Var(String),
 }
 impl Term {
+    /// `c` as a term, `Real` if it's on the real line. A negative zero imaginary part is kept,
+    /// since it decides which side of a branch cut `c` is on.
+    pub fn inexact(c: Complex64) -> Self {
+        if (c.im == 0.0) & c.im.is_sign_positive() { return Self::Real(c.re) }
+        Self::Complex(c)
+    }
+
+    /// `n` as a term, exact if it's a whole number.
+    pub fn number(n: f64) -> Self {
+        // * whole numbers past 2^53 aren't exact as floats anyway
+        if (n.fract() == 0.0) & (n.abs() <= 9007199254740992.0) {
+            return Self::Rational(BigRational::from_integer(BigInt::from(n as i64)));
+        }
+        Self::Real(n)
+    }
 
     /// Checks if `self` is const.
     pub fn is_const(&self) -> bool {
         match self {
             Self::Var(_) => false,
             Self::Real(_) => true,
+            Self::Rational(_) => true,
             Self::Complex(_) => true,
+            Self::Const(_) => true,
         }
     }
 
+    /// Checks if `self` is a number, so constant reduction can fold it.
+    pub fn is_number(&self) -> bool {
+        matches!(self, Self::Real(_) | Self::Rational(_) | Self::Complex(_))
+    }
+
     /// Panics if `self` is not const.
     pub fn force_const(&self) -> Self {
         match self {
             Self::Var(_) => panic!(),
             Self::Real(_) => self.clone(),
+            Self::Rational(_) => self.clone(),
             Self::Complex(_) => self.clone(),
+            Self::Const(_) => self.clone(),
         }
     }
 
@@ -517,13 +1807,35 @@ impl Term {
         return *t;
     }
 
+    /// Gets `self` as a complex number, panics if it's not const.
+    pub fn as_complex(&self) -> Complex64 {
+        match self {
+            Self::Real(n) => Complex64::from(n),
+            Self::Rational(r) => Complex64::from(to_f64(r)),
+            Self::Complex(n) => *n,
+            Self::Const(c) => c.value().as_complex(),
+            Self::Var(_) => panic!(),
+        }
+    }
+
     pub fn is_neg_one(&self) -> bool {
         match self {
             Self::Real(n) => (-n).is_one(),
+            Self::Rational(r) => (-r).is_one(),
             Self::Complex(n) => (-n).is_one(),
             _ => false,
         }
     }
+
+    pub fn is_positive(&self) -> bool {
+        match self {
+            Self::Real(n) => *n > 0.0,
+            Self::Rational(r) => r.is_positive(),
+            Self::Complex(n) => (n.im == 0.0) & (n.re > 0.0),
+            Self::Const(c) => *c != Constant::I,
+            Self::Var(_) => false,
+        }
+    }
 }
 impl FromStr for Term {
     type Err = ();
@@ -542,6 +1854,11 @@ impl From<f64> for Term {
         Term::Real(value)
     }
 }
+impl From<BigRational> for Term {
+    fn from(value: BigRational) -> Self {
+        Term::Rational(value)
+    }
+}
 impl From<Complex64> for Term {
     fn from(value: Complex64) -> Self {
         Term::Complex(value)
@@ -549,12 +1866,13 @@ impl From<Complex64> for Term {
 }
 impl Zero for Term {
     fn zero() -> Self {
-        Self::Real(f64::zero())
+        Self::Rational(BigRational::zero())
     }
 
     fn is_zero(&self) -> bool {
         match self {
             Self::Real(n) => n.is_zero(),
+            Self::Rational(r) => r.is_zero(),
             Self::Complex(n) => n.is_zero(),
             _ => false,
         }
@@ -562,13 +1880,14 @@ impl Zero for Term {
 }
 impl One for Term {
     fn one() -> Self {
-        Self::Real(f64::one())
+        Self::Rational(BigRational::one())
     }
     
     fn is_one(&self) -> bool {
         match self {
             Self::Real(n) => n.is_one(),
-            Self::Complex(n) => n.is_zero(),
+            Self::Rational(r) => r.is_one(),
+            Self::Complex(n) => n.is_one(),
             _ => false,
         }
     }
@@ -578,11 +1897,12 @@ impl Add<Term> for Term {
 
     fn add(self, rhs: Term) -> Self::Output {
         match (self, rhs) {
-            (Term::Real(a), Term::Real(b)) => (a+b).into(),
-            (Term::Real(a), Term::Complex(b)) => (a+b).into(),
-            (Term::Complex(a), Term::Real(b)) => (a+b).into(),
-            (Term::Complex(a), Term::Complex(b)) => (a+b).into(),
-            _ => panic!(),
+            (Term::Rational(a), Term::Rational(b)) => (a+b).into(),
+            // Constants that can't stay symbolic become floats.
+            (Term::Const(a), b) => a.value() + b,
+            (a, Term::Const(b)) => a + b.value(),
+            // Mixing in anything inexact makes the result inexact.
+            (a, b) => inexact(&a, &b, add_complex),
         }
     }
 }
@@ -591,11 +1911,10 @@ impl Mul<Term> for Term {
 
     fn mul(self, rhs: Term) -> Self::Output {
         match (self, rhs) {
-            (Term::Real(a), Term::Real(b)) => (a*b).into(),
-            (Term::Real(a), Term::Complex(b)) => (a*b).into(),
-            (Term::Complex(a), Term::Real(b)) => (a*b).into(),
-            (Term::Complex(a), Term::Complex(b)) => (a*b).into(),
-            _ => panic!(),
+            (Term::Rational(a), Term::Rational(b)) => (a*b).into(),
+            (Term::Const(a), b) => a.value() * b,
+            (a, Term::Const(b)) => a * b.value(),
+            (a, b) => inexact(&a, &b, mul_complex),
         }
     }
 }
@@ -604,27 +1923,30 @@ impl Pow<Term> for Term {
 
     fn pow(self, rhs: Term) -> Self::Output {
         match (self, rhs) {
-            (Term::Real(a), Term::Real(b)) => (a.pow(b)).into(),
-            (Term::Real(a), Term::Complex(b)) => (a.powc(b)).into(),
-            (Term::Complex(a), Term::Real(b)) => (a.pow(b)).into(),
-            (Term::Complex(a), Term::Complex(b)) => (a.pow(b)).into(),
-            _ => panic!(),
+            (Term::Const(a), b) => a.value().pow(b),
+            (a, Term::Const(b)) => a.pow(b.value()),
+            (Term::Rational(a), Term::Rational(b)) if exact_pow(&a, &b).is_some() => exact_pow(&a, &b).unwrap().into(),
+            (a, b) => inexact(&a, &b, pow_complex),
         }
     }
 }
+
+/// Works out `op` on constant terms the same way evaluating them would, so folding a
+/// constant never changes its value.
+fn inexact(a: &Term, b: &Term, op: fn(Complex64, Complex64) -> Complex64) -> Term {
+    let (a, b) = (a.as_complex(), b.as_complex());
+    Term::inexact(op(a, b))
+}
+
 /// Orders terms based on how they should be ordered in expressions. (less -> more)
 impl PartialOrd for Term {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         match (self, other) {
-            (Term::Real(_), Term::Real(_)) => Some(std::cmp::Ordering::Equal),
-            (Term::Real(_), Term::Complex(_)) => Some(std::cmp::Ordering::Equal),
-            (Term::Real(_), Term::Var(_)) => Some(std::cmp::Ordering::Less),
-            (Term::Complex(_), Term::Real(_)) => Some(std::cmp::Ordering::Equal),
-            (Term::Complex(_), Term::Complex(_)) => Some(std::cmp::Ordering::Equal),
-            (Term::Complex(_), Term::Var(_)) => Some(std::cmp::Ordering::Less),
-            (Term::Var(_), Term::Real(_)) => Some(std::cmp::Ordering::Greater),
-            (Term::Var(_), Term::Complex(_)) => Some(std::cmp::Ordering::Greater),
             (Term::Var(a), Term::Var(b)) => a.partial_cmp(b),
+            (Term::Var(_), _) => Some(std::cmp::Ordering::Greater),
+            (_, Term::Var(_)) => Some(std::cmp::Ordering::Less),
+            // Constants are all equal.
+            _ => Some(std::cmp::Ordering::Equal),
         }
     }
 }
@@ -632,12 +1954,65 @@ impl PartialOrd for Term {
 impl PartialEq for Term {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
+            (Self::Rational(a), Self::Rational(b)) => a == b,
+            // * compared as floats, the same way they'd be evaluated
+            (Self::Rational(a), b) => Self::Real(to_f64(a)) == *b,
+            (a, Self::Rational(b)) => *a == Self::Real(to_f64(b)),
             (Self::Real(a), Self::Real(b)) => a == b,
             (Self::Real(a), Self::Complex(b)) => Complex64::from(a) == *b,
             (Self::Complex(a), Self::Real(b)) => a == &Complex64::from(b),
             (Self::Complex(a), Self::Complex(b)) => a == b,
+            (Self::Const(a), Self::Const(b)) => a == b,
             (Self::Var(a), Self::Var(b)) => a == b,
             _ => false,
         }
     }
-}
\ No newline at end of file
+}
+
+/// The largest power (and root) rationals are raised to exactly. Past this the numbers
+/// get too big to be worth it.
+const EXACT_POW_LIMIT: i32 = 256;
+/// Factorials past this are left to floats, which are infinite by then anyway.
+const EXACT_FACTORIAL_LIMIT: u32 = 1000;
+
+/// The closest float to `r`.
+fn to_f64(r: &BigRational) -> f64 {
+    r.to_f64().unwrap_or(f64::NAN)
+}
+
+/// `n!` for whole numbers `0 <= n <= EXACT_FACTORIAL_LIMIT`.
+fn factorial(n: &BigRational) -> Option<BigRational> {
+    if !n.is_integer() || n.is_negative() { return None }
+    let n = n.to_integer().to_u32().filter(|n| *n <= EXACT_FACTORIAL_LIMIT)?;
+    Some(BigRational::from_integer(special::factorial(n)))
+}
+
+/// `nCr(n, k)` or `nPr(n, k)`, for whole numbers `k <= EXACT_FACTORIAL_LIMIT`.
+fn count(b: Builtin, n: &BigRational, k: &BigRational) -> Option<BigRational> {
+    if !k.is_integer() { return None }
+    // * there are no ways to pick a negative number of things, and nPr isn't defined for them
+    if k.is_negative() { return (b == Builtin::NCr).then(BigRational::zero) }
+    let k = k.to_integer().to_u32().filter(|k| *k <= EXACT_FACTORIAL_LIMIT)?;
+    let p = (0..k).fold(BigRational::one(), |p, j| p * (n - BigRational::from_integer(j.into())));
+    if b == Builtin::NPr { return Some(p) }
+    Some(p / BigRational::from_integer(special::factorial(k)))
+}
+
+/// `a^b`, if it's rational. Only whole powers and roots of non-negative numbers that are
+/// perfect powers (like `4^(3/2)`) are.
+fn exact_pow(a: &BigRational, b: &BigRational) -> Option<BigRational> {
+    let n = b.numer().to_i32().filter(|n| n.abs() <= EXACT_POW_LIMIT)?;
+    let d = b.denom().to_u32().filter(|d| *d <= EXACT_POW_LIMIT as u32)?;
+    if (n < 0) & a.is_zero() { return None }
+
+    let root = if d == 1 {
+        a.clone()
+    } else {
+        // * negative numbers have complex principal roots
+        if a.is_negative() { return None }
+        let (p, q) = (a.numer().nth_root(d), a.denom().nth_root(d));
+        if (Pow::pow(&p, d) != *a.numer()) | (Pow::pow(&q, d) != *a.denom()) { return None }
+        BigRational::new(p, q)
+    };
+    Some(Pow::pow(root, n))
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use num::{One, Zero};
+use num_complex::Complex64;
+
+use crate::constant::Constant;
+use crate::expr::{f::*, Context, Expr, Term};
+use crate::test_util::{ratio, reduced, var};
+
+
+#[test]
+fn whole_numbers_are_exact() {
+    assert!(matches!(Term::number(3.0), Term::Rational(_)));
+    assert!(matches!(Term::number(0.5), Term::Real(_)));
+    assert!(matches!(Term::number(1e300), Term::Real(_)));
+}
+
+#[test]
+fn thirds_add_up_to_one() {
+    let third = div(num(1.0), num(3.0));
+    let e = reduced(*add(add(third.clone(), third.clone()), third));
+    assert!(matches!(&e, Expr::Term(Term::Rational(r)) if r.is_one()));
+    assert!(e.is_one());
+
+    // 0.1 + 0.2 isn't 0.3 in floats, but 1/10 + 2/10 is 3/10.
+    let e = reduced(*add(div(num(1.0), num(10.0)), div(num(2.0), num(10.0))));
+    assert_eq!(e, Expr::Term(ratio(3, 10)));
+}
+
+#[test]
+fn exact_powers_and_roots() {
+    assert_eq!(reduced(*pow(num(2.0), num(-2.0))), Expr::Term(ratio(1, 4)));
+    assert_eq!(reduced(*pow(div(num(4.0), num(9.0)), div(num(3.0), num(2.0)))), Expr::Term(ratio(8, 27)));
+    assert_eq!(reduced(*sqrt(div(num(4.0), num(9.0)))), Expr::Term(ratio(2, 3)));
+    assert_eq!(reduced(*cbrt(num(-27.0))), Expr::Term(ratio(-3, 1)));
+    assert_eq!(reduced(*root(num(4.0), num(81.0))), Expr::Term(ratio(3, 1)));
+    assert_eq!(reduced(*abs(num(-5.0))), Expr::Term(ratio(5, 1)));
+    assert_eq!(reduced(*builtin(crate::expr::Builtin::Max, vec![num(2.0), div(num(7.0), num(3.0))])), Expr::Term(ratio(7, 3)));
+}
+
+#[test]
+fn irrational_results_are_floats() {
+    let e = reduced(*sqrt(num(2.0)));
+    assert!(matches!(e, Expr::Term(Term::Real(v)) if v == 2f64.sqrt()));
+    let e = reduced(*sin(num(1.0)));
+    assert!(matches!(e, Expr::Term(Term::Real(_))));
+    // Principal roots of negative numbers aren't real, let alone rational.
+    let e = reduced(*pow(num(-8.0), div(num(1.0), num(3.0))));
+    assert!(!matches!(e, Expr::Term(Term::Rational(_))));
+    // Dividing by 0 isn't exact.
+    let e = reduced(*div(num(1.0), num(0.0)));
+    assert!(matches!(e, Expr::Term(Term::Real(v)) if v == f64::INFINITY));
+}
+
+#[test]
+fn floats_make_results_floats() {
+    let e = reduced(*add(num(1.0), num(0.5)));
+    assert!(matches!(e, Expr::Term(Term::Real(v)) if v == 1.5));
+    let e = reduced(*mul(div(num(1.0), num(3.0)), term(Term::Real(3.0))));
+    assert!(matches!(e, Expr::Term(Term::Real(_))));
+}
+
+#[test]
+fn exact_ones_simplify() {
+    // (1/3 * 3) * x is x
+    let e = reduced(*mul(mul(div(num(1.0), num(3.0)), num(3.0)), var("x"))).special_cases();
+    assert_eq!(e, Expr::Mul(vec![*var("x")]));
+}
+
+fn constant(c: Constant) -> Box<Expr> {
+    term(Term::Const(c))
+}
+
+fn simplified(e: Expr) -> Expr {
+    let mut ctx = Context::new();
+    ctx.def_var("out", e).unwrap();
+    ctx.simplify_for_var("out").0
+}
+
+#[test]
+fn eulers_formula_is_exact() {
+    // e^(i*pi) + 1 = 0, with the names `Context` gives the constants.
+    let e = simplified(*add(pow(var("e"), mul(var("i"), var("pi"))), num(1.0)));
+    assert!(matches!(&e, Expr::Term(Term::Rational(r)) if r.is_zero()), "{e:?}");
+    assert_eq!(reduced(*pow(constant(Constant::E), mul(div(num(1.0), num(2.0)), mul(constant(Constant::I), constant(Constant::Pi))))), *constant(Constant::I));
+    assert_eq!(reduced(*mul(constant(Constant::I), constant(Constant::I))), Expr::Term(ratio(-1, 1)));
+    assert_eq!(reduced(*pow(constant(Constant::I), num(4.0))), Expr::Term(ratio(1, 1)));
+    assert_eq!(reduced(*pow(constant(Constant::E), ln(var("x")))), *var("x"));
+    assert_eq!(reduced(*ln(constant(Constant::E))), Expr::Term(ratio(1, 1)));
+}
+
+#[test]
+fn trig_of_pi_is_exact() {
+    let pi = constant(Constant::Pi);
+    assert!(reduced(*sin(pi.clone())).is_zero());
+    assert_eq!(reduced(*cos(mul(num(2.0), pi.clone()))), Expr::Term(ratio(1, 1)));
+    assert_eq!(reduced(*sin(div(pi.clone(), num(6.0)))), Expr::Term(ratio(1, 2)));
+    assert_eq!(reduced(*cos(mul(div(num(2.0), num(3.0)), pi.clone()))), Expr::Term(ratio(-1, 2)));
+    assert!(reduced(*tan(mul(num(-3.0), pi.clone()))).is_zero());
+    // sin(pi/4) is irrational, so it's left for evaluation.
+    assert!(!reduced(*sin(div(pi.clone(), num(4.0)))).is_const());
+}
+
+#[test]
+fn constants_stay_symbolic_until_evaluated() {
+    let e = reduced(*mul(num(2.0), constant(Constant::Pi)));
+    assert_eq!(e, Expr::Mul(vec![*num(2.0), *constant(Constant::Pi)]));
+    let v = e.evaluate(&HashMap::new(), &HashMap::new());
+    assert_eq!(v, Complex64::from(std::f64::consts::TAU));
+    // Ranges still need numbers.
+    let e = Expr::Range(num(0.0), mul(num(2.0), constant(Constant::Pi))).broadcast();
+    assert_eq!(e.items().len(), 7);
+}
+
+fn value(e: &Expr) -> Complex64 {
+    e.evaluate(&HashMap::new(), &HashMap::new())
+}
+
+#[test]
+fn complex_ones_and_zeros() {
+    assert!(!Term::Complex(Complex64::new(0.0, 0.0)).is_one());
+    assert!(Term::Complex(Complex64::new(1.0, 0.0)).is_one());
+    assert!(Term::Complex(Complex64::new(-1.0, 0.0)).is_neg_one());
+    // A complex zero factor isn't dropped like a one would be.
+    let e = reduced(*mul(term(Term::Complex(Complex64::new(0.0, 0.0))), var("x")));
+    assert!(!matches!(&e, Expr::Term(Term::Var(_))), "{e:?}");
+}
+
+#[test]
+fn folding_takes_principal_branches() {
+    // (-8)^(1/3) is the principal root, not -2.
+    let Expr::Term(Term::Complex(c)) = reduced(*pow(num(-8.0), div(num(1.0), num(3.0)))) else { panic!() };
+    assert!((c - Complex64::new(1.0, 3f64.sqrt())).norm() < 1e-12, "{c}");
+    // cos(2) is negative, so its square root is on the positive imaginary axis both ways.
+    let e = sqrt(cos(num(2.0)));
+    let folded = value(&reduced(*e.clone()));
+    let direct = value(&e);
+    assert!(folded.im > 0.0 && (folded - direct).norm() == 0.0, "{folded} but {direct}");
+    // Whole powers are exact.
+    let one_i = Complex64::new(1.0, 1.0);
+    assert_eq!(reduced(*pow(term(Term::Complex(one_i)), num(2.0))), Expr::Term(Term::Complex(Complex64::new(0.0, 2.0))));
+    assert_eq!(reduced(*pow(term(Term::Complex(Complex64::I)), num(2.0))), Expr::Term(Term::Real(-1.0)));
+}
+
+/// A random constant expression, from a linear congruential generator.
+fn random_const(seed: &mut u64, depth: u32) -> Box<Expr> {
+    let mut next = |n: u64| {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (*seed >> 33) % n
+    };
+    if depth == 0 || next(4) == 0 {
+        return match next(6) {
+            0 => num(next(7) as f64 - 3.0),
+            1 => term(ratio(next(9) as i64 - 4, next(4) as i64 + 1)),
+            2 => num((next(200) as f64 - 100.0) / 37.0),
+            3 => term(Term::Complex(Complex64::new(next(5) as f64 - 2.0, next(5) as f64 - 2.0))),
+            4 => constant([Constant::Pi, Constant::E, Constant::I][next(3) as usize]),
+            _ => num(0.0),
+        };
+    }
+    let k = next(9);
+    let (a, b) = (random_const(seed, depth - 1), random_const(seed, depth - 1));
+    match k {
+        0 | 1 => add(a, b),
+        2 => mul(a, b),
+        3 => pow(a, b),
+        4 => sqrt(a),
+        5 => ln(a),
+        6 => sin(a),
+        7 => pow(constant(Constant::E), a),
+        _ => abs(a),
+    }
+}
+
+#[test]
+fn folding_agrees_with_evaluating() {
+    let close = |a: Complex64, b: Complex64| (a - b).norm() <= 1e-9 * (1.0 + b.norm());
+    let mut seed = 7;
+    for _ in 0..2000 {
+        let e = random_const(&mut seed, 3);
+        let direct = value(&e);
+        let folded = value(&reduced(*e.clone()));
+        // * overflowing or NaN results don't have a value to agree on
+        if !direct.is_finite() || direct.norm() > 1e6 { continue }
+        // Exact constants can make folding more accurate than floats, like `sin(pi) = 0`,
+        // which can also land exactly on a pole that floats miss.
+        if !folded.is_finite() { continue }
+        let precise = e.evaluate_precise(&HashMap::new(), &HashMap::new(), 256);
+        assert!(close(folded, direct) || close(folded, precise), "{e:?}: {folded} but {direct}");
+    }
+}
+
+#[test]
+fn odd_roots_are_real() {
+    // Like cbrt, and unlike (-8)^(1/3).
+    assert_eq!(reduced(*root(num(3.0), num(-8.0))), Expr::Term(ratio(-2, 1)));
+    assert_eq!(reduced(*root(num(5.0), div(num(-1.0), num(32.0)))), Expr::Term(ratio(-1, 2)));
+    assert!((value(&root(num(3.0), num(-2.0))) - Complex64::from(-2f64.cbrt())).norm() < 1e-15);
+    // Even roots still take the principal branch.
+    let Expr::Term(Term::Complex(c)) = reduced(*root(num(2.0), num(-4.0))) else { panic!() };
+    assert!((c - Complex64::new(0.0, 2.0)).norm() < 1e-15, "{c}");
+    // The slope of root{3}(x) at -8 is 1/12, from either side.
+    let d = root(num(3.0), var("x")).derivative("x").unwrap();
+    let at = HashMap::from([("x".to_string(), Complex64::from(-8.0))]);
+    assert!((d.evaluate(&at, &HashMap::new()) - Complex64::from(1.0 / 12.0)).norm() < 1e-15);
+}
+
+#[test]
+fn imaginary_literals_and_polar() {
+    let tree = |s: &str| reduced(*crate::convert::convert_expr(crate::parse::expr_parse(s)));
+    let i = || constant(Constant::I);
+    assert_eq!(tree("4i"), *mul(num(4.0), i()));
+    // The same as `4*i*4*i`, which is exactly -16.
+    assert_eq!(tree("4i*4i"), Expr::Term(ratio(-16, 1)));
+    assert_eq!(value(&tree("2 + 1.5i")), Complex64::new(2.0, 1.5));
+    assert!((value(&tree("polar(2, 0.5)")) - Complex64::from_polar(2.0, 0.5)).norm() < 1e-15);
+    let polar = |s: &str| simplified(*crate::convert::convert_expr(crate::parse::expr_parse(s)));
+    assert_eq!(polar("polar(1, pi)"), Expr::Term(ratio(-1, 1)));
+    assert_eq!(polar("polar(3, pi/2)"), *mul(num(3.0), i()));
+    assert_eq!(tree("re(3 + 4i)"), Expr::Term(ratio(3, 1)));
+    assert_eq!(tree("im(3 + 4i)"), Expr::Term(ratio(4, 1)));
+    assert_eq!(tree("|3 + 4i|"), Expr::Term(ratio(5, 1)));
+    assert_eq!(tree("conj(3 + 4i)"), *add(num(3.0), mul(num(-4.0), i())));
+    assert!((value(&tree("arg(0 - 2i)")) - Complex64::from(-std::f64::consts::FRAC_PI_2)).norm() < 1e-15);
+}
+
+#[test]
+fn closed_forms_round_bounds_inwards() {
+    let k = || var("k");
+    let at = |e: &Expr, x: f64| e.evaluate(&HashMap::from([("x".to_string(), Complex64::from(x))]), &HashMap::new()).re;
+    let triangle = simplified(*sum("k".to_string(), num(1.0), var("x"), k()));
+    assert!(!matches!(triangle, Expr::Series(..)), "{triangle:?}");
+    assert_eq!(at(&triangle, 4.0), 10.0);
+    assert_eq!(at(&triangle, 2.5), 3.0);
+    // Backwards bounds are empty.
+    assert_eq!(at(&triangle, -3.0), 0.0);
+    let powers = simplified(*sum("k".to_string(), num(0.0), var("x"), pow(num(2.0), k())));
+    assert_eq!(at(&powers, 3.0), 15.0);
+    assert_eq!(at(&powers, -3.0), 0.0);
+    let fives = simplified(*prod("k".to_string(), var("x"), num(3.0), num(5.0)));
+    assert_eq!(at(&fives, 1.5), 25.0);
+    assert_eq!(at(&fives, 7.0), 1.0);
+    // Constant bounds that aren't whole.
+    assert_eq!(reduced(*sum("k".to_string(), div(num(1.0), num(2.0)), div(num(7.0), num(2.0)), k())), Expr::Term(ratio(6, 1)));
+}
+
+#[test]
+fn geometric_series_at_one() {
+    let at = |e: &Expr, x: f64, n: f64| {
+        let vars = HashMap::from([("x".to_string(), Complex64::from(x)), ("n".to_string(), Complex64::from(n))]);
+        e.evaluate(&vars, &HashMap::new()).re
+    };
+    // 1 + x + ... + x^5, which has no closed form that works at x = 1.
+    let short = simplified(*sum("k".to_string(), num(0.0), num(5.0), pow(var("x"), var("k"))));
+    assert_eq!(at(&short, 1.0, 0.0), 6.0);
+    assert_eq!(at(&short, 2.0, 0.0), 63.0);
+    let long = simplified(*sum("k".to_string(), num(0.0), var("n"), pow(var("x"), var("k"))));
+    assert_eq!(at(&long, 1.0, 9.0), 10.0);
+    assert_eq!(at(&long, 2.0, 9.0), 1023.0);
+    let huge = simplified(*sum("k".to_string(), num(1.0), num(1000.0), pow(var("x"), var("k"))));
+    assert_eq!(at(&huge, 1.0, 0.0), 1000.0);
+}
+
+#[test]
+fn zero_to_a_power() {
+    // 0^x isn't 0 for x <= 0, so it's left alone.
+    let e = simplified(*pow(num(0.0), var("x")));
+    assert!(matches!(e, Expr::Pow(..)), "{e:?}");
+    assert_eq!(e.evaluate(&HashMap::from([("x".to_string(), Complex64::from(2.0))]), &HashMap::new()), Complex64::from(0.0));
+    assert!(simplified(*pow(num(0.0), constant(Constant::Pi))).is_zero());
+    assert_eq!(simplified(*pow(num(0.0), num(0.0))), Expr::Term(ratio(1, 1)));
+    // Only the first term of this is 0^0.
+    assert_eq!(simplified(*sum("k".to_string(), num(0.0), num(3.0), pow(num(0.0), var("k")))), Expr::Term(ratio(1, 1)));
+}
@@ -0,0 +1,429 @@
+/*
+This is where implicit equations like `x^2 + y^2 = 1` are turned into lines and
+surfaces.
+
+`lhs - rhs` is sampled on a coarse grid, and cells where its sign changes are split
+into quarters (or eighths in 3D) until they're the size of the finest grid. Marching
+squares then puts a line through each of those cells, and the lines are joined into
+polylines. Marching cubes does the same with the faces of each cube, and fills in
+the loops they make with triangles.
+*/
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use num_complex::Complex64;
+
+use crate::expr::{Context, Expr, Func};
+use crate::mesh::{self, Mesh, Vertex};
+use crate::sample::Point;
+
+/// The number of cells along each side of the coarse grid.
+pub const COARSE_CELLS: usize = 32;
+/// How many times a coarse cell can be split into quarters.
+pub const MAX_DEPTH: u32 = 4;
+/// How many times an edge is halved to tell a zero that's hard to see from a pole.
+const BISECTIONS: u32 = 16;
+
+/// A corner of the finest grid.
+type Corner = (usize, usize);
+/// An edge of the finest grid, from its lower corner to its upper one.
+type Edge = (Corner, Corner);
+
+/// Finds the lines where `f(x, y) = 0`.
+pub fn curve(f: &mut dyn FnMut(f64, f64) -> f64, x: RangeInclusive<f64>, y: RangeInclusive<f64>) -> Vec<Vec<Point>> {
+    let size = 1 << MAX_DEPTH;
+    let fine = (COARSE_CELLS * size) as f64;
+    let mut s = Squares {
+        f,
+        start: (*x.start(), *y.start()),
+        step: ((x.end() - x.start()) / fine, (y.end() - y.start()) / fine),
+        values: HashMap::new(),
+        points: HashMap::new(),
+        segments: Vec::new(),
+    };
+    for i in 0..COARSE_CELLS {
+        for j in 0..COARSE_CELLS {
+            s.cell((i * size, j * size), size);
+        }
+    }
+    s.join()
+}
+
+/// The state of one run of marching squares.
+struct Squares<'a> {
+    f: &'a mut dyn FnMut(f64, f64) -> f64,
+    start: (f64, f64),
+    /// The size of a cell of the finest grid.
+    step: (f64, f64),
+    values: HashMap<Corner, f64>,
+    /// Where the curve crosses each edge, `None` if it only looks like it does.
+    points: HashMap<Edge, Option<Point>>,
+    segments: Vec<(Edge, Edge)>,
+}
+impl Squares<'_> {
+    fn pos(&self, c: Corner) -> (f64, f64) {
+        (self.start.0 + self.step.0 * c.0 as f64, self.start.1 + self.step.1 * c.1 as f64)
+    }
+
+    fn value(&mut self, c: Corner) -> f64 {
+        if let Some(v) = self.values.get(&c) { return *v }
+        let (x, y) = self.pos(c);
+        let v = (self.f)(x, y);
+        self.values.insert(c, v);
+        v
+    }
+
+    /// Splits a cell if the sign changes inside it, until it's the size of the finest grid.
+    fn cell(&mut self, c: Corner, size: usize) {
+        let corners = [c, (c.0 + size, c.1), (c.0 + size, c.1 + size), (c.0, c.1 + size)];
+        let mut signs: Vec<bool> = corners.iter().map(|k| self.value(*k) >= 0.0).collect();
+        // The middle catches small loops that don't reach any corner.
+        if size > 1 {
+            signs.push(self.value((c.0 + size / 2, c.1 + size / 2)) >= 0.0);
+        }
+        if signs.iter().all(|s| *s == signs[0]) { return }
+
+        if size == 1 {
+            self.march(c);
+            return;
+        }
+        let h = size / 2;
+        for (di, dj) in [(0, 0), (h, 0), (0, h), (h, h)] {
+            self.cell((c.0 + di, c.1 + dj), h);
+        }
+    }
+
+    /// Adds the segments through a cell of the finest grid.
+    fn march(&mut self, (i, j): Corner) {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        // Bottom, right, top and left.
+        let found: Vec<Edge> = (0..4).filter_map(|k| self.crossing(corners[k], corners[(k + 1) % 4])).collect();
+
+        match found[..] {
+            [a, b] => self.segments.push((a, b)),
+            [b, r, t, l] => {
+                // A saddle. If the saddle point has the same sign as the bottom left corner,
+                // that corner is joined to the top right one and the other two are cut off.
+                let values = corners.map(|k| self.values[&k]);
+                if (saddle(values) >= 0.0) == (values[0] >= 0.0) {
+                    self.segments.extend([(b, r), (t, l)]);
+                } else {
+                    self.segments.extend([(l, b), (r, t)]);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Where the curve crosses the edge between two corners, if it does.
+    fn crossing(&mut self, a: Corner, b: Corner) -> Option<Edge> {
+        let (va, vb) = (self.values[&a], self.values[&b]);
+        if !va.is_finite() || !vb.is_finite() || (va >= 0.0) == (vb >= 0.0) {
+            return None;
+        }
+
+        let edge = if a < b { (a, b) } else { (b, a) };
+        if !self.points.contains_key(&edge) {
+            let (pa, pb) = (self.pos(a), self.pos(b));
+            let mut along = |t: f64| (self.f)(pa.0 + (pb.0 - pa.0) * t, pa.1 + (pb.1 - pa.1) * t);
+            let t = va / (va - vb);
+            let zero = zero_between(&mut along, va, vb, t);
+            let p = (pa.0 + (pb.0 - pa.0) * t, pa.1 + (pb.1 - pa.1) * t);
+            self.points.insert(edge, zero.then_some((p.0, p.1, 0.0)));
+        }
+        self.points[&edge].map(|_| edge)
+    }
+
+    /// Joins the segments that share an end into polylines. Closed curves end where they start.
+    fn join(&self) -> Vec<Vec<Point>> {
+        let mut ends: HashMap<Edge, Vec<usize>> = HashMap::new();
+        for (k, (a, b)) in self.segments.iter().enumerate() {
+            ends.entry(*a).or_default().push(k);
+            ends.entry(*b).or_default().push(k);
+        }
+
+        // Open curves have to start at one of their ends, loops can start anywhere.
+        let free = |e: &Edge| ends[e].len() == 1;
+        let starts: Vec<usize> = (0..self.segments.len())
+            .filter(|k| free(&self.segments[*k].0) || free(&self.segments[*k].1))
+            .chain(0..self.segments.len())
+            .collect();
+
+        let mut used = vec![false; self.segments.len()];
+        let mut lines = Vec::new();
+        for s in starts {
+            if used[s] { continue }
+            used[s] = true;
+
+            let (a, b) = self.segments[s];
+            let (from, mut at) = if free(&b) { (b, a) } else { (a, b) };
+            let mut line = vec![from, at];
+            while let Some(&next) = ends[&at].iter().find(|k| !used[**k]) {
+                used[next] = true;
+                let (c, d) = self.segments[next];
+                at = if c == at { d } else { c };
+                line.push(at);
+            }
+            lines.push(line.iter().map(|e| self.points[e].unwrap()).collect());
+        }
+        lines
+    }
+}
+
+/// The value at the saddle point of the bilinear fit through the corners of a square, given in
+/// order around it. The middle of the square isn't good enough when the saddle point is near a
+/// side.
+fn saddle([v00, v10, v11, v01]: [f64; 4]) -> f64 {
+    (v00 * v11 - v10 * v01) / (v00 + v11 - v10 - v01)
+}
+
+/// Whether `f` is 0 between `0` and `1`, where it's `va` and `vb` (which have different
+/// signs), and doesn't just change sign at a pole or a jump. `t` is where the line between
+/// them crosses 0.
+fn zero_between(f: &mut dyn FnMut(f64) -> f64, va: f64, vb: f64, t: f64) -> bool {
+    let (a, b) = (va.abs(), vb.abs());
+    // Next to a zero the line is only off by how much `f` bends, which is less than this on
+    // a fine enough grid. Next to a pole `f` is at least this big everywhere in between.
+    if f(t).abs() <= a * b / (a + b) { return true }
+
+    // `f` bends a lot or there's a pole, so the sign change is narrowed down. Only a zero
+    // gets smaller there.
+    let (mut lo, mut hi) = ((0.0, va), (1.0, vb));
+    for _ in 0..BISECTIONS {
+        let m = (lo.0 + hi.0) / 2.0;
+        let v = f(m);
+        if !v.is_finite() { return false }
+        if (v >= 0.0) == (va >= 0.0) { lo = (m, v) } else { hi = (m, v) }
+    }
+    lo.1.abs().min(hi.1.abs()) <= a.max(b)
+}
+
+/// The number of cells along each side of the coarse grid in 3D.
+pub const COARSE_CUBES: usize = 16;
+/// How many times a coarse cube can be split into eighths.
+pub const MAX_CUBE_DEPTH: u32 = 3;
+
+type Corner3 = (usize, usize, usize);
+type Edge3 = (Corner3, Corner3);
+
+/// The corners of each face of a cube, in order around the face. Corner `k` is
+/// offset by its bits, `x` being the lowest.
+const FACES: [[usize; 4]; 6] = [
+    [0, 2, 6, 4],
+    [1, 3, 7, 5],
+    [0, 1, 5, 4],
+    [2, 3, 7, 6],
+    [0, 1, 3, 2],
+    [4, 5, 7, 6],
+];
+
+/// Finds the surface where `f(x, y, z) = 0`, with normals pointing towards where `f` is positive.
+pub fn surface(f: &mut dyn FnMut(f64, f64, f64) -> f64, x: RangeInclusive<f64>, y: RangeInclusive<f64>, z: RangeInclusive<f64>) -> Mesh {
+    let size = 1 << MAX_CUBE_DEPTH;
+    let fine = (COARSE_CUBES * size) as f64;
+    let mut c = Cubes {
+        f,
+        start: [*x.start(), *y.start(), *z.start()],
+        step: [(x.end() - x.start()) / fine, (y.end() - y.start()) / fine, (z.end() - z.start()) / fine],
+        values: HashMap::new(),
+        vertices: HashMap::new(),
+        mesh: Mesh::default(),
+    };
+    for i in 0..COARSE_CUBES {
+        for j in 0..COARSE_CUBES {
+            for k in 0..COARSE_CUBES {
+                c.cube((i * size, j * size, k * size), size);
+            }
+        }
+    }
+
+    // Zeros right on a corner give the cubes around it many vertices in the same place.
+    let tolerance = c.step.iter().fold(f64::INFINITY, |a, b| a.min(b.abs())) * 1e-3;
+    c.mesh.weld(tolerance);
+    c.mesh
+}
+
+/// The state of one run of marching cubes.
+struct Cubes<'a> {
+    f: &'a mut dyn FnMut(f64, f64, f64) -> f64,
+    start: Vertex,
+    /// The size of a cube of the finest grid.
+    step: Vertex,
+    values: HashMap<Corner3, f64>,
+    /// The vertex on each edge, `None` if the surface only looks like it crosses it.
+    vertices: HashMap<Edge3, Option<u32>>,
+    mesh: Mesh,
+}
+impl Cubes<'_> {
+    fn pos(&self, c: Corner3) -> Vertex {
+        [0, 1, 2].map(|a| self.start[a] + self.step[a] * [c.0, c.1, c.2][a] as f64)
+    }
+
+    fn value(&mut self, c: Corner3) -> f64 {
+        if let Some(v) = self.values.get(&c) { return *v }
+        let [x, y, z] = self.pos(c);
+        let v = (self.f)(x, y, z);
+        self.values.insert(c, v);
+        v
+    }
+
+    fn corners(c: Corner3, size: usize) -> [Corner3; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|k| (c.0 + (k & 1) * size, c.1 + (k >> 1 & 1) * size, c.2 + (k >> 2 & 1) * size))
+    }
+
+    /// Splits a cube if the sign changes inside it, until it's the size of the finest grid.
+    fn cube(&mut self, c: Corner3, size: usize) {
+        let mut signs: Vec<bool> = Self::corners(c, size).iter().map(|k| self.value(*k) >= 0.0).collect();
+        if size > 1 {
+            let h = size / 2;
+            signs.push(self.value((c.0 + h, c.1 + h, c.2 + h)) >= 0.0);
+        }
+        if signs.iter().all(|s| *s == signs[0]) { return }
+
+        if size == 1 {
+            self.march(c);
+            return;
+        }
+        let h = size / 2;
+        for k in Self::corners(c, h) {
+            self.cube(k, h);
+        }
+    }
+
+    /// Adds the triangles in a cube of the finest grid.
+    fn march(&mut self, c: Corner3) {
+        let corners = Self::corners(c, 1);
+        let values = corners.map(|k| self.values[&k]);
+
+        // Each face is done like a cell in marching squares, and the segments make loops.
+        let mut segments = Vec::new();
+        for face in FACES {
+            let found: Vec<u32> = (0..4).filter_map(|k| self.crossing(corners[face[k]], corners[face[(k + 1) % 4]])).collect();
+            match found[..] {
+                [a, b] => segments.push((a, b)),
+                [a, b, c, d] => {
+                    if (saddle(face.map(|k| values[k])) >= 0.0) == (values[face[0]] >= 0.0) {
+                        segments.extend([(a, b), (c, d)]);
+                    } else {
+                        segments.extend([(d, a), (b, c)]);
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        // Which way `f` goes up, so the triangles can face that way.
+        let gradient = [0, 1, 2].map(|a| (0..8).map(|k| if k >> a & 1 == 1 { values[k] } else { -values[k] }).sum::<f64>());
+
+        while let Some((start, mut at)) = segments.pop() {
+            let mut l = vec![start];
+            while let Some(i) = segments.iter().position(|(a, b)| *a == at || *b == at) {
+                l.push(at);
+                let (a, b) = segments.swap_remove(i);
+                at = if a == at { b } else { a };
+            }
+            // Only closed loops are filled, open ones are missing a vertex from a pole.
+            if at != start || l.len() < 3 { continue }
+
+            for k in 1..l.len() - 1 {
+                let mut t = [l[0], l[k], l[k + 1]];
+                let [p, q, r] = t.map(|i| self.mesh.vertices[i as usize]);
+                let n = mesh::cross(mesh::sub(q, p), mesh::sub(r, p));
+                if n[0] * gradient[0] + n[1] * gradient[1] + n[2] * gradient[2] < 0.0 {
+                    t.swap(1, 2);
+                }
+                self.mesh.triangles.push(t);
+            }
+        }
+    }
+
+    /// The vertex where the surface crosses the edge between two corners, if it does.
+    fn crossing(&mut self, a: Corner3, b: Corner3) -> Option<u32> {
+        let (va, vb) = (self.values[&a], self.values[&b]);
+        if !va.is_finite() || !vb.is_finite() || (va >= 0.0) == (vb >= 0.0) {
+            return None;
+        }
+
+        let edge = if a < b { (a, b) } else { (b, a) };
+        if !self.vertices.contains_key(&edge) {
+            let (pa, pb) = (self.pos(a), self.pos(b));
+            let mut along = |t: f64| {
+                let p = [0, 1, 2].map(|i| pa[i] + (pb[i] - pa[i]) * t);
+                (self.f)(p[0], p[1], p[2])
+            };
+            let t = va / (va - vb);
+            let zero = zero_between(&mut along, va, vb, t);
+            let p = [0, 1, 2].map(|i| pa[i] + (pb[i] - pa[i]) * t);
+            let v = zero.then(|| {
+                self.mesh.vertices.push(p);
+                self.mesh.vertices.len() as u32 - 1
+            });
+            self.vertices.insert(edge, v);
+        }
+        self.vertices[&edge]
+    }
+}
+
+/// Finds the lines of every implicit curve in `ctx`.
+pub fn curves(ctx: &Context) -> Result<Vec<Vec<Point>>, Vec<String>> {
+    let mut lines = Vec::new();
+    let mut errs = Vec::new();
+    for name in ctx.implicit_names().filter(|n| ctx.get_implicit(n).unwrap().params().len() == 2) {
+        match implicit_curve(ctx, name) {
+            Ok(l) => lines.extend(l),
+            Err(e) => errs.push(e),
+        }
+    }
+
+    if errs.is_empty() { return Ok(lines) }
+    Err(errs)
+}
+
+/// Simplifies `lhs - rhs` of an implicit curve and finds where it's 0.
+/// Only the real part is used.
+pub fn implicit_curve(ctx: &Context, name: &str) -> Result<Vec<Vec<Point>>, String> {
+    let c = ctx.get_implicit(name).unwrap();
+    let ranges = c.ranges(ctx)?;
+    let (e, funcs) = ctx.simplify_implicit(name);
+    let mut f = real(&e, c.params(), &funcs);
+    let lines = curve(&mut |x, y| f(&[x, y]), ranges[0].clone(), ranges[1].clone());
+    Ok(lines)
+}
+
+/// Finds the mesh of every implicit surface in `ctx`.
+pub fn surfaces(ctx: &Context) -> Result<Vec<Mesh>, Vec<String>> {
+    let mut meshes = Vec::new();
+    let mut errs = Vec::new();
+    for name in ctx.implicit_names().filter(|n| ctx.get_implicit(n).unwrap().params().len() == 3) {
+        match implicit_surface(ctx, name) {
+            Ok(m) => meshes.push(m),
+            Err(e) => errs.push(e),
+        }
+    }
+
+    if errs.is_empty() { return Ok(meshes) }
+    Err(errs)
+}
+
+/// Simplifies `lhs - rhs` of an implicit surface and finds where it's 0.
+/// Only the real part is used.
+pub fn implicit_surface(ctx: &Context, name: &str) -> Result<Mesh, String> {
+    let c = ctx.get_implicit(name).unwrap();
+    let ranges = c.ranges(ctx)?;
+    let (e, funcs) = ctx.simplify_implicit(name);
+    let mut f = real(&e, c.params(), &funcs);
+    let mesh = surface(&mut |x, y, z| f(&[x, y, z]), ranges[0].clone(), ranges[1].clone(), ranges[2].clone());
+    Ok(mesh)
+}
+
+/// `e` as a real function of `params`.
+fn real<'a>(e: &'a Expr, params: &'a [String], funcs: &'a HashMap<String, Func>) -> impl FnMut(&[f64]) -> f64 + 'a {
+    let mut vars = HashMap::new();
+    move |values| {
+        for (p, v) in params.iter().zip(values) {
+            vars.insert(p.clone(), Complex64::from(*v));
+        }
+        e.evaluate(&vars, funcs).re
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{convert, implicit, parse};
+use crate::sample::Point;
+
+
+/// The lines of the implicit curve `c` in `src`.
+fn lines(src: &str) -> Vec<Vec<Point>> {
+    let ctx = convert::convert(parse::str_parse(src)).unwrap();
+    implicit::implicit_curve(&ctx, "c").unwrap()
+}
+
+fn norm(p: Point) -> f64 {
+    (p.0 * p.0 + p.1 * p.1 + p.2 * p.2).sqrt()
+}
+
+
+#[test]
+fn circles_are_closed() {
+    let circles = lines("implicit c(x, y): x^2 + y^2 = 1 for x in -2..2, y in -2..2;");
+    assert_eq!(circles.len(), 1);
+    let circle = &circles[0];
+    assert_eq!(circle[0], *circle.last().unwrap());
+    assert!(circle.iter().all(|p| (norm(*p) - 1.0).abs() < 1e-4));
+    // The refined cells are 1/128 wide, so the whole way around is covered.
+    assert!(circle.len() > 700, "{}", circle.len());
+
+    // Two circles make two loops.
+    let circles = lines("implicit c(x, y): ((x - 1)^2 + y^2 - 0.25) ((x + 1)^2 + y^2 - 0.25) = 0 for x in -2..2, y in -2..2;");
+    assert_eq!(circles.len(), 2);
+}
+
+#[test]
+fn saddles_keep_branches_apart() {
+    // `x y = -c` for a tiny `c` goes through the cell around the origin from all four sides.
+    // The origin isn't a corner of the grid, so that cell is a saddle.
+    let c = 1e-7;
+    let lines = implicit::curve(&mut |x, y| x * y + c, -1.001..=1.0, -1.0..=1.002);
+    assert_eq!(lines.len(), 2);
+    for line in &lines {
+        // Each branch stays in its own quadrant.
+        let left = line[0].0 < 0.0;
+        assert!(line.iter().all(|p| (p.0 < 0.0) == left && (p.1 > 0.0) == left), "{line:?}");
+        assert!(line.iter().all(|p| (p.0 * p.1 + c).abs() < 1e-4));
+    }
+}
+
+#[test]
+fn poles_arent_curves() {
+    // `1/x` changes sign at 0 without being 0 there.
+    assert!(implicit::curve(&mut |x, _| 1.0 / x, -1.001..=1.0, -1.0..=1.0).is_empty());
+    let lines = implicit::curve(&mut |x, y| 1.0 / x - y, -1.001..=1.0, -1.0..=1.0);
+    assert!(lines.iter().flatten().all(|p| (1.0 / p.0 - p.1).abs() < 1e-2 * (1.0 + p.1.abs())));
+}
+
+#[test]
+fn steep_surfaces_are_complete() {
+    let g = |x: f64, y: f64, z: f64| x * x + y * y + z * z - 1.0;
+    let sphere = implicit::surface(&mut |x, y, z| g(x, y, z), -2.0..=2.0, -2.0..=2.0, -2.0..=2.0);
+    assert!(!sphere.triangles.is_empty());
+    assert!(sphere.vertices.iter().all(|v| (norm((v[0], v[1], v[2])) - 1.0).abs() < 1e-3));
+    // `g / (g^2 + c)` has the same signs as `g`, but it peaks right next to the sphere, so
+    // between two corners it's bigger than at either of them.
+    let steep = implicit::surface(&mut |x, y, z| g(x, y, z) / (g(x, y, z).powi(2) + 1e-4), -2.0..=2.0, -2.0..=2.0, -2.0..=2.0);
+    assert_eq!(steep.triangles.len(), sphere.triangles.len());
+}
+
+#[test]
+fn poles_arent_surfaces() {
+    let m = implicit::surface(&mut |x, _, _| 1.0 / x, -1.001..=1.0, -1.0..=1.0, -1.0..=1.0);
+    assert!(m.triangles.is_empty());
+}
+
+#[test]
+fn face_saddles_keep_sheets_apart() {
+    // The same as `saddles_keep_branches_apart`, stretched along z.
+    let c = 1e-7;
+    let m = implicit::surface(&mut |x, y, _| x * y + c, -1.001..=1.0, -1.0..=1.002, -1.0..=1.0);
+    assert!(!m.triangles.is_empty());
+    for t in &m.triangles {
+        let left = m.vertices[t[0] as usize][0] < 0.0;
+        assert!(t.iter().all(|i| {
+            let v = m.vertices[*i as usize];
+            (v[0] < 0.0) == left && (v[1] > 0.0) == left
+        }), "{t:?}");
+    }
+}
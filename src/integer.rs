@@ -0,0 +1,94 @@
+/*
+This is where the functions of whole numbers are worked out: `mod`, `gcd`, `lcm` and
+the counting functions `nCr` and `nPr`. `floor`, `ceil`, `round` and `sign` are simple
+enough to be worked out where they're used.
+
+These only take real numbers, and anything they aren't defined for gives NaN, like
+`gcd(1.5, 3)`. The counting functions work for any number of things, `nCr(1/2, 2)` is
+`-1/8`, but only for a whole number of them picked.
+*/
+
+use num_complex::Complex64;
+
+use crate::special;
+
+/// Past this many factors `nCr` and `nPr` are worked out with `lgamma` instead.
+const PRODUCT_LIMIT: f64 = 1000.0;
+
+fn is_whole(x: f64) -> bool {
+    // * infinities and NaN have a NaN fraction
+    x.fract() == 0.0
+}
+
+/// `(-1)^k` for a whole number `k`.
+fn parity(k: f64) -> f64 {
+    if k % 2.0 == 0.0 { 1.0 } else { -1.0 }
+}
+
+/// -1, 0 or 1. Unlike `f64::signum`, 0 has a sign of 0.
+pub fn sign(x: f64) -> f64 {
+    if x == 0.0 { return 0.0 }
+    x.signum()
+}
+
+/// `a mod b`, which has the sign of `b`, so `mod(-1, 3)` is 2. NaN when `b` is 0.
+pub fn modulo(a: f64, b: f64) -> f64 {
+    if b == 0.0 { return f64::NAN }
+    let r = a % b;
+    if (r != 0.0) & ((r < 0.0) != (b < 0.0)) { r + b } else { r }
+}
+
+/// The greatest common divisor of two whole numbers, which is never negative. `gcd(0, 0)` is 0.
+pub fn gcd(a: f64, b: f64) -> f64 {
+    if !is_whole(a) | !is_whole(b) { return f64::NAN }
+    // * `%` is exact, so this is too
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0.0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The least common multiple of two whole numbers, which is never negative. It's 0 if
+/// either of them is.
+pub fn lcm(a: f64, b: f64) -> f64 {
+    let g = gcd(a, b);
+    if g == 0.0 { return g }
+    (a / g * b).abs()
+}
+
+/// `nCr(n, k) = n (n-1) ... (n-k+1) / k!`, the number of ways to pick `k` of `n` things.
+/// `k` has to be whole, and there are no ways to pick a negative number of things.
+pub fn choose(n: f64, k: f64) -> f64 {
+    if !is_whole(k) | n.is_nan() { return f64::NAN }
+    if k < 0.0 { return 0.0 }
+    let mut k = k;
+    if is_whole(n) {
+        // nCr(-m, k) = (-1)^k nCr(m+k-1, k)
+        if n < 0.0 { return parity(k) * choose(k - n - 1.0, k) }
+        if k > n { return 0.0 }
+        k = k.min(n - k);
+    }
+    if k <= PRODUCT_LIMIT {
+        // * every partial product is a whole number when `n` is, so these stay exact
+        return (0..k as u32).fold(1.0, |c, j| c * (n - j as f64) / (j as f64 + 1.0));
+    }
+    let lgamma = |x: f64| special::lgamma(Complex64::from(x));
+    (lgamma(n + 1.0) - lgamma(k + 1.0) - lgamma(n - k + 1.0)).exp().re
+}
+
+/// `nPr(n, k) = n (n-1) ... (n-k+1)`, the number of ways to line up `k` of `n` things.
+/// `k` has to be whole and not negative.
+pub fn permute(n: f64, k: f64) -> f64 {
+    if !is_whole(k) | (k < 0.0) | n.is_nan() { return f64::NAN }
+    if is_whole(n) {
+        // nPr(-m, k) = (-1)^k nPr(m+k-1, k)
+        if n < 0.0 { return parity(k) * permute(k - n - 1.0, k) }
+        if k > n { return 0.0 }
+    }
+    if k <= PRODUCT_LIMIT {
+        return (0..k as u32).fold(1.0, |p, j| p * (n - j as f64));
+    }
+    let lgamma = |x: f64| special::lgamma(Complex64::from(x));
+    (lgamma(n + 1.0) - lgamma(n - k + 1.0)).exp().re
+}
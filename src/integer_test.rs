@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use num_complex::Complex64;
+
+use crate::constant::Constant;
+use crate::expr::{f::*, Builtin, Expr, Term};
+use crate::integer;
+use crate::sample;
+use crate::test_util::{ratio, reduced, var};
+
+
+fn at(e: &Expr, x: Complex64) -> Complex64 {
+    e.evaluate(&HashMap::from([("x".to_string(), x)]), &HashMap::new())
+}
+
+
+#[test]
+fn whole_number_functions() {
+    assert_eq!(integer::modulo(-1.0, 3.0), 2.0);
+    assert_eq!(integer::modulo(7.5, -2.0), -0.5);
+    assert!(integer::modulo(1.0, 0.0).is_nan());
+    assert_eq!(integer::gcd(-12.0, 18.0), 6.0);
+    assert_eq!(integer::gcd(0.0, 0.0), 0.0);
+    assert!(integer::gcd(1.5, 3.0).is_nan());
+    assert_eq!(integer::lcm(4.0, -6.0), 12.0);
+    assert_eq!(integer::lcm(0.0, 5.0), 0.0);
+    assert_eq!(integer::sign(-0.0), 0.0);
+
+    assert_eq!(integer::choose(52.0, 5.0), 2598960.0);
+    assert_eq!(integer::choose(60.0, 30.0), 118264581564861424.0);
+    assert_eq!(integer::choose(5.0, 7.0), 0.0);
+    assert_eq!(integer::choose(5.0, -1.0), 0.0);
+    assert_eq!(integer::choose(0.5, 2.0), -0.125);
+    // nCr(-2, 3) = (-2)(-3)(-4) / 3!
+    assert_eq!(integer::choose(-2.0, 3.0), -4.0);
+    assert!(integer::choose(5.0, 2.5).is_nan());
+    // * from mpmath, past the point where the product is written out
+    for (n, k, want) in [(0.5, 1500.0, -4.856985166987579e-6), (-2.5, 1200.0, 31319.43203913922), (30.5, 1100.0, -1.1429728108778339e-63)] {
+        let c = integer::choose(n, k);
+        assert!((c / want - 1.0).abs() < 1e-10, "nCr({n}, {k}) is {c} but should be {want}");
+    }
+
+    assert_eq!(integer::permute(10.0, 3.0), 720.0);
+    assert_eq!(integer::permute(3.0, 5.0), 0.0);
+    assert_eq!(integer::permute(-3.0, 2.0), 12.0);
+    assert!(integer::permute(3.0, -1.0).is_nan());
+}
+
+#[test]
+fn exact_values() {
+    let pi = || term(Term::Const(Constant::Pi));
+    assert_eq!(reduced(*floor(pi())), *num(3.0));
+    assert!(matches!(reduced(*floor(pi())), Expr::Term(Term::Rational(_))));
+    assert_eq!(reduced(*ceil(div(num(-7.0), num(2.0)))), *num(-3.0));
+    assert_eq!(reduced(*round(div(num(5.0), num(2.0)))), *num(3.0));
+    assert_eq!(reduced(*round(div(num(-5.0), num(2.0)))), *num(-3.0));
+    assert_eq!(reduced(*sign(num(-0.5))), *num(-1.0));
+    assert_eq!(reduced(*modulo(num(-7.0), div(num(3.0), num(2.0)))), Expr::Term(ratio(1, 2)));
+    let many = |b: Builtin, n: &[f64]| reduced(Expr::Builtin(b, n.iter().map(|n| num(*n)).collect()));
+    assert_eq!(many(Builtin::Gcd, &[12.0, -18.0, 27.0]), *num(3.0));
+    assert_eq!(many(Builtin::Lcm, &[4.0, 6.0, 10.0]), *num(60.0));
+    assert_eq!(reduced(*ncr(num(100.0), num(50.0))), Expr::Term(Term::Rational("100891344545564193334812497256".parse().unwrap())));
+    assert_eq!(reduced(*ncr(div(num(1.0), num(2.0)), num(2.0))), Expr::Term(ratio(-1, 8)));
+    assert_eq!(reduced(*npr(num(10.0), num(3.0))), *num(720.0));
+    // Anything they aren't defined for isn't a number.
+    for e in [modulo(num(1.0), num(0.0)), gcd(num(1.5), num(3.0)), npr(num(3.0), num(-1.0))] {
+        assert!(matches!(reduced(*e), Expr::Term(Term::Real(v)) if v.is_nan()));
+    }
+}
+
+#[test]
+fn complex_inputs_are_not_numbers() {
+    let x = var("x");
+    for e in [
+        floor(x.clone()), ceil(x.clone()), round(x.clone()), sign(x.clone()), modulo(x.clone(), num(2.0)),
+        gcd(x.clone(), num(4.0)), lcm(num(4.0), x.clone()), ncr(x.clone(), num(2.0)), npr(num(5.0), x.clone()),
+    ] {
+        assert!(at(&e, Complex64::new(2.0, 1.0)).re.is_nan(), "{e:?}");
+        assert!(at(&e, Complex64::new(2.0, 0.0)).re.is_finite(), "{e:?}");
+    }
+    let i = term(Term::Const(Constant::I));
+    assert!(matches!(reduced(*floor(add(num(2.0), i))), Expr::Term(Term::Real(v)) if v.is_nan()));
+}
+
+#[test]
+fn graphs_break_at_steps() {
+    let x = var("x");
+    let lines = |e: Box<Expr>, lo: f64, hi: f64| sample::graph(&e, &HashMap::new(), lo..=hi, None);
+    // floor(x) on -3..3 has 6 steps, and no line crosses from one to the next.
+    let l = lines(floor(x.clone()), -3.0, 3.0);
+    assert_eq!(l.len(), 6);
+    for line in &l {
+        assert!(line.iter().all(|p| p.1 == line[0].1), "{line:?}");
+    }
+    // The steps of floor(x) / 100 are too small to see, but the line still breaks at them.
+    assert_eq!(lines(div(floor(x.clone()), num(100.0)), 0.5, 10.5).len(), 11);
+    assert_eq!(lines(modulo(mul(num(3.0), x.clone()), num(2.0)), 0.1, 4.1).len(), 7);
+    // Without steps, the line doesn't break.
+    assert_eq!(lines(x.clone(), -3.0, 3.0).len(), 1);
+}
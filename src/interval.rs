@@ -0,0 +1,442 @@
+/*
+This is where expressions are evaluated over intervals instead of single numbers.
+
+The result of evaluating over some ranges of the variables encloses every real value
+the expression takes for any choice of the variables in those ranges. Values that
+aren't real (`sqrt(x)` for `x < 0`) or aren't finite (`1/x` at `0`) aren't enclosed,
+but the result is marked `complex` when there might be non-real ones.
+
+Every bound is rounded outwards, so the enclosure holds even with rounding errors.
+*/
+
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+use num::BigRational;
+use num_complex::Complex64;
+
+use crate::expr::{odd_root, Builtin, Expr, Func, Series, Term};
+use crate::{integer, special};
+
+/// How far (relative to their size) bounds from library functions are moved outwards.
+const LOOSE: f64 = 1e-13;
+
+/// The same as `LOOSE`, for the special functions, which are only good to about 1e-13.
+const ROUGH: f64 = 1e-11;
+/// Where the gamma function has its minimum on the positive real line, and the minimum.
+const GAMMA_MIN: (f64, f64) = (1.461_632_144_968_362_2, 0.885_603_194_410_888_7);
+
+/// How deep recursive functions are followed before giving up and returning `ENTIRE`.
+pub const MAX_RECURSION: usize = 64;
+
+/// A closed interval of real numbers. `lo > hi` is the empty interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+    /// Whether some values might not be real. Those aren't in `lo..=hi`.
+    pub complex: bool,
+}
+impl Interval {
+    pub const EMPTY: Self = Self { lo: f64::INFINITY, hi: f64::NEG_INFINITY, complex: false };
+    pub const ENTIRE: Self = Self { lo: f64::NEG_INFINITY, hi: f64::INFINITY, complex: false };
+
+    pub fn new(lo: f64, hi: f64) -> Self {
+        Self { lo, hi, complex: false }
+    }
+    pub fn point(v: f64) -> Self {
+        Self::new(v, v)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lo.is_nan() || self.hi.is_nan() || self.lo > self.hi
+    }
+    pub fn contains(&self, v: f64) -> bool {
+        (self.lo <= v) & (v <= self.hi)
+    }
+    /// Whether `self` is a single integer.
+    pub fn as_int(&self) -> Option<i32> {
+        if (self.lo == self.hi) & (self.lo.fract() == 0.0) & (self.lo.abs() <= i32::MAX as f64) {
+            return Some(self.lo as i32);
+        }
+        None
+    }
+
+    /// The smallest interval containing both.
+    pub fn hull(&self, other: &Self) -> Self {
+        Self { lo: self.lo.min(other.lo), hi: self.hi.max(other.hi), complex: self.complex | other.complex }
+    }
+
+    fn with_complex(mut self, complex: bool) -> Self {
+        self.complex |= complex;
+        self
+    }
+
+    /// Rounds the bounds outwards by one step.
+    fn widen(self) -> Self {
+        if self.is_empty() { return self }
+        Self { lo: self.lo.next_down(), hi: self.hi.next_up(), complex: self.complex }
+    }
+
+    /// Rounds the bounds outwards by even more than `loosen`, for the special functions.
+    fn rough(self) -> Self {
+        if self.is_empty() { return self }
+        Self { lo: self.lo - self.lo.abs() * ROUGH, hi: self.hi + self.hi.abs() * ROUGH, complex: self.complex }.widen()
+    }
+
+    /// Rounds the bounds outwards by a bit more than `widen`, for functions that can be
+    /// off by more than a step. The complex versions `evaluate` uses often are.
+    fn loosen(self) -> Self {
+        if self.is_empty() { return self }
+        Self { lo: self.lo - self.lo.abs() * LOOSE, hi: self.hi + self.hi.abs() * LOOSE, complex: self.complex }.widen()
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        // A non-real value plus a real one is never real, but two non-real ones can be.
+        if self.complex & other.complex { return Self::ENTIRE.with_complex(true) }
+        let complex = self.complex | other.complex;
+        if self.is_empty() | other.is_empty() { return Self::EMPTY.with_complex(complex) }
+        Self::new(self.lo + other.lo, self.hi + other.hi).widen().with_complex(complex)
+    }
+
+    pub fn neg(&self) -> Self {
+        Self { lo: -self.hi, hi: -self.lo, complex: self.complex }
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        // A non-real value times a real one is only real when the real one is 0.
+        if self.complex & other.complex { return Self::ENTIRE.with_complex(true) }
+        let zero = (self.complex & other.contains(0.0)) | (other.complex & self.contains(0.0));
+        let complex = self.complex | other.complex;
+
+        let mut r = if self.is_empty() | other.is_empty() {
+            Self::EMPTY
+        } else {
+            // * `0 * inf` is 0 here, since an infinite bound is never reached.
+            let m = |a: f64, b: f64| if (a == 0.0) | (b == 0.0) { 0.0 } else { a * b };
+            let p = [m(self.lo, other.lo), m(self.lo, other.hi), m(self.hi, other.lo), m(self.hi, other.hi)];
+            Self::new(p.iter().copied().fold(f64::INFINITY, f64::min), p.iter().copied().fold(f64::NEG_INFINITY, f64::max)).widen()
+        };
+        if zero { r = r.hull(&Self::point(0.0)) }
+        r.with_complex(complex)
+    }
+
+    /// `1 / self`. Dividing by an interval with 0 strictly inside gives values on both
+    /// sides, so the result is `ENTIRE`.
+    pub fn recip(&self) -> Self {
+        if self.complex { return Self::ENTIRE.with_complex(true) }
+        if self.is_empty() | ((self.lo == 0.0) & (self.hi == 0.0)) { return Self::EMPTY }
+        // * written out so a zero bound of either sign goes to the right infinity
+        let r = if self.lo >= 0.0 {
+            Self::new(1.0 / self.hi, if self.lo == 0.0 { f64::INFINITY } else { 1.0 / self.lo })
+        } else if self.hi <= 0.0 {
+            Self::new(if self.hi == 0.0 { f64::NEG_INFINITY } else { 1.0 / self.hi }, 1.0 / self.lo)
+        } else {
+            return Self::ENTIRE;
+        };
+        r.widen()
+    }
+
+    /// `self^n` for an integer `n`.
+    pub fn powi(&self, n: i32) -> Self {
+        if self.complex { return Self::ENTIRE.with_complex(true) }
+        if self.is_empty() { return Self::EMPTY }
+        if n == 0 { return Self::point(1.0) }
+        if n < 0 { return self.powi(n.unsigned_abs() as i32).recip() }
+        if n % 2 == 1 { return Self::new(self.lo.powi(n), self.hi.powi(n)).loosen() }
+        self.even(|v| v.powi(n))
+    }
+
+    /// `self^other`. Negative bases only give real values for integer exponents.
+    pub fn pow(&self, other: &Self) -> Self {
+        if self.complex | other.complex { return Self::ENTIRE.with_complex(true) }
+        if let Some(n) = other.as_int() { return self.powi(n) }
+        if self.is_empty() | other.is_empty() { return Self::EMPTY }
+
+        // `x^y` for `x >= 0` is monotonic in both, so the extremes are at the corners.
+        let corners = |x: Self| {
+            let p = [x.lo.powf(other.lo), x.lo.powf(other.hi), x.hi.powf(other.lo), x.hi.powf(other.hi)];
+            Self::new(p.iter().copied().fold(f64::INFINITY, f64::min), p.iter().copied().fold(f64::NEG_INFINITY, f64::max)).loosen()
+        };
+
+        let mut r = Self::EMPTY;
+        if self.hi >= 0.0 {
+            r = corners(Self::new(self.lo.max(0.0), self.hi));
+        }
+        if self.lo < 0.0 {
+            // Negative bases give real values (of either sign) only when `y` is an integer.
+            if other.lo.ceil() <= other.hi.floor() {
+                let m = corners(Self::new((-self.hi).max(0.0), -self.lo)).hi;
+                r = r.hull(&Self::new(-m, m));
+            }
+            r.complex = true;
+        }
+        r
+    }
+
+    /// Applies a function that is even and increasing for positive inputs.
+    fn even(&self, f: impl Fn(f64) -> f64) -> Self {
+        let (a, b) = (f(self.lo), f(self.hi));
+        if self.contains(0.0) {
+            return Self::new(f(0.0), a.max(b)).loosen();
+        }
+        Self::new(a.min(b), a.max(b)).loosen()
+    }
+
+    /// Applies an increasing function defined on `lo..=hi`. Inputs outside of that give
+    /// values that aren't real.
+    fn increasing(&self, f: impl Fn(f64) -> f64, (lo, hi): (f64, f64)) -> Self {
+        let outside = (self.lo < lo) | (self.hi > hi);
+        let clipped = Self::new(self.lo.max(lo), self.hi.min(hi));
+        if clipped.is_empty() { return Self::EMPTY.with_complex(outside) }
+        Self::new(f(clipped.lo), f(clipped.hi)).loosen().with_complex(outside)
+    }
+
+    /// Applies a function with a period of `2pi`, its maximum of 1 at `peak` and its minimum
+    /// of -1 half a period later.
+    fn periodic(&self, f: impl Fn(f64) -> f64, peak: f64) -> Self {
+        let width = self.hi - self.lo;
+        if width.is_nan() || width >= TAU { return Self::new(-1.0, 1.0) }
+        let hits = |p: f64| ((self.lo - p) / TAU).ceil() <= ((self.hi - p) / TAU).floor();
+        let (a, b) = (f(self.lo), f(self.hi));
+        let r = Self::new(
+            if hits(peak + PI) { -1.0 } else { a.min(b) },
+            if hits(peak) { 1.0 } else { a.max(b) },
+        ).loosen();
+        Self::new(r.lo.max(-1.0), r.hi.min(1.0))
+    }
+
+    /// `max` or `min` of some intervals. Inputs without a real value are never picked
+    /// in a way that gives a real result, so they're skipped.
+    fn extreme(max: bool, args: &[Self]) -> Self {
+        // Any of the inputs could be picked over one that isn't real.
+        if args.iter().any(|a| a.complex) {
+            return args.iter().fold(Self::EMPTY, |r, a| r.hull(a)).with_complex(true);
+        }
+        let pick = if max { f64::max } else { f64::min };
+        args.iter().filter(|a| !a.is_empty()).fold(None, |r: Option<Self>, a| match r {
+            Some(r) => Some(Self::new(pick(r.lo, a.lo), pick(r.hi, a.hi))),
+            None => Some(*a),
+        }).unwrap_or(Self::EMPTY)
+    }
+
+    /// `mod(self, b)`. Within one period it's `self` moved down by a multiple of `b`, and
+    /// otherwise it can be anything between 0 and `b`.
+    fn modulo(&self, b: &Self) -> Self {
+        // * `mod(a, 0)` is NaN
+        let zero = b.contains(0.0);
+        let all = Self::new(b.lo.min(0.0), b.hi.max(0.0)).with_complex(zero);
+        if zero | (b.lo != b.hi) | !(self.lo.is_finite() & self.hi.is_finite()) { return all }
+
+        let k = (self.lo / b.lo).floor();
+        if (self.hi / b.lo).floor() != k { return all }
+        let r = self.add(&Self::point(b.lo).mul(&Self::point(k)).neg());
+        // Dividing can round to the wrong side of a multiple, which moves a bound out of the period.
+        if (r.lo < all.lo) | (r.hi > all.hi) { return all }
+        r
+    }
+
+    /// Applies `gcd`, `lcm`, `nCr` or `nPr`, which are only real for whole numbers, so
+    /// only single numbers are worked out.
+    fn whole(b: Builtin, args: &[Self]) -> Self {
+        if args.iter().all(|a| a.lo == a.hi) {
+            let c = b.apply_complex(&args.iter().map(|a| Complex64::from(a.lo)).collect::<Vec<Complex64>>());
+            if c.re.is_nan() { return Self::EMPTY.with_complex(true) }
+            return Self::point(c.re).loosen();
+        }
+        let r = match b {
+            // gcd is at most the largest input, unless they're all 0
+            Builtin::Gcd => Self::new(0.0, args.iter().map(|a| a.lo.abs().max(a.hi.abs())).fold(0.0, f64::max)),
+            Builtin::Lcm => Self::new(0.0, f64::INFINITY),
+            _ => Self::ENTIRE,
+        };
+        r.with_complex(true)
+    }
+
+    /// Applies one of the special functions, whose values aren't as precise as the others'.
+    fn special(b: Builtin, args: &[Self]) -> Self {
+        let at = |v: &[f64]| b.apply_complex(&v.iter().map(|v| Complex64::from(*v)).collect::<Vec<Complex64>>());
+        if args.iter().all(|a| a.lo == a.hi) {
+            let c = at(&args.iter().map(|a| a.lo).collect::<Vec<f64>>());
+            if c.im != 0.0 { return Self::EMPTY.with_complex(true) }
+            return Self::point(c.re).rough();
+        }
+        let f = |v: f64| at(&[v]).re;
+        let a = args[0];
+        let r = match b {
+            // gamma has its minimum on the positive real line at `GAMMA_MIN`, and poles at 0 and below.
+            Builtin::Gamma | Builtin::Lgamma | Builtin::Factorial => {
+                let a = if b == Builtin::Factorial { a.add(&Self::point(1.0)) } else { a };
+                let g = if b == Builtin::Lgamma { f64::ln } else { |v| v };
+                if a.lo <= 0.0 { return Self::ENTIRE.with_complex(b == Builtin::Lgamma) }
+                let gamma = |v: f64| g(special::gamma(v.into()).re);
+                let (lo, hi) = (gamma(a.lo), gamma(a.hi));
+                if a.hi <= GAMMA_MIN.0 {
+                    Self::new(hi, lo)
+                } else if a.lo >= GAMMA_MIN.0 {
+                    Self::new(lo, hi)
+                } else {
+                    Self::new(g(GAMMA_MIN.1), lo.max(hi))
+                }
+            },
+            Builtin::Digamma if a.lo > 0.0 => Self::new(f(a.lo), f(a.hi)),
+            // beta decreases in both inputs while they're positive
+            Builtin::Beta if (a.lo > 0.0) & (args[1].lo > 0.0) => {
+                let beta = |a: f64, b: f64| at(&[a, b]).re;
+                Self::new(beta(a.hi, args[1].hi), beta(a.lo, args[1].lo))
+            },
+            Builtin::Erf => Self::new(f(a.lo), f(a.hi)),
+            Builtin::Erfc => Self::new(f(a.hi), f(a.lo)),
+            Builtin::BesselJ if a.as_int().is_some() => Self::new(-1.0, 1.0),
+            Builtin::BesselY if a.as_int().is_some() => Self::ENTIRE.with_complex(args[1].lo < 0.0),
+            // zeta decreases from its pole at 1
+            Builtin::Zeta if a.lo > 1.0 => Self::new(f(a.hi), f(a.lo)),
+            _ => return Self::ENTIRE.with_complex(true),
+        };
+        r.rough()
+    }
+
+    /// Applies a builtin to intervals.
+    pub fn apply(b: Builtin, args: &[Self]) -> Self {
+        if matches!(b, Builtin::Max | Builtin::Min) {
+            return Self::extreme(b == Builtin::Max, args);
+        }
+
+        let a = args[0];
+        // These are real even for values that aren't.
+        if args.iter().any(|a| a.complex) {
+            return match b {
+                Builtin::Abs => Self::new(0.0, f64::INFINITY),
+                Builtin::Arg => Self::new(-PI, PI),
+                Builtin::Re | Builtin::Im => Self::ENTIRE,
+                _ => Self::ENTIRE.with_complex(true),
+            };
+        }
+        if args.iter().any(|a| a.is_empty()) { return Self::EMPTY }
+
+        match b {
+            Builtin::Ln => a.increasing(f64::ln, (0.0, f64::INFINITY)),
+            Builtin::Log => args[1].increasing(f64::ln, (0.0, f64::INFINITY)).mul(&a.increasing(f64::ln, (0.0, f64::INFINITY)).recip()),
+            // * odd roots are real and increasing everywhere, see `odd_root`
+            Builtin::Root if (a.lo == a.hi) & (a.lo > 0.0) & (a.lo.rem_euclid(2.0) == 1.0) => {
+                let n = a.lo;
+                args[1].increasing(|x| odd_root(n, x).unwrap_or_else(|| x.powf(n.recip())), (f64::NEG_INFINITY, f64::INFINITY))
+            },
+            Builtin::Root => args[1].pow(&a.recip()),
+            Builtin::Sqrt => a.increasing(f64::sqrt, (0.0, f64::INFINITY)),
+            Builtin::Cbrt => a.increasing(f64::cbrt, (f64::NEG_INFINITY, f64::INFINITY)),
+
+            Builtin::Sin => a.periodic(f64::sin, FRAC_PI_2),
+            Builtin::Cos => a.periodic(f64::cos, 0.0),
+            Builtin::Tan => {
+                // Anything with a pole inside can be any number.
+                if (a.hi - a.lo).is_nan() || a.hi - a.lo >= PI || ((a.lo - FRAC_PI_2) / PI).ceil() <= ((a.hi - FRAC_PI_2) / PI).floor() {
+                    return Self::ENTIRE;
+                }
+                Self::new(a.lo.tan(), a.hi.tan()).loosen()
+            },
+            Builtin::Sinh => a.increasing(f64::sinh, (f64::NEG_INFINITY, f64::INFINITY)),
+            Builtin::Cosh => a.even(f64::cosh),
+            Builtin::Tanh => a.increasing(f64::tanh, (f64::NEG_INFINITY, f64::INFINITY)),
+            Builtin::Asin => a.increasing(f64::asin, (-1.0, 1.0)),
+            Builtin::Acos => a.neg().increasing(|v| (-v).acos(), (-1.0, 1.0)),
+            Builtin::Atan => a.increasing(f64::atan, (f64::NEG_INFINITY, f64::INFINITY)),
+            Builtin::Asinh => a.increasing(f64::asinh, (f64::NEG_INFINITY, f64::INFINITY)),
+            Builtin::Acosh => a.increasing(f64::acosh, (1.0, f64::INFINITY)),
+            Builtin::Atanh => a.increasing(f64::atanh, (-1.0, 1.0)),
+
+            Builtin::Abs => a.even(f64::abs),
+            Builtin::Re | Builtin::Conj => a,
+            Builtin::Im => Self::point(0.0),
+            // * real numbers have an argument of 0 or pi, or -pi with a negative zero imaginary part
+            Builtin::Arg => if a.lo < 0.0 { Self::new(-PI, PI) } else { Self::point(0.0) },
+            Builtin::Polar => {
+                let theta = args[1];
+                let r = a.mul(&Self::apply(Builtin::Cos, &[theta]));
+                r.with_complex(theta != Self::point(0.0))
+            },
+
+            Builtin::Gamma | Builtin::Lgamma | Builtin::Digamma | Builtin::Factorial | Builtin::Beta
+            | Builtin::Erf | Builtin::Erfc | Builtin::BesselJ | Builtin::BesselY | Builtin::Zeta => Self::special(b, args),
+
+            // These never go down, and the bounds are whole numbers so there's nothing to round.
+            Builtin::Floor => Self::new(a.lo.floor(), a.hi.floor()),
+            Builtin::Ceil => Self::new(a.lo.ceil(), a.hi.ceil()),
+            Builtin::Round => Self::new(a.lo.round(), a.hi.round()),
+            Builtin::Sign => Self::new(integer::sign(a.lo), integer::sign(a.hi)),
+            Builtin::Mod => a.modulo(&args[1]),
+            Builtin::Gcd | Builtin::Lcm | Builtin::NCr | Builtin::NPr => Self::whole(b, args),
+
+            Builtin::Max | Builtin::Min => unreachable!(),
+
+            Builtin::Dot | Builtin::Cross | Builtin::Det | Builtin::Inv | Builtin::Transpose => {
+                panic!("ERROR: {b:?} has to be resolved before evaluating!")
+            },
+        }
+    }
+}
+
+impl Expr {
+    /// Evaluates `self` with every variable somewhere in its interval. See `Interval`.
+    pub fn evaluate_interval(&self, vars: &HashMap<String, Interval>, funcs: &HashMap<String, Func>) -> Interval {
+        self.interval(vars, funcs, 0)
+    }
+
+    fn interval(&self, vars: &HashMap<String, Interval>, funcs: &HashMap<String, Func>, depth: usize) -> Interval {
+        match self {
+            Self::Term(Term::Var(v)) => *vars.get(v).unwrap_or_else(|| panic!("ERROR: variable {v} has no value!")),
+            Self::Term(t) => {
+                let c = t.as_complex();
+                if c.im != 0.0 { return Interval::EMPTY.with_complex(true) }
+                // * the float is only close to `pi`, `e` or a fraction like `1/3`, so the interval
+                //   has to reach both ways
+                let exact = match t {
+                    Term::Const(_) => false,
+                    Term::Rational(r) => BigRational::from_float(c.re).as_ref() == Some(r),
+                    _ => true,
+                };
+                if !exact { return Interval::new(c.re.next_down(), c.re.next_up()) }
+                Interval::point(c.re)
+            },
+            Self::Add(n) => n.iter().fold(Interval::point(0.0), |r, a| r.add(&a.interval(vars, funcs, depth))),
+            Self::Mul(n) => n.iter().fold(Interval::point(1.0), |r, a| r.mul(&a.interval(vars, funcs, depth))),
+            Self::Pow(a, b) => a.interval(vars, funcs, depth).pow(&b.interval(vars, funcs, depth)),
+            Self::Fn(name, args) => {
+                let f = funcs.get(name).unwrap_or_else(|| panic!("ERROR: function {name} is not defined!"));
+                // Without a way to stop, recursion could go on forever.
+                if depth >= MAX_RECURSION {
+                    return Interval::ENTIRE.with_complex(true);
+                }
+                let mut inner = vars.clone();
+                for (a, v) in f.args().iter().zip(args) {
+                    inner.insert(a.clone(), v.interval(vars, funcs, depth));
+                }
+                f.body().interval(&inner, funcs, depth + 1)
+            },
+            Self::Builtin(b, n) => Interval::apply(*b, &n.iter().map(|a| a.interval(vars, funcs, depth)).collect::<Vec<Interval>>()),
+            Self::Series(s, k, a, b, n) => {
+                // Bounds that change with the variables could give any number of terms.
+                let (from, to) = (a.interval(vars, funcs, depth), b.interval(vars, funcs, depth));
+                let finite = [from.lo, from.hi, to.lo, to.hi].iter().all(|v| v.is_finite());
+                let (lo, hi) = (Series::bounds(from.lo, to.lo), Series::bounds(from.hi, to.hi));
+                if !finite | (lo != hi) | from.complex | to.complex {
+                    return Interval::ENTIRE.with_complex(true);
+                }
+                let (from, to) = lo;
+                let mut inner = vars.clone();
+                let mut acc = Interval::point(s.identity().as_complex().re);
+                for i in from..=to {
+                    inner.insert(k.clone(), Interval::point(i as f64));
+                    let v = n.interval(&inner, funcs, depth);
+                    acc = match s {
+                        Series::Sum => acc.add(&v),
+                        Series::Prod => acc.mul(&v),
+                    };
+                }
+                acc
+            },
+            Self::List(_) | Self::Range(_, _) | Self::Reduce(_, _) => panic!("ERROR: lists have to be broadcast before evaluating!"),
+            Self::Vector(_) | Self::Matrix(_) => panic!("ERROR: vectors and matrices have to be evaluated one component at a time!"),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use num::BigRational;
+use num_complex::Complex64;
+
+use crate::expr::{f::*, Builtin, Context, Expr, Func, Term};
+use crate::interval::Interval;
+use crate::test_util::var;
+
+
+/// Splits `lo..hi` into `n` pieces.
+fn pieces(lo: f64, hi: f64, n: usize) -> Vec<(f64, f64)> {
+    (0..n).map(|i| (lo + (hi - lo) * i as f64 / n as f64, lo + (hi - lo) * (i + 1) as f64 / n as f64)).collect()
+}
+
+/// Asserts that for every box of `x` and `y` in the pieces of `-range..range`, the real
+/// values of `e` at points in the box are in the interval of the box.
+fn encloses(e: &Expr, funcs: &HashMap<String, Func>, range: f64) {
+    for (x0, x1) in pieces(-range, range, 7) {
+        for (y0, y1) in pieces(-range, range, 3) {
+            let vars = HashMap::from([
+                ("x".to_string(), Interval::new(x0, x1)),
+                ("y".to_string(), Interval::new(y0, y1)),
+            ]);
+            let i = e.evaluate_interval(&vars, funcs);
+
+            for (x, _) in pieces(x0, x1, 40).into_iter().chain([(x1, x1)]) {
+                for (y, _) in pieces(y0, y1, 5).into_iter().chain([(y1, y1)]) {
+                    let vars = HashMap::from([("x".to_string(), Complex64::from(x)), ("y".to_string(), Complex64::from(y))]);
+                    let v = e.evaluate(&vars, funcs);
+                    if !(v.re.is_finite() & v.im.is_finite()) { continue }
+                    // * complex versions of real functions can leave rounding noise in the imaginary part
+                    if v.im.abs() > 1e-12 * (1.0 + v.re.abs()) {
+                        assert!(i.complex, "{e:?} is {v} at ({x}, {y}) but {i:?} isn't complex");
+                    } else {
+                        assert!(i.contains(v.re), "{e:?} is {v} at ({x}, {y}) but {i:?} doesn't contain it");
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn encloses_all(es: impl IntoIterator<Item = Box<Expr>>) {
+    for e in es {
+        encloses(&e, &HashMap::new(), 3.0);
+    }
+}
+
+
+#[test]
+fn arithmetic() {
+    let (x, y) = (var("x"), var("y"));
+    encloses_all(vec![
+        add(x.clone(), y.clone()),
+        sub(x.clone(), mul(num(2.0), y.clone())),
+        mul(x.clone(), y.clone()),
+        mul(x.clone(), x.clone()),
+        div(x.clone(), y.clone()),
+        div(num(1.0), x.clone()),
+        div(add(pow(x.clone(), num(2.0)), num(-1.0)), sub(x.clone(), num(1.0))),
+        pow(x.clone(), num(3.0)),
+        pow(x.clone(), num(-2.0)),
+        pow(x.clone(), num(0.5)),
+        pow(x.clone(), y.clone()),
+        pow(num(2.0), x.clone()),
+        pow(sqrt(x.clone()), num(2.0)),
+    ]);
+}
+
+#[test]
+fn builtins() {
+    let (x, y) = (var("x"), var("y"));
+    let mut es = vec![
+        ln(x.clone()),
+        log(num(2.0), x.clone()),
+        log(x.clone(), y.clone()),
+        root(num(3.0), x.clone()),
+        root(y.clone(), x.clone()),
+        polar(x.clone(), y.clone()),
+        builtin(Builtin::Max, vec![x.clone(), y.clone(), num(0.5)]),
+        builtin(Builtin::Min, vec![x.clone(), div(num(1.0), y.clone())]),
+    ];
+    for b in [
+        Builtin::Sqrt, Builtin::Cbrt,
+        Builtin::Sin, Builtin::Cos, Builtin::Tan, Builtin::Sinh, Builtin::Cosh, Builtin::Tanh,
+        Builtin::Asin, Builtin::Acos, Builtin::Atan, Builtin::Asinh, Builtin::Acosh, Builtin::Atanh,
+        Builtin::Abs, Builtin::Re, Builtin::Im, Builtin::Arg, Builtin::Conj,
+    ] {
+        es.push(builtin(b, vec![x.clone()]));
+        es.push(builtin(b, vec![mul(num(2.0), sub(x.clone(), y.clone()))]));
+    }
+    encloses_all(es);
+}
+
+#[test]
+fn special_functions() {
+    let (x, y) = (var("x"), var("y"));
+    encloses_all(vec![
+        gamma(x.clone()),
+        gamma(add(x.clone(), num(3.5))),
+        lgamma(add(x.clone(), num(3.0))),
+        lgamma(x.clone()),
+        digamma(add(x.clone(), num(3.0))),
+        factorial(y.clone()),
+        beta(add(x.clone(), num(3.0)), add(y.clone(), num(3.5))),
+        erf(mul(num(2.0), x.clone())),
+        erfc(x.clone()),
+        besselj(num(2.0), mul(num(3.0), x.clone())),
+        bessely(num(1.0), x.clone()),
+        zeta(add(x.clone(), num(4.0))),
+        zeta(x.clone()),
+    ]);
+}
+
+#[test]
+fn step_functions() {
+    let (x, y) = (var("x"), var("y"));
+    encloses_all(vec![
+        floor(x.clone()),
+        ceil(sub(mul(num(2.0), x.clone()), y.clone())),
+        round(mul(x.clone(), y.clone())),
+        sign(sub(x.clone(), num(1.0))),
+        modulo(x.clone(), num(1.5)),
+        modulo(mul(num(3.0), x.clone()), y.clone()),
+        modulo(x.clone(), num(-2.0)),
+        gcd(x.clone(), num(6.0)),
+        lcm(x.clone(), y.clone()),
+        ncr(x.clone(), num(2.0)),
+        npr(num(5.0), y.clone()),
+    ]);
+    // Within one period, `mod` is as tight as adding.
+    let r = Interval::apply(Builtin::Mod, &[Interval::new(4.5, 5.0), Interval::point(2.0)]);
+    assert!((r.lo <= 0.5) & (r.lo > 0.49) & (r.hi >= 1.0) & (r.hi < 1.01));
+    assert_eq!(Interval::apply(Builtin::Floor, &[Interval::new(1.5, 3.2)]), Interval::new(1.0, 3.0));
+}
+
+#[test]
+fn complex_values() {
+    let (x, i) = (var("x"), term(Complex64::I.into()));
+    encloses_all(vec![
+        mul(mul(x.clone(), i.clone()), i.clone()),
+        add(sqrt(x.clone()), num(1.0)),
+        abs(sqrt(x.clone())),
+        re(ln(x.clone())),
+        im(ln(x.clone())),
+        mul(sqrt(x.clone()), sqrt(x.clone())),
+        mul(sqrt(x.clone()), num(0.0)),
+    ]);
+}
+
+#[test]
+fn series() {
+    let x = var("x");
+    encloses_all(vec![
+        sum("k".to_string(), num(1.0), num(5.0), pow(x.clone(), var("k"))),
+        prod("k".to_string(), num(1.0), num(4.0), sub(x.clone(), var("k"))),
+        sum("k".to_string(), num(1.0), x.clone(), var("k")),
+    ]);
+}
+
+#[test]
+fn fractions_are_enclosed() {
+    // 1/3 isn't a float, so the closest one is only on one side of it.
+    let third = BigRational::new(1.into(), 3.into());
+    let i = Expr::Term(Term::Rational(third.clone())).evaluate_interval(&HashMap::new(), &HashMap::new());
+    let (lo, hi) = (BigRational::from_float(i.lo).unwrap(), BigRational::from_float(i.hi).unwrap());
+    assert!((lo < third) & (third < hi), "{i:?}");
+    // Fractions that are floats stay points.
+    let half = Expr::Term(Term::Rational(BigRational::new(1.into(), 2.into())));
+    assert_eq!(half.evaluate_interval(&HashMap::new(), &HashMap::new()), Interval::point(0.5));
+    // Bounds are still fine when they're fractions.
+    let s = sum("k".to_string(), Box::new(Expr::Term(Term::Rational(third))), num(3.0), var("k"));
+    let i = s.evaluate_interval(&HashMap::new(), &HashMap::new());
+    assert!(i.contains(6.0) & (i.hi - i.lo < 1e-12), "{i:?}");
+}
+
+#[test]
+fn functions() {
+    let mut ctx = Context::new();
+    ctx.def_func("sq", false, vec!["a".to_string()], *mul(var("a"), var("a"))).unwrap();
+    let funcs = HashMap::from([("sq".to_string(), ctx.get_func("sq").unwrap().clone())]);
+    encloses(&func("sq".to_string(), vec![sin(var("x"))]), &funcs, 3.0);
+
+    // Recursion can't stop on its own, so it's cut off.
+    ctx.def_func("f", true, vec!["a".to_string()], *func("f".to_string(), vec![add(var("a"), num(1.0))])).unwrap();
+    let funcs = HashMap::from([("f".to_string(), ctx.get_func("f").unwrap().clone())]);
+    let vars = HashMap::from([("x".to_string(), Interval::new(0.0, 1.0))]);
+    assert_eq!(func("f".to_string(), vec![var("x")]).evaluate_interval(&vars, &funcs).hi, f64::INFINITY);
+}
+
+#[test]
+fn division_by_zero() {
+    let one = Interval::point(1.0);
+    assert_eq!(Interval::new(-1.0, 1.0).recip(), Interval::ENTIRE);
+    let r = one.mul(&Interval::new(0.0, 2.0).recip());
+    assert!((r.lo <= 0.5) & (r.lo > 0.49) & (r.hi == f64::INFINITY));
+    let r = one.mul(&Interval::new(-2.0, 0.0).recip());
+    assert!((r.hi >= -0.5) & (r.hi < -0.49) & (r.lo == f64::NEG_INFINITY));
+    assert!(Interval::point(0.0).recip().is_empty());
+}
+
+#[test]
+fn branches() {
+    let r = Interval::apply(Builtin::Sqrt, &[Interval::new(-1.0, 4.0)]);
+    assert!(r.complex & (r.lo <= 0.0) & (r.lo > -1e-300) & (r.hi >= 2.0) & (r.hi < 2.001));
+    let r = Interval::apply(Builtin::Sqrt, &[Interval::new(1.0, 4.0)]);
+    assert!(!r.complex);
+    let r = Interval::apply(Builtin::Ln, &[Interval::new(-1.0, 0.0)]);
+    assert!(r.complex);
+    let r = Interval::apply(Builtin::Ln, &[Interval::new(-1.0, 1.0)]);
+    assert!(r.complex & (r.lo == f64::NEG_INFINITY) & (r.hi >= 0.0) & (r.hi < 1e-300));
+    let r = Interval::apply(Builtin::Sin, &[Interval::new(0.0, std::f64::consts::PI)]);
+    assert!((r.lo <= 0.0) & (r.lo > -1e-15) & (r.hi == 1.0));
+}
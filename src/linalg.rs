@@ -0,0 +1,387 @@
+/*
+Vectors and matrices.
+
+Vectors and matrices only exist before simplification. Shapes are checked when
+a `Context` is made, and `resolve_linalg` then works every vector and matrix
+operation out symbolically, so what's left is a vector or matrix of ordinary
+expressions that simplify and evaluate one component at a time.
+*/
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::expr::{f, Builtin, Context, Expr, Series, Term};
+
+
+/// The shape of a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Scalar,
+    Vector(usize),
+    /// Rows, then columns.
+    Matrix(usize, usize),
+}
+impl Display for Shape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Scalar => write!(f, "a number"),
+            Self::Vector(n) => write!(f, "a {n}-vector"),
+            Self::Matrix(r, c) => write!(f, "a {r}x{c} matrix"),
+        }
+    }
+}
+impl Shape {
+    /// The shape of `a * b`, if they can be multiplied.
+    fn mul(a: Self, b: Self) -> Option<Self> {
+        match (a, b) {
+            (Self::Scalar, o) | (o, Self::Scalar) => Some(o),
+            (Self::Matrix(r, n), Self::Vector(m)) if n == m => Some(Self::Vector(r)),
+            (Self::Matrix(r, n), Self::Matrix(m, c)) if n == m => Some(Self::Matrix(r, c)),
+            _ => None,
+        }
+    }
+}
+
+
+impl Expr {
+    /// Works out the shape of `self`. `args` are the shapes of the function arguments in scope,
+    /// and `seen` are the variables and functions being looked into, to stop at recursion.
+    pub fn shape(&self, c: &Context, args: &HashMap<String, Shape>, seen: &mut Vec<String>) -> Result<Shape, String> {
+        match self {
+            Self::Term(Term::Var(v)) => {
+                if let Some(s) = args.get(v) { return Ok(*s) }
+                let Some(val) = c.get_var(v) else { return Ok(Shape::Scalar) };
+                if seen.contains(v) { return Ok(Shape::Scalar) }
+                seen.push(v.clone());
+                let s = val.shape(c, &HashMap::new(), seen);
+                seen.pop();
+                s
+            },
+            Self::Term(_) => Ok(Shape::Scalar),
+            Self::Add(n) => {
+                let mut shape = n[0].shape(c, args, seen)?;
+                for a in &n[1..] {
+                    let s = a.shape(c, args, seen)?;
+                    if s != shape {
+                        return Err(format!("ERROR: can't add {shape} and {s}!"));
+                    }
+                    shape = s;
+                }
+                Ok(shape)
+            },
+            Self::Mul(n) => {
+                let mut shape = n[0].shape(c, args, seen)?;
+                for a in &n[1..] {
+                    let s = a.shape(c, args, seen)?;
+                    shape = Shape::mul(shape, s).ok_or_else(|| format!("ERROR: can't multiply {shape} by {s}!"))?;
+                }
+                Ok(shape)
+            },
+            Self::Pow(a, b) => {
+                let (a, b) = (a.shape(c, args, seen)?, b.shape(c, args, seen)?);
+                match (a, b) {
+                    (Shape::Scalar, Shape::Scalar) => Ok(Shape::Scalar),
+                    (Shape::Matrix(r, c), Shape::Scalar) if r == c => Ok(a),
+                    _ => Err(format!("ERROR: can't raise {a} to the power of {b}!")),
+                }
+            },
+            Self::Fn(name, n) => {
+                let shapes = n.iter().map(|a| a.shape(c, args, seen)).collect::<Result<Vec<Shape>, String>>()?;
+                let Some(func) = c.get_func(name) else {
+                    // `x(y)` is a multiplication when there isn't a function named `x`.
+                    return Expr::Mul(vec![*f::term(name.clone().into()), *n[0].clone()]).shape(c, args, seen);
+                };
+                if seen.contains(name) { return Ok(Shape::Scalar) }
+                seen.push(name.clone());
+                let s = func.body().shape(c, &func.args().iter().cloned().zip(shapes).collect(), seen);
+                seen.pop();
+                s
+            },
+            Self::Builtin(b, n) => {
+                let shapes = n.iter().map(|a| a.shape(c, args, seen)).collect::<Result<Vec<Shape>, String>>()?;
+                builtin_shape(*b, &shapes)
+            },
+            Self::Series(_, k, a, b, n) => {
+                for s in [a.shape(c, args, seen)?, b.shape(c, args, seen)?] {
+                    if s != Shape::Scalar {
+                        return Err(format!("ERROR: the bounds of a series have to be numbers, not {s}!"));
+                    }
+                }
+                let mut args = args.clone();
+                args.insert(k.clone(), Shape::Scalar);
+                n.shape(c, &args, seen)
+            },
+            Self::List(n) => {
+                // Lists are broadcast, so they have the shape of their items.
+                let Some(first) = n.first() else { return Ok(Shape::Scalar) };
+                let shape = first.shape(c, args, seen)?;
+                for a in &n[1..] {
+                    let s = a.shape(c, args, seen)?;
+                    if s != shape {
+                        return Err(format!("ERROR: the items of a list have to be the same shape, not {shape} and {s}!"));
+                    }
+                }
+                Ok(shape)
+            },
+            Self::Range(_, _) => Ok(Shape::Scalar),
+            Self::Reduce(_, a) => {
+                let s = a.shape(c, args, seen)?;
+                if s != Shape::Scalar {
+                    return Err(format!("ERROR: lists of {s} can't be reduced!"));
+                }
+                Ok(s)
+            },
+            Self::Vector(n) => {
+                for a in n {
+                    let s = a.shape(c, args, seen)?;
+                    if s != Shape::Scalar {
+                        return Err(format!("ERROR: the components of a vector have to be numbers, not {s}!"));
+                    }
+                }
+                Ok(Shape::Vector(n.len()))
+            },
+            Self::Matrix(m) => {
+                let cols = m.first().map_or(0, |r| r.len());
+                if m.iter().any(|r| r.len() != cols) {
+                    return Err("ERROR: every row of a matrix has to be the same length!".to_string());
+                }
+                for a in m.iter().flatten() {
+                    let s = a.shape(c, args, seen)?;
+                    if s != Shape::Scalar {
+                        return Err(format!("ERROR: the components of a matrix have to be numbers, not {s}!"));
+                    }
+                }
+                Ok(Shape::Matrix(m.len(), cols))
+            },
+        }
+    }
+
+    /// Checks that the bounds of ranges and the powers of matrices are constant, since they're
+    /// worked out before simplifying, and that reducers without an empty value get items. Expects variables and functions to have been expanded and
+    /// shapes to have been checked. `args` are the shapes of the names left in.
+    pub fn check_constant(&self, c: &Context, args: &HashMap<String, Shape>) -> Result<(), String> {
+        // * broadcast first, so lists of bounds (`[1..[2, 3]]`) are each checked
+        let constant = |e: &Expr| e.broadcast().items().into_iter().map(|a| a.flatten().reduce_const().as_number()).collect::<Option<Vec<Term>>>();
+        match self {
+            Self::Term(_) => Ok(()),
+            Self::Add(n) | Self::Mul(n) | Self::List(n) | Self::Vector(n) => n.iter().try_for_each(|a| a.check_constant(c, args)),
+            Self::Fn(_, n) | Self::Builtin(_, n) => n.iter().try_for_each(|a| a.check_constant(c, args)),
+            Self::Matrix(m) => m.iter().flatten().try_for_each(|a| a.check_constant(c, args)),
+            Self::Reduce(r, a) => {
+                a.check_constant(c, args)?;
+                if r.empty().is_none() && a.broadcast().items().is_empty() {
+                    return Err(format!("ERROR: can't take the {r:?} of an empty list!"));
+                }
+                Ok(())
+            },
+            Self::Pow(a, b) => {
+                a.check_constant(c, args)?;
+                b.check_constant(c, args)?;
+                if !matches!(a.shape(c, args, &mut Vec::new())?, Shape::Matrix(_, _)) { return Ok(()) }
+                let whole = |p: &Term| {
+                    let p = p.as_complex();
+                    (p.im == 0.0) & (p.re.fract() == 0.0)
+                };
+                match constant(b) {
+                    Some(p) if p.iter().all(whole) => Ok(()),
+                    _ => Err("ERROR: matrices can only be raised to constant integer powers!".to_string()),
+                }
+            },
+            Self::Series(_, k, a, b, n) => {
+                a.check_constant(c, args)?;
+                b.check_constant(c, args)?;
+                let mut args = args.clone();
+                args.insert(k.clone(), Shape::Scalar);
+                n.check_constant(c, &args)
+            },
+            Self::Range(a, b) => {
+                a.check_constant(c, args)?;
+                b.check_constant(c, args)?;
+                let (Some(a), Some(b)) = (constant(a), constant(b)) else {
+                    return Err("ERROR: the bounds of a range have to be constant!".to_string());
+                };
+                // * the widest of them, when the bounds are lists
+                let lo = a.iter().map(|t| t.as_complex().re).fold(f64::INFINITY, f64::min);
+                let hi = b.iter().map(|t| t.as_complex().re).fold(f64::NEG_INFINITY, f64::max);
+                let (lo, hi) = Series::bounds(lo, hi);
+                if hi.saturating_sub(lo) >= Expr::RANGE_LIMIT {
+                    return Err(format!("ERROR: the range [{lo}..{hi}] is too long! ranges can have at most {} items.", Expr::RANGE_LIMIT));
+                }
+                Ok(())
+            },
+        }
+    }
+
+    /// Works out every vector and matrix operation, leaving vectors and matrices only at the
+    /// top of the tree (or in the items of a list). Expects shapes to have been checked and
+    /// lists to have been broadcast.
+    pub fn resolve_linalg(&self) -> Self {
+        match self {
+            Self::Term(_) => self.clone(),
+            Self::Add(n) => {
+                let n: Vec<Expr> = n.iter().map(|a| a.resolve_linalg()).collect();
+                match &n[0] {
+                    Self::Vector(v) => Self::Vector((0..v.len()).map(|i| {
+                        Self::Add(n.iter().map(|a| a.components()[i].clone()).collect())
+                    }).collect()),
+                    Self::Matrix(m) => {
+                        let cols = m[0].len();
+                        let items: Vec<Expr> = (0..m.len() * cols).map(|i| {
+                            Self::Add(n.iter().map(|a| a.components()[i].clone()).collect())
+                        }).collect();
+                        Self::Matrix(items.chunks(cols).map(|r| r.to_vec()).collect())
+                    },
+                    _ => Self::Add(n),
+                }
+            },
+            Self::Mul(n) => {
+                // Numbers can be moved to the front, but vectors and matrices have to stay in order.
+                let (scalars, rest): (Vec<Expr>, Vec<Expr>) = n.iter().map(|a| a.resolve_linalg()).partition(|a| !a.is_linalg());
+                let Some(rest) = rest.into_iter().reduce(|a, b| mul(&a, &b)) else { return Self::Mul(scalars) };
+                if scalars.is_empty() { return rest }
+                rest.map_components(&|a| Self::Mul([scalars.clone(), vec![a.clone()]].concat()))
+            },
+            Self::Pow(a, b) => {
+                let (a, b) = (a.resolve_linalg(), b.resolve_linalg());
+                let Self::Matrix(m) = &a else { return *f::pow(a.r#box(), b.r#box()) };
+
+                // `check_constant` makes sure this is a constant integer.
+                let p = b.flatten().reduce_const().force_const().as_complex();
+                let base = if p.re < 0.0 { inverse(m) } else { m.clone() };
+                let mut acc = identity(m.len());
+                for _ in 0..(p.re.abs() as usize) {
+                    acc = matmul(&acc, &base);
+                }
+                Self::Matrix(acc)
+            },
+            Self::Fn(s, n) => Self::Fn(s.clone(), n.iter().map(|a| a.resolve_linalg().r#box()).collect()),
+            Self::Builtin(b, n) => {
+                let n: Vec<Expr> = n.iter().map(|a| a.resolve_linalg()).collect();
+                match (b, &n[..]) {
+                    (Builtin::Dot, [Self::Vector(a), Self::Vector(b)]) => dot(a, b),
+                    (Builtin::Cross, [Self::Vector(a), Self::Vector(b)]) => Self::Vector(vec![
+                        Self::Add(vec![Self::Mul(vec![a[1].clone(), b[2].clone()]), *f::neg(Self::Mul(vec![a[2].clone(), b[1].clone()]).r#box())]),
+                        Self::Add(vec![Self::Mul(vec![a[2].clone(), b[0].clone()]), *f::neg(Self::Mul(vec![a[0].clone(), b[2].clone()]).r#box())]),
+                        Self::Add(vec![Self::Mul(vec![a[0].clone(), b[1].clone()]), *f::neg(Self::Mul(vec![a[1].clone(), b[0].clone()]).r#box())]),
+                    ]),
+                    (Builtin::Det, [Self::Matrix(m)]) => det(m),
+                    (Builtin::Inv, [Self::Matrix(m)]) => Self::Matrix(inverse(m)),
+                    (Builtin::Transpose, [Self::Matrix(m)]) => Self::Matrix(transpose(m)),
+                    (Builtin::Transpose, [Self::Vector(v)]) => Self::Matrix(vec![v.clone()]),
+                    (b, _) if b.is_linalg() => unreachable!("shapes are checked before {b:?} is worked out"),
+                    _ => Self::Builtin(*b, n.into_iter().map(|a| a.r#box()).collect()),
+                }
+            },
+            Self::Series(s, k, a, b, n) => {
+                // Series of vectors and matrices are worked out one component at a time.
+                let (a, b) = (a.resolve_linalg(), b.resolve_linalg());
+                n.resolve_linalg().map_components(&|n| Self::Series(*s, k.clone(), a.clone().r#box(), b.clone().r#box(), n.clone().r#box()))
+            },
+            Self::List(n) => Self::List(n.iter().map(|a| a.resolve_linalg()).collect()),
+            Self::Range(_, _) | Self::Reduce(_, _) => panic!("ERROR: lists have to be broadcast before resolving vectors!"),
+            Self::Vector(n) => Self::Vector(n.iter().map(|a| a.resolve_linalg()).collect()),
+            Self::Matrix(m) => Self::Matrix(m.iter().map(|r| r.iter().map(|a| a.resolve_linalg()).collect()).collect()),
+        }
+    }
+
+    /// Checks if `self` is a vector or matrix.
+    pub fn is_linalg(&self) -> bool {
+        matches!(self, Self::Vector(_) | Self::Matrix(_))
+    }
+
+    /// The components of a vector or matrix (row by row), or just `self` for anything else.
+    pub fn components(&self) -> Vec<Expr> {
+        match self {
+            Self::Vector(n) => n.clone(),
+            Self::Matrix(m) => m.iter().flatten().cloned().collect(),
+            o => vec![o.clone()],
+        }
+    }
+
+    /// Applies `f` to every component of a vector or matrix, or to `self` for anything else.
+    pub fn map_components(&self, f: &dyn Fn(&Expr) -> Expr) -> Self {
+        match self {
+            Self::Vector(n) => Self::Vector(n.iter().map(f).collect()),
+            Self::Matrix(m) => Self::Matrix(m.iter().map(|r| r.iter().map(f).collect()).collect()),
+            o => f(o),
+        }
+    }
+}
+
+/// The shape a builtin gives for inputs of `shapes`.
+fn builtin_shape(b: Builtin, shapes: &[Shape]) -> Result<Shape, String> {
+    use Shape::*;
+    match (b, shapes) {
+        (Builtin::Dot, [Vector(n), Vector(m)]) if n == m => Ok(Scalar),
+        (Builtin::Cross, [Vector(3), Vector(3)]) => Ok(Vector(3)),
+        (Builtin::Det, [Matrix(r, c)]) if r == c => Ok(Scalar),
+        (Builtin::Inv, [Matrix(r, c)]) if r == c => Ok(Matrix(*r, *c)),
+        (Builtin::Transpose, [Matrix(r, c)]) => Ok(Matrix(*c, *r)),
+        (Builtin::Transpose, [Vector(n)]) => Ok(Matrix(1, *n)),
+        (b, s) if b.is_linalg() => {
+            Err(format!("ERROR: {b:?} doesn't work on {}!", s.iter().map(|s| s.to_string()).collect::<Vec<String>>().join(" and ")))
+        },
+        (b, s) => {
+            if let Some(s) = s.iter().find(|s| **s != Scalar) {
+                return Err(format!("ERROR: {b:?} only works on numbers, not {s}!"));
+            }
+            Ok(Scalar)
+        },
+    }
+}
+
+/// Multiplies two resolved values, at least one of which is a vector or matrix.
+fn mul(a: &Expr, b: &Expr) -> Expr {
+    match (a, b) {
+        (Expr::Matrix(a), Expr::Matrix(b)) => Expr::Matrix(matmul(a, b)),
+        (Expr::Matrix(m), Expr::Vector(v)) => Expr::Vector(m.iter().map(|r| dot(r, v)).collect()),
+        _ => panic!("ERROR: can't multiply these shapes!"),
+    }
+}
+
+fn dot(a: &[Expr], b: &[Expr]) -> Expr {
+    Expr::Add(a.iter().zip(b).map(|(a, b)| Expr::Mul(vec![a.clone(), b.clone()])).collect())
+}
+
+fn matmul(a: &[Vec<Expr>], b: &[Vec<Expr>]) -> Vec<Vec<Expr>> {
+    let b = transpose(b);
+    a.iter().map(|r| b.iter().map(|c| dot(r, c)).collect()).collect()
+}
+
+fn transpose(m: &[Vec<Expr>]) -> Vec<Vec<Expr>> {
+    let cols = m.first().map_or(0, |r| r.len());
+    (0..cols).map(|j| m.iter().map(|r| r[j].clone()).collect()).collect()
+}
+
+fn identity(n: usize) -> Vec<Vec<Expr>> {
+    (0..n).map(|i| (0..n).map(|j| *f::num(if i == j { 1.0 } else { 0.0 })).collect()).collect()
+}
+
+/// The matrix without row `i` and column `j`.
+fn minor(m: &[Vec<Expr>], i: usize, j: usize) -> Vec<Vec<Expr>> {
+    m.iter().enumerate().filter(|(r, _)| *r != i)
+        .map(|(_, row)| row.iter().enumerate().filter(|(c, _)| *c != j).map(|(_, a)| a.clone()).collect())
+        .collect()
+}
+
+/// The determinant, by cofactor expansion along the first row.
+fn det(m: &[Vec<Expr>]) -> Expr {
+    match m.len() {
+        0 => *f::num(1.0),
+        1 => m[0][0].clone(),
+        _ => Expr::Add((0..m.len()).map(|j| Expr::Mul(vec![m[0][j].clone(), cofactor(m, 0, j)])).collect()),
+    }
+}
+
+/// The signed cofactor of item `(i, j)`.
+fn cofactor(m: &[Vec<Expr>], i: usize, j: usize) -> Expr {
+    let d = det(&minor(m, i, j));
+    if (i + j).is_multiple_of(2) { return d }
+    *f::neg(d.r#box())
+}
+
+/// The inverse, as the adjugate divided by the determinant.
+fn inverse(m: &[Vec<Expr>]) -> Vec<Vec<Expr>> {
+    let d = det(m).r#box();
+    (0..m.len()).map(|i| (0..m.len()).map(|j| *f::div(cofactor(m, j, i).r#box(), d.clone())).collect()).collect()
+}
@@ -0,0 +1,60 @@
+use crate::expr::Expr;
+use crate::test_util::{int, out, program};
+
+
+/// The errors converting `src` gives.
+fn errors(src: &str) -> Vec<String> {
+    program(src).unwrap_err()
+}
+
+fn matrix(m: &[&[i64]]) -> Expr {
+    Expr::Matrix(m.iter().map(|r| r.iter().map(|a| int(*a)).collect()).collect())
+}
+
+
+#[test]
+fn vector_products() {
+    assert_eq!(out("let out = dot(vec(1, 2, 3), vec(4, 5, 6));"), int(32));
+    assert_eq!(out("let out = cross(vec(1, 0, 0), vec(0, 1, 0));"), Expr::Vector(vec![int(0), int(0), int(1)]));
+    assert_eq!(out("let out = 2 vec(1, 2) + vec(3, 4);"), Expr::Vector(vec![int(5), int(8)]));
+}
+
+#[test]
+fn matrix_arithmetic() {
+    let m = "let m = mat[[1, 2], [3, 4]];";
+    assert_eq!(out(&format!("{m} let out = m * m;")), matrix(&[&[7, 10], &[15, 22]]));
+    assert_eq!(out(&format!("{m} let out = m * vec(1, 1);")), Expr::Vector(vec![int(3), int(7)]));
+    assert_eq!(out(&format!("{m} let out = det(m);")), int(-2));
+    assert_eq!(out(&format!("{m} let out = m * inv(m);")), matrix(&[&[1, 0], &[0, 1]]));
+    assert_eq!(out(&format!("{m} let out = transpose(m);")), matrix(&[&[1, 3], &[2, 4]]));
+    assert_eq!(out("let out = transpose(vec(1, 2));"), matrix(&[&[1, 2]]));
+}
+
+#[test]
+fn matrix_powers() {
+    let m = "let m = mat[[1, 1], [0, 1]];";
+    assert_eq!(out(&format!("{m} let out = m^3;")), matrix(&[&[1, 3], &[0, 1]]));
+    assert_eq!(out(&format!("{m} let n = 2; let out = m^(n - 3);")), matrix(&[&[1, -1], &[0, 1]]));
+    assert_eq!(out(&format!("{m} let out = m^0;")), matrix(&[&[1, 0], &[0, 1]]));
+    assert_eq!(out(&format!("{m} fn p(a, n) = a^n; let out = p(m, 2);")), matrix(&[&[1, 2], &[0, 1]]));
+    for power in ["x", "0.5", "i", "[1..x]"] {
+        let errs = errors(&format!("{m} fn p(a, n) = a^n; let out = p(m, {power});"));
+        assert!(errs.iter().any(|e| e.contains("constant")), "m^{power}: {errs:?}");
+    }
+}
+
+#[test]
+fn shape_errors() {
+    for (src, error) in [
+        ("let out = vec(1, 2) + vec(1, 2, 3);", "can't add"),
+        ("let out = dot(vec(1, 2), vec(1, 2, 3));", "Dot doesn't work"),
+        ("let out = cross(vec(1, 2), vec(3, 4));", "Cross doesn't work"),
+        ("let out = det(mat[[1, 2, 3], [4, 5, 6]]);", "Det doesn't work"),
+        ("let out = mat[[1, 2]]^2;", "can't raise"),
+        ("let out = sin(vec(1, 2));", "only works on numbers"),
+        ("let out = mat[[1, 2], [3]];", "same length"),
+    ] {
+        let errs = errors(src);
+        assert!(errs[0].contains(error), "{src}: {errs:?}");
+    }
+}
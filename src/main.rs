@@ -4,33 +4,130 @@
 const C_DEBUG_LEVEL: u8 = 0;
 const E_DEBUG_LEVEL: u8 = 0;
 
+mod analysis;
+#[cfg(test)]
+mod analysis_test;
+mod bigfloat;
+mod constant;
+mod data;
+#[cfg(test)]
+mod data_test;
+mod diff;
 mod expr;
+#[cfg(test)]
+mod expr_test;
+mod implicit;
+#[cfg(test)]
+mod implicit_test;
+mod integer;
+#[cfg(test)]
+mod integer_test;
+mod interval;
+#[cfg(test)]
+mod interval_test;
+mod linalg;
+#[cfg(test)]
+mod linalg_test;
+mod mesh;
+#[cfg(test)]
+mod mesh_test;
+mod number;
+#[cfg(test)]
+mod number_test;
 mod perf_test;
+mod poly;
+#[cfg(test)]
+mod poly_test;
+#[cfg(test)]
+mod parse_test;
 mod parse;
 mod convert;
+#[cfg(test)]
+mod convert_test;
+mod domain;
+#[cfg(test)]
+mod domain_test;
 mod render;
+mod sample;
+#[cfg(test)]
+mod sample_test;
+mod special;
+#[cfg(test)]
+mod special_test;
+#[cfg(test)]
+mod test_util;
 
+use std::collections::HashMap;
 use std::fs;
 
+use num_complex::Complex64;
+
 use expr::{Context, f::*};
 
 fn main() {
-    parse_test();
+    if std::env::args().any(|a| a == "--domain") { return domain_colouring() }
+    let precision = precision();
+    if let Some(grid) = grid() { return render::render(grid, precision) }
+    parse_test(precision);
     
 }
 
-fn parse_test() {
+/// The grid to draw behind the graphs in a window, from `--render` or `--polar`, or `None` to
+/// only write the results to files.
+fn grid() -> Option<render::Grid> {
+    if std::env::args().any(|a| a == "--polar") { return Some(render::Grid::Polar) }
+    if std::env::args().any(|a| a == "--render") { return Some(render::Grid::Cartesian) }
+    None
+}
+
+/// The bits to evaluate with from `--precise` or `--precise=BITS`, or `None` for floats.
+fn precision() -> Option<u32> {
+    for arg in std::env::args().skip(1) {
+        if arg == "--precise" { return Some(number::DEFAULT_PRECISION) }
+        if let Some(bits) = arg.strip_prefix("--precise=") {
+            return Some(bits.parse().unwrap_or_else(|_| panic!("ERROR: {bits} isn't a number of bits!")));
+        }
+    }
+    None
+}
+
+fn parse_test(precision: Option<u32>) {
     println!("reading file...");
-    let f = std::fs::read_to_string("tst/test.txt").unwrap();
-    println!("parsing file...");
-    let p = parse::str_parse(&f);
-    println!("converting tokens...");
-    let c = convert::convert(p);
+    let c = convert::convert_file("tst/test.txt").unwrap();
     fs::write("ctx.txt", format!("{c:#?}"));
     println!("simplifying...");
-    let e = c.simplify_for_var("out");
+    let (e, funcs) = c.simplify_for_var("out");
     println!("writing to file...");
     fs::write("output.txt", format!("{e:#?}"));
-}
 
+    println!("evaluating...");
+    let mut values = String::new();
+    for item in e.items() {
+        // * vectors and matrices are drawn, not listed
+        if matches!(item, expr::Expr::Vector(_) | expr::Expr::Matrix(_)) { continue }
+        for i in -4..=4 {
+            let vars = HashMap::from([("x".to_string(), Complex64::from(i as f64))]);
+            let y = match precision {
+                Some(p) => item.evaluate_precise(&vars, &funcs, p),
+                None => item.evaluate(&vars, &funcs),
+            };
+            values += &format!("{i}: {y}\n");
+        }
+    }
+    fs::write("values.txt", values).unwrap();
+
+    let mut fits = String::new();
+    for name in c.fit_names() {
+        // * fits that couldn't be fitted have already been reported by `Context::fit`
+        let Some(r) = c.get_fit(name).unwrap().regression() else { continue };
+        fits += &format!("{name}:\n{r}\n");
+    }
+    fs::write("fits.txt", fits).unwrap();
+}
 
+/// Writes a domain colouring of `out` to `domain.png`, no window needed.
+fn domain_colouring() {
+    let c = convert::convert_file("tst/test.txt").unwrap();
+    let img = domain::colour_var(&c, "out", -2.0..=2.0, -2.0..=2.0, 512, 512, true);
+    img.write_png("domain.png").unwrap();
+}
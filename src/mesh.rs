@@ -0,0 +1,184 @@
+/*
+This is where surfaces are turned into triangle meshes.
+
+Nothing in here needs a window, the renderer just copies the mesh over.
+*/
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use num_complex::Complex64;
+
+use crate::expr::{Context, Expr, Func};
+
+/// The number of cells along each side of a surface grid.
+pub const GRID_CELLS: usize = 64;
+
+pub type Vertex = [f64; 3];
+
+/// An indexed triangle mesh. Every vertex has a normal, and triangles are counter clockwise
+/// when seen from the side their normals point to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub normals: Vec<Vertex>,
+    pub triangles: Vec<[u32; 3]>,
+}
+impl Mesh {
+    /// Samples `z = f(x, y)` on a grid of `n` by `n` cells. Cells with a corner that isn't
+    /// finite are left out, and so are vertices that only they use.
+    pub fn grid(f: &mut dyn FnMut(f64, f64) -> f64, x: RangeInclusive<f64>, y: RangeInclusive<f64>, n: usize) -> Self {
+        let n = n.max(1);
+        let step = |r: &RangeInclusive<f64>, i: usize| r.start() + (r.end() - r.start()) * i as f64 / n as f64;
+
+        let mut z = vec![vec![0.0; n + 1]; n + 1];
+        for (i, row) in z.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = f(step(&x, i), step(&y, j));
+            }
+        }
+
+        // Vertices are only added once a triangle uses them.
+        let mut mesh = Self::default();
+        let mut index: HashMap<(usize, usize), u32> = HashMap::new();
+        let mut vertex = |mesh: &mut Self, i: usize, j: usize| *index.entry((i, j)).or_insert_with(|| {
+            mesh.vertices.push([step(&x, i), step(&y, j), z[i][j]]);
+            mesh.vertices.len() as u32 - 1
+        });
+
+        for i in 0..n {
+            for j in 0..n {
+                if ![z[i][j], z[i+1][j], z[i][j+1], z[i+1][j+1]].iter().all(|v| v.is_finite()) {
+                    continue;
+                }
+                let a = vertex(&mut mesh, i, j);
+                let b = vertex(&mut mesh, i + 1, j);
+                let c = vertex(&mut mesh, i + 1, j + 1);
+                let d = vertex(&mut mesh, i, j + 1);
+                mesh.triangles.push([a, b, c]);
+                mesh.triangles.push([a, c, d]);
+            }
+        }
+
+        mesh.compute_normals();
+        mesh
+    }
+
+    /// Merges vertices closer than about `tolerance` to each other. Triangles that collapse
+    /// and vertices that are no longer used are removed.
+    pub fn weld(&mut self, tolerance: f64) {
+        let key = |v: &Vertex| v.map(|c| (c / tolerance).round() as i64);
+        let mut index: HashMap<[i64; 3], u32> = HashMap::new();
+        let mut vertices = Vec::new();
+        let remap: Vec<u32> = self.vertices.iter().map(|v| *index.entry(key(v)).or_insert_with(|| {
+            vertices.push(*v);
+            vertices.len() as u32 - 1
+        })).collect();
+
+        self.vertices = vertices;
+        self.triangles = self.triangles.iter()
+            .map(|t| t.map(|i| remap[i as usize]))
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[2] != t[0])
+            .collect();
+        self.remove_unused();
+        self.compute_normals();
+    }
+
+    /// Removes the vertices no triangle uses.
+    fn remove_unused(&mut self) {
+        let mut remap = vec![None; self.vertices.len()];
+        let mut vertices = Vec::new();
+        for t in self.triangles.iter_mut() {
+            for i in t.iter_mut() {
+                *i = *remap[*i as usize].get_or_insert_with(|| {
+                    vertices.push(self.vertices[*i as usize]);
+                    vertices.len() as u32 - 1
+                });
+            }
+        }
+        self.vertices = vertices;
+    }
+
+    /// Writes the mesh as a Wavefront OBJ file.
+    pub fn write_obj(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut obj = String::new();
+        for v in self.vertices.iter() {
+            obj += &format!("v {} {} {}\n", v[0], v[1], v[2]);
+        }
+        for n in self.normals.iter() {
+            obj += &format!("vn {} {} {}\n", n[0], n[1], n[2]);
+        }
+        // * OBJ counts from 1
+        for t in self.triangles.iter() {
+            let [a, b, c] = t.map(|i| i + 1);
+            obj += &format!("f {a}//{a} {b}//{b} {c}//{c}\n");
+        }
+        fs::write(path, obj)
+    }
+
+    /// Sets the normal of each vertex to the area weighted average of the triangles around it.
+    pub fn compute_normals(&mut self) {
+        self.normals = vec![[0.0; 3]; self.vertices.len()];
+        for t in self.triangles.iter() {
+            let [a, b, c] = t.map(|i| self.vertices[i as usize]);
+            // * not normalized, so bigger triangles count for more
+            let n = cross(sub(b, a), sub(c, a));
+            for i in t {
+                let s = &mut self.normals[*i as usize];
+                *s = [s[0] + n[0], s[1] + n[1], s[2] + n[2]];
+            }
+        }
+        for n in self.normals.iter_mut() {
+            let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            if len > 0.0 {
+                *n = n.map(|v| v / len);
+            }
+        }
+    }
+}
+
+pub fn sub(a: Vertex, b: Vertex) -> Vertex {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn cross(a: Vertex, b: Vertex) -> Vertex {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+/// Builds the mesh of every surface in `ctx`.
+pub fn surfaces(ctx: &Context) -> Result<Vec<Mesh>, Vec<String>> {
+    let mut meshes = Vec::new();
+    let mut errs = Vec::new();
+    for name in ctx.surface_names() {
+        match surface(ctx, name) {
+            Ok(m) => meshes.push(m),
+            Err(e) => errs.push(e),
+        }
+    }
+
+    if errs.is_empty() { return Ok(meshes) }
+    Err(errs)
+}
+
+/// Simplifies a surface and builds its mesh. Only the real part of the height is used.
+pub fn surface(ctx: &Context, name: &str) -> Result<Mesh, String> {
+    let s = ctx.get_surface(name).unwrap();
+    let (x, y) = s.ranges(ctx)?;
+    let (e, funcs) = ctx.simplify_surface(name);
+    let [p, q] = s.params();
+    let mesh = Mesh::grid(&mut height(&e, p, q, &funcs), x, y, GRID_CELLS);
+    Ok(mesh)
+}
+
+/// `e` as a function of two variables.
+fn height<'a>(e: &'a Expr, p: &'a str, q: &'a str, funcs: &'a HashMap<String, Func>) -> impl FnMut(f64, f64) -> f64 + 'a {
+    let mut vars = HashMap::new();
+    move |a, b| {
+        vars.insert(p.to_string(), Complex64::from(a));
+        vars.insert(q.to_string(), Complex64::from(b));
+        e.evaluate(&vars, funcs).re
+    }
+}
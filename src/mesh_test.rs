@@ -0,0 +1,73 @@
+use crate::expr::{f::*, Context};
+use crate::mesh::{self, Mesh};
+use crate::test_util::var;
+
+
+fn close(a: [f64; 3], b: [f64; 3]) -> bool {
+    (0..3).all(|i| (a[i] - b[i]).abs() < 1e-9)
+}
+
+
+#[test]
+fn flat_grid_is_complete() {
+    let m = Mesh::grid(&mut |_, _| 1.0, 0.0..=1.0, 0.0..=1.0, 2);
+    assert_eq!(m.vertices.len(), 9);
+    assert_eq!(m.normals.len(), 9);
+    assert_eq!(m.triangles.len(), 8);
+    assert!(m.vertices.iter().all(|v| v[2] == 1.0));
+    assert!(m.normals.iter().all(|n| close(*n, [0.0, 0.0, 1.0])));
+}
+
+#[test]
+fn triangles_use_every_vertex_once_at_least() {
+    let m = Mesh::grid(&mut |x, y| x * y, -1.0..=1.0, -1.0..=1.0, 5);
+    let mut used = vec![false; m.vertices.len()];
+    for t in m.triangles.iter() {
+        for i in t {
+            used[*i as usize] = true;
+        }
+    }
+    assert!(used.iter().all(|u| *u));
+}
+
+#[test]
+fn normals_of_a_slope() {
+    let m = Mesh::grid(&mut |x, _| x, 0.0..=1.0, 0.0..=1.0, 3);
+    let s = 1.0 / 2f64.sqrt();
+    assert!(m.normals.iter().all(|n| close(*n, [-s, 0.0, s])), "{:?}", m.normals);
+}
+
+#[test]
+fn nan_cells_are_skipped() {
+    // Only the cells with x >= 0 are kept, which is half of them.
+    let m = Mesh::grid(&mut |x, _| x.sqrt(), -1.0..=1.0, 0.0..=1.0, 4);
+    assert_eq!(m.triangles.len(), 16);
+    assert!(m.vertices.iter().all(|v| v[0] >= 0.0 && v[2].is_finite()));
+}
+
+#[test]
+fn poles_are_skipped() {
+    // The middle vertex is infinite, so the 4 cells around it are left out along with it.
+    let m = Mesh::grid(&mut |x, y| 1.0 / (x * x + y * y), -1.0..=1.0, -1.0..=1.0, 2);
+    assert!(m.triangles.is_empty());
+    assert!(m.vertices.is_empty());
+
+    let m = Mesh::grid(&mut |x, y| 1.0 / (x * x + y * y), -1.0..=1.0, -1.0..=1.0, 4);
+    assert_eq!(m.triangles.len(), 2 * (16 - 4));
+    assert!(m.vertices.iter().all(|v| v[2].is_finite()));
+}
+
+#[test]
+fn surface_from_context() {
+    let mut ctx = Context::new();
+    // The parameter shadows this.
+    ctx.def_var("y", *num(100.0)).unwrap();
+    ctx.def_var("w", *num(2.0)).unwrap();
+    let body = add(pow(var("x"), num(2.0)), var("y"));
+    ctx.def_surface("s", ["x".into(), "y".into()], *body, (*neg(var("w")), *var("w")), (*num(0.0), *num(1.0))).unwrap();
+
+    let m = mesh::surface(&ctx, "s").unwrap();
+    assert_eq!(m.vertices.len(), (mesh::GRID_CELLS + 1).pow(2));
+    assert!(m.vertices.iter().all(|v| (v[2] - (v[0] * v[0] + v[1])).abs() < 1e-9));
+    assert!(m.vertices.iter().any(|v| v[0] == -2.0) && m.vertices.iter().any(|v| v[0] == 2.0));
+}
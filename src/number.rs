@@ -0,0 +1,283 @@
+/*
+This is where expressions are evaluated with different kinds of numbers.
+
+`evaluate_as` works with anything implementing `Number`: `f64` for real values only
+(anything that leaves the real line is NaN), `Complex64` for the usual evaluation,
+and `BigComplex` for as many bits as asked for, which is slow but doesn't round
+small differences away or overflow. Exact numbers and the constants `pi`, `e` and
+`i` are only rounded once, to the precision of the number type. The special functions
+(`gamma`, `erf`, ...) are the exception, and are only ever worked out with floats,
+along with `gcd`, `lcm`, `nCr` and `nPr`.
+*/
+
+use std::collections::HashMap;
+
+use num::{One, Zero};
+use num_complex::Complex64;
+
+use crate::bigfloat::{self, BigComplex, BigFloat};
+use crate::constant::Constant;
+use crate::expr::{add_complex, mul_complex, odd_root, pow_complex, Builtin, Expr, Func, Series, Term};
+use crate::integer;
+
+/// The precision of `f64` and `Complex64`, for `evaluate_as` with those.
+pub const F64_PRECISION: u32 = 53;
+/// The precision `--precise` uses if it isn't given one.
+pub const DEFAULT_PRECISION: u32 = 256;
+
+/// Something expressions can be evaluated with.
+pub trait Number: Clone {
+    /// `t` as a number, worked out to `precision` bits where that means anything.
+    fn constant(t: &Term, precision: u32) -> Self;
+    fn add(&self, other: &Self) -> Self;
+    fn mul(&self, other: &Self) -> Self;
+    /// `self^other`, keeping real results real where possible like `pow_complex`.
+    fn pow(&self, other: &Self) -> Self;
+    fn builtin(b: Builtin, args: &[Self]) -> Self;
+    /// The real part as an `f64`, for the bounds of series.
+    fn re(&self) -> f64;
+}
+
+impl Number for f64 {
+    fn constant(t: &Term, _: u32) -> Self {
+        let c = t.as_complex();
+        if c.im == 0.0 { c.re } else { f64::NAN }
+    }
+    fn add(&self, other: &Self) -> Self {
+        self + other
+    }
+    fn mul(&self, other: &Self) -> Self {
+        self * other
+    }
+    fn pow(&self, other: &Self) -> Self {
+        if (other.fract() == 0.0) & (other.abs() <= i32::MAX as f64) { return self.powi(*other as i32) }
+        // * negative bases give NaN here, which is what leaving the real line means
+        self.powf(*other)
+    }
+    fn builtin(b: Builtin, a: &[Self]) -> Self {
+        match b {
+            Builtin::Ln => a[0].ln(),
+            Builtin::Log => a[1].ln() / a[0].ln(),
+            Builtin::Root => odd_root(a[0], a[1]).unwrap_or_else(|| a[1].powf(a[0].recip())),
+            Builtin::Sqrt => a[0].sqrt(),
+            Builtin::Cbrt => a[0].cbrt(),
+
+            Builtin::Sin => a[0].sin(),
+            Builtin::Cos => a[0].cos(),
+            Builtin::Tan => a[0].tan(),
+            Builtin::Sinh => a[0].sinh(),
+            Builtin::Cosh => a[0].cosh(),
+            Builtin::Tanh => a[0].tanh(),
+            Builtin::Asin => a[0].asin(),
+            Builtin::Acos => a[0].acos(),
+            Builtin::Atan => a[0].atan(),
+            Builtin::Asinh => a[0].asinh(),
+            Builtin::Acosh => a[0].acosh(),
+            Builtin::Atanh => a[0].atanh(),
+
+            Builtin::Abs => a[0].abs(),
+            Builtin::Re | Builtin::Conj => a[0],
+            Builtin::Im => 0.0,
+            Builtin::Arg => if a[0] < 0.0 { std::f64::consts::PI } else { 0.0 },
+            Builtin::Polar => {
+                // * only real when the angle is a multiple of pi
+                let (s, c) = a[1].sin_cos();
+                if s == 0.0 { a[0] * c } else { f64::NAN }
+            },
+
+            Builtin::Max => a.iter().copied().reduce(|a, b| if b > a { b } else { a }).unwrap(),
+            Builtin::Min => a.iter().copied().reduce(|a, b| if b < a { b } else { a }).unwrap(),
+
+            Builtin::Gamma | Builtin::Lgamma | Builtin::Digamma | Builtin::Factorial | Builtin::Beta
+            | Builtin::Erf | Builtin::Erfc | Builtin::BesselJ | Builtin::BesselY | Builtin::Zeta => {
+                let c = b.apply_complex(&a.iter().map(|a| Complex64::from(*a)).collect::<Vec<Complex64>>());
+                if c.im == 0.0 { c.re } else { f64::NAN }
+            },
+
+            Builtin::Floor => a[0].floor(),
+            Builtin::Ceil => a[0].ceil(),
+            Builtin::Round => a[0].round(),
+            Builtin::Sign => integer::sign(a[0]),
+            Builtin::Mod => integer::modulo(a[0], a[1]),
+            Builtin::Gcd => a.iter().copied().fold(0.0, integer::gcd),
+            Builtin::Lcm => a.iter().copied().fold(1.0, integer::lcm),
+            Builtin::NCr => integer::choose(a[0], a[1]),
+            Builtin::NPr => integer::permute(a[0], a[1]),
+
+            Builtin::Dot | Builtin::Cross | Builtin::Det | Builtin::Inv | Builtin::Transpose => {
+                panic!("ERROR: {b:?} has to be resolved before evaluating!")
+            },
+        }
+    }
+    fn re(&self) -> f64 {
+        *self
+    }
+}
+
+impl Number for Complex64 {
+    fn constant(t: &Term, _: u32) -> Self {
+        t.as_complex()
+    }
+    fn add(&self, other: &Self) -> Self {
+        add_complex(*self, *other)
+    }
+    fn mul(&self, other: &Self) -> Self {
+        mul_complex(*self, *other)
+    }
+    fn pow(&self, other: &Self) -> Self {
+        pow_complex(*self, *other)
+    }
+    fn builtin(b: Builtin, args: &[Self]) -> Self {
+        b.apply_complex(args)
+    }
+    fn re(&self) -> f64 {
+        self.re
+    }
+}
+
+impl Number for BigComplex {
+    fn constant(t: &Term, precision: u32) -> Self {
+        match t {
+            Term::Rational(r) => Self::real(BigFloat::from_ratio(r, precision)),
+            Term::Const(Constant::Pi) => Self::real(bigfloat::pi(precision)),
+            Term::Const(Constant::E) => Self::real(BigFloat::one(precision).exp()),
+            Term::Const(Constant::I) => Self::new(BigFloat::zero(precision), BigFloat::one(precision)),
+            t => Self::from_complex(t.as_complex(), precision),
+        }
+    }
+    fn add(&self, other: &Self) -> Self {
+        BigComplex::add(self, other)
+    }
+    fn mul(&self, other: &Self) -> Self {
+        BigComplex::mul(self, other)
+    }
+    fn pow(&self, other: &Self) -> Self {
+        BigComplex::pow(self, other)
+    }
+    fn builtin(b: Builtin, a: &[Self]) -> Self {
+        match b {
+            Builtin::Ln => a[0].ln(),
+            Builtin::Log => a[1].ln().div(&a[0].ln()),
+            Builtin::Root => {
+                let p = a[0].precision();
+                let inv = Self::real(BigFloat::one(p)).div(&a[0]);
+                // Odd roots of negative numbers are real, see `odd_root`.
+                let odd = a[0].is_real() & a[1].is_real() && odd_root(a[0].re.to_f64(), -1.0).is_some();
+                if odd & a[1].re.is_negative() { return a[1].neg().pow(&inv).neg() }
+                a[1].pow(&inv)
+            },
+            Builtin::Sqrt => a[0].sqrt(),
+            Builtin::Cbrt => a[0].cbrt(),
+
+            Builtin::Sin => a[0].sin(),
+            Builtin::Cos => a[0].cos(),
+            Builtin::Tan => a[0].tan(),
+            Builtin::Sinh => a[0].sinh(),
+            Builtin::Cosh => a[0].cosh(),
+            Builtin::Tanh => a[0].tanh(),
+            Builtin::Asin => a[0].asin(),
+            Builtin::Acos => a[0].acos(),
+            Builtin::Atan => a[0].atan(),
+            Builtin::Asinh => a[0].asinh(),
+            Builtin::Acosh => a[0].acosh(),
+            Builtin::Atanh => a[0].atanh(),
+
+            Builtin::Abs => Self::real(a[0].norm()),
+            Builtin::Re => Self::real(a[0].re.clone()),
+            Builtin::Im => Self::real(a[0].im.clone()),
+            Builtin::Arg => Self::real(a[0].arg()),
+            Builtin::Conj => a[0].conj(),
+            Builtin::Polar => {
+                let i = Self::new(BigFloat::zero(a[1].precision()), BigFloat::one(a[1].precision()));
+                a[0].mul(&i.mul(&a[1]).exp())
+            },
+
+            Builtin::Max => a.iter().cloned().reduce(|a, b| if a.re.less(&b.re) { b } else { a }).unwrap(),
+            Builtin::Min => a.iter().cloned().reduce(|a, b| if b.re.less(&a.re) { b } else { a }).unwrap(),
+
+            // * these are only as precise as floats
+            Builtin::Gamma | Builtin::Lgamma | Builtin::Digamma | Builtin::Factorial | Builtin::Beta
+            | Builtin::Erf | Builtin::Erfc | Builtin::BesselJ | Builtin::BesselY | Builtin::Zeta => {
+                let c = b.apply_complex(&a.iter().map(|a| a.to_complex()).collect::<Vec<Complex64>>());
+                Self::from_complex(c, a[0].precision())
+            },
+
+            b if b.is_real_only() & a.iter().any(|a| !a.is_real()) => Self::from_complex(f64::NAN.into(), a[0].precision()),
+            Builtin::Floor => Self::real(a[0].re.floor()),
+            Builtin::Ceil => Self::real(a[0].re.neg().floor().neg()),
+            Builtin::Round => Self::real(a[0].re.nearest()),
+            Builtin::Sign => {
+                let p = a[0].precision();
+                if !a[0].re.is_finite() { return Self::from_complex(integer::sign(a[0].re.to_f64()).into(), p) }
+                if a[0].re.is_zero() { return Self::real(BigFloat::zero(p)) }
+                Self::real(BigFloat::from_int(if a[0].re.is_negative() { -1 } else { 1 }, p))
+            },
+            Builtin::Mod => {
+                // a - b floor(a/b)
+                if a[1].re.is_zero() { return Self::from_complex(f64::NAN.into(), a[0].precision()) }
+                let (x, y) = (&a[0].re, &a[1].re);
+                Self::real(x.sub(&y.mul(&x.div(y).floor())))
+            },
+            // * these are only as precise as floats, but they're exact for whole numbers that fit
+            Builtin::Gcd | Builtin::Lcm | Builtin::NCr | Builtin::NPr => {
+                let c = b.apply_complex(&a.iter().map(|a| a.to_complex()).collect::<Vec<Complex64>>());
+                Self::from_complex(c, a[0].precision())
+            },
+
+            Builtin::Dot | Builtin::Cross | Builtin::Det | Builtin::Inv | Builtin::Transpose => {
+                panic!("ERROR: {b:?} has to be resolved before evaluating!")
+            },
+        }
+    }
+    fn re(&self) -> f64 {
+        self.re.to_f64()
+    }
+}
+
+impl Expr {
+    /// Evaluates `self` with numbers of type `N`, like `evaluate`. Constants are worked out to
+    /// `precision` bits, which only matters for `BigComplex`.
+    pub fn evaluate_as<N: Number>(&self, vars: &HashMap<String, N>, funcs: &HashMap<String, Func>, precision: u32) -> N {
+        let eval = |a: &Expr| a.evaluate_as(vars, funcs, precision);
+        match self {
+            Self::Term(Term::Var(v)) => vars.get(v).unwrap_or_else(|| panic!("ERROR: variable {v} has no value!")).clone(),
+            Self::Term(t) => N::constant(t, precision),
+            Self::Add(n) => n.iter().map(&eval).reduce(|a, b| a.add(&b)).unwrap_or_else(|| N::constant(&Term::zero(), precision)),
+            Self::Mul(n) => n.iter().map(&eval).reduce(|a, b| a.mul(&b)).unwrap_or_else(|| N::constant(&Term::one(), precision)),
+            Self::Pow(a, b) => eval(a).pow(&eval(b)),
+            Self::Fn(name, args) => {
+                let f = funcs.get(name).unwrap_or_else(|| panic!("ERROR: function {name} is not defined!"));
+                let mut inner = vars.clone();
+                for (a, v) in f.args().iter().zip(args) {
+                    inner.insert(a.clone(), eval(v));
+                }
+                f.body().evaluate_as(&inner, funcs, precision)
+            },
+            Self::Builtin(b, n) => N::builtin(*b, &n.iter().map(|a| eval(a)).collect::<Vec<N>>()),
+            Self::Series(s, k, a, b, n) => {
+                // The bounds are only evaluated once, even when they depend on `x`.
+                let (from, to) = Series::bounds(eval(a).re(), eval(b).re());
+                let mut inner = vars.clone();
+                let mut acc = N::constant(&s.identity(), precision);
+                for i in from..=to {
+                    inner.insert(k.clone(), N::constant(&Term::number(i as f64), precision));
+                    let v = n.evaluate_as(&inner, funcs, precision);
+                    acc = match s {
+                        Series::Sum => acc.add(&v),
+                        Series::Prod => acc.mul(&v),
+                    };
+                }
+                acc
+            },
+            Self::List(_) | Self::Range(_, _) | Self::Reduce(_, _) => panic!("ERROR: lists have to be broadcast before evaluating!"),
+            Self::Vector(_) | Self::Matrix(_) => panic!("ERROR: vectors and matrices have to be evaluated one component at a time!"),
+        }
+    }
+
+    /// Evaluates `self` with `BigComplex` numbers of `precision` bits, rounding the result back
+    /// to a `Complex64`.
+    pub fn evaluate_precise(&self, vars: &HashMap<String, Complex64>, funcs: &HashMap<String, Func>, precision: u32) -> Complex64 {
+        let vars = vars.iter().map(|(k, v)| (k.clone(), BigComplex::from_complex(*v, precision))).collect();
+        self.evaluate_as::<BigComplex>(&vars, funcs, precision).to_complex()
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use num::BigRational;
+use num_complex::Complex64;
+
+use crate::bigfloat::{self, BigComplex, BigFloat};
+use crate::expr::{f::*, Builtin, Expr};
+use crate::number::F64_PRECISION;
+use crate::test_util::var;
+
+
+fn at<N: crate::number::Number>(e: &Expr, x: N, precision: u32) -> N {
+    e.evaluate_as(&HashMap::from([("x".to_string(), x)]), &HashMap::new(), precision)
+}
+
+fn precise(e: &Expr, x: Complex64) -> Complex64 {
+    e.evaluate_precise(&HashMap::from([("x".to_string(), x)]), &HashMap::new(), 256)
+}
+
+
+#[test]
+fn backends_agree() {
+    let x = var("x");
+    let es = vec![
+        add(mul(num(3.0), pow(x.clone(), num(4.0))), div(num(1.0), x.clone())),
+        mul(sin(x.clone()), pow(num(std::f64::consts::E), x.clone())),
+        div(ln(x.clone()), add(x.clone(), num(2.0))),
+        pow(x.clone(), x.clone()),
+        pow(x.clone(), num(0.5)),
+        log(num(2.0), x.clone()),
+        root(num(3.0), x.clone()),
+        cbrt(sub(x.clone(), num(5.0))),
+        sqrt(sub(x.clone(), num(5.0))),
+        ln(sub(x.clone(), num(5.0))),
+        tan(x.clone()),
+        cos(mul(x.clone(), num(100.0))),
+        sinh(x.clone()),
+        cosh(x.clone()),
+        tanh(x.clone()),
+        asin(div(x.clone(), num(2.0))),
+        acos(div(x.clone(), num(2.0))),
+        asin(mul(x.clone(), num(2.0))),
+        atan(x.clone()),
+        asinh(sub(x.clone(), num(3.0))),
+        acosh(add(x.clone(), num(2.0))),
+        acosh(div(x.clone(), num(4.0))),
+        atanh(div(x.clone(), num(2.0))),
+        abs(sub(x.clone(), num(5.0))),
+        arg(sub(x.clone(), num(5.0))),
+        polar(x.clone(), x.clone()),
+        builtin(Builtin::Max, vec![x.clone(), num(1.0)]),
+        sum("k".to_string(), num(1.0), num(20.0), div(pow(x.clone(), var("k")), var("k"))),
+    ];
+    for e in es {
+        for x in [0.3, 0.7, 1.1, 2.5] {
+            let fast = at(&e, Complex64::from(x), F64_PRECISION);
+            let slow = precise(&e, Complex64::from(x));
+            assert!((fast - slow).norm() <= 1e-12 * (1.0 + slow.norm()), "{e:?} at {x}: {fast} but {slow}");
+            // Real evaluation agrees wherever the result is real, and is NaN where it isn't.
+            let real = at(&e, x, F64_PRECISION);
+            if fast.im == 0.0 {
+                assert!((real - fast.re).abs() <= 1e-12 * (1.0 + fast.re.abs()), "{e:?} at {x}: {real} but {fast}");
+            } else if fast.im.abs() > 1e-12 {
+                assert!(real.is_nan(), "{e:?} at {x}: {real} but {fast}");
+            }
+        }
+    }
+}
+
+#[test]
+fn precise_where_floats_arent() {
+    let x = var("x");
+    // The small part is rounded away with floats.
+    let e = sub(add(x.clone(), num(1e-20)), x.clone());
+    assert_eq!(at(&e, 1.0, F64_PRECISION), 0.0);
+    assert!((precise(&e, Complex64::from(1.0)).re / 1e-20 - 1.0).abs() < 1e-15);
+    // So are the first terms of a series with lots of cancelling.
+    let e = sub(cos(x.clone()), num(1.0));
+    assert!((precise(&e, Complex64::from(1e-10)).re / -5e-21 - 1.0).abs() < 1e-9);
+    // Floats overflow.
+    let e = div(pow(num(10.0), mul(x.clone(), num(400.0))), pow(num(10.0), mul(x.clone(), num(399.0))));
+    assert!(at(&e, Complex64::from(1.0), F64_PRECISION).is_nan());
+    assert!((precise(&e, Complex64::from(1.0)).re - 10.0).abs() < 1e-12);
+}
+
+#[test]
+fn functions_to_many_digits() {
+    let p = 256;
+    // Going there and back again loses no more than a few bits.
+    for v in [0.1, 0.5, 1.0, 3.0, 123.456, 1e-30, 1e30] {
+        let x = BigFloat::from_f64(v, p);
+        let back = x.ln().exp();
+        assert!(back.sub(&x).div(&x).abs().to_f64() < 1e-70, "exp(ln({v}))");
+        let (s, c) = x.sin_cos();
+        assert!(s.mul(&s).add(&c.mul(&c)).sub(&BigFloat::one(p)).abs().to_f64() < 1e-70, "sin^2 + cos^2 at {v}");
+        let z = BigComplex::real(x.clone());
+        assert!(z.sqrt().mul(&z.sqrt()).sub(&z).re.div(&x).abs().to_f64() < 1e-70, "sqrt({v})^2");
+    }
+    for v in [0.1, 0.5, 1.5] {
+        let x = BigFloat::from_f64(v, p);
+        let (s, c) = x.sin_cos();
+        assert!(s.div(&c).atan().sub(&x).abs().to_f64() < 1e-70, "atan(tan({v}))");
+    }
+    // pi and ln(2), to more digits than an f64 has.
+    let third = BigFloat::from_ratio(&BigRational::new(1.into(), 3.into()), p);
+    assert!((bigfloat::pi(p).mul(&third).to_f64() - std::f64::consts::FRAC_PI_3).abs() < 1e-16);
+    assert_eq!(bigfloat::ln2(p).to_f64(), std::f64::consts::LN_2);
+    // sin(pi) isn't 0, since pi isn't either.
+    assert_eq!(BigFloat::from_f64(std::f64::consts::PI, p).sin_cos().0.to_f64(), 1.2246467991473532e-16);
+}
@@ -1,39 +1,50 @@
 
 /*
 This is where the data for the parsel parser is.
+
+Comments (`//` and `/* */`) are dropped by the tokenizer before any of this
+runs, so they don't need to be part of the grammar. Doc comments (`///`, `//!`)
+are turned into attributes by the tokenizer and are not allowed.
 */
 
 
 use kw::recursive;
+use num::{BigInt, BigRational};
 use parsel::{
-    self, ast::{Brace, LeftAssoc, LitFloat, LitInt, Many, Maybe, Paren, Punctuated}, parse_str, syn::{token::{Caret, Comma, Eq, Fn, Let, Minus, Plus, Semi, Slash, Star}, Ident, Token}, Parse, ToTokens
+    self, ast::{Brace, Bracket, LeftAssoc, Many, Maybe, Paren, Punctuated, RightAssoc}, parse_str, syn::{self, parse::ParseStream, token::{self, As, Caret, Comma, Eq, Fn, Let, Minus, Plus, Semi, Slash, Star}, Ident, Lit, LitStr, Token}, Parse, ToTokens, TokenStream, TokenTree
 };
 
 // Custom keywords
 mod kw {
     use parsel::custom_keyword;
 
-    custom_keyword!(sin);
-    custom_keyword!(cos);
-    custom_keyword!(tan);
-    custom_keyword!(sinh);
-    custom_keyword!(cosh);
-    custom_keyword!(tanh);
-    custom_keyword!(asin);
-    custom_keyword!(acos);
-    custom_keyword!(atan);
-    custom_keyword!(asinh);
-    custom_keyword!(acosh);
-    custom_keyword!(atanh);
-
-    custom_keyword!(ln);
-    custom_keyword!(log);
-
-    custom_keyword!(root);
-    custom_keyword!(sqrt);
-    custom_keyword!(cbrt);
+    /// Defines a keyword for each builtin function, and lists their names in `BUILTINS`.
+    macro_rules! builtins {
+        ($($name:ident)*) => {
+            $(custom_keyword!($name);)*
+            pub const BUILTINS: &[&str] = &[$(stringify!($name)),*];
+        };
+    }
+
+    builtins! {
+        sin cos tan sinh cosh tanh asin acos atan asinh acosh atanh
+        ln log
+        root sqrt cbrt
+        abs re im arg conj polar
+        gamma lgamma digamma factorial beta erf erfc besselj bessely zeta
+        floor ceil round sign gcd lcm nCr nPr
+        sum prod
+        total mean max min len
+        vec mat dot cross det inv transpose
+    }
 
     custom_keyword!(recursive);
+    custom_keyword!(include);
+    custom_keyword!(curve);
+    custom_keyword!(surface);
+    custom_keyword!(implicit);
+    custom_keyword!(table);
+    custom_keyword!(fit);
 
 }
 
@@ -41,6 +52,22 @@ pub fn str_parse(s: &str) -> Vec<Statement> {
     parse_str::<Many<Statement>>(s).unwrap().into_iter().collect()
 }
 
+/// Like `str_parse`, but a syntax error is returned with the line and column it's on.
+pub fn try_parse(s: &str) -> Result<Vec<Statement>, String> {
+    match parse_str::<Many<Statement>>(s) {
+        Ok(statements) => Ok(statements.into_iter().collect()),
+        Err(e) => {
+            let at = e.span().start();
+            Err(format!("{e} at line {}, column {}", at.line, at.column + 1))
+        },
+    }
+}
+
+/// Parses a single expression.
+pub fn expr_parse(s: &str) -> Expr {
+    parse_str::<Expr>(s).unwrap()
+}
+
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens)]
 /// A standalone statement in some context description
 pub enum Statement {
@@ -60,6 +87,113 @@ pub enum Statement {
         body: Expr,
         kw_semi: Semi,
     },
+    /// `include "path" as name;`, the included definitions are put under `name::`.
+    // * must come before `Include` so the alias isn't left unparsed.
+    IncludeAs {
+        kw_include: kw::include,
+        path: LitStr,
+        kw_as: As,
+        name: Ident,
+        kw_semi: Semi,
+    },
+    /// `include "path";`, the included definitions are put in the including namespace.
+    Include {
+        kw_include: kw::include,
+        path: LitStr,
+        kw_semi: Semi,
+    },
+    /// `curve c(t) = (x, y, z) for t in from..to;`, a 2D or 3D parametric curve.
+    Curve {
+        kw_curve: kw::curve,
+        name: Ident,
+        param: Paren<Ident>,
+        kw_eq: Eq,
+        body: Paren<Punctuated<Expr, Comma>>,
+        kw_for: Token![for],
+        var: Ident,
+        kw_in: Token![in],
+        range: Range,
+        kw_semi: Semi,
+    },
+    /// `polar r(θ) = f(θ) for θ in from..to;`, a curve in polar coordinates.
+    Polar {
+        kw_polar: kw::polar,
+        name: Ident,
+        param: Paren<Ident>,
+        kw_eq: Eq,
+        body: Expr,
+        kw_for: Token![for],
+        var: Ident,
+        kw_in: Token![in],
+        range: Range,
+        kw_semi: Semi,
+    },
+    /// `surface s(x, y) = z for x in a..b, y in c..d;`, a surface over a rectangle.
+    Surface {
+        kw_surface: kw::surface,
+        name: Ident,
+        params: Paren<Punctuated<Ident, Comma>>,
+        kw_eq: Eq,
+        body: Expr,
+        kw_for: Token![for],
+        x: Ident,
+        kw_in_x: Token![in],
+        x_range: Range,
+        comma: Comma,
+        y: Ident,
+        kw_in_y: Token![in],
+        y_range: Range,
+        kw_semi: Semi,
+    },
+    /// `implicit c(x, y): lhs = rhs for x in a..b, y in c..d;`, the points where both sides are equal.
+    Implicit {
+        kw_implicit: kw::implicit,
+        name: Ident,
+        params: Paren<Punctuated<Ident, Comma>>,
+        colon: Token![:],
+        lhs: Expr,
+        kw_eq: Eq,
+        rhs: Expr,
+        kw_for: Token![for],
+        bounds: Bounds,
+        kw_semi: Semi,
+    },
+    /// `table t(x1, y1) = "data.csv";` or `table t(x1, y1) = ([...], [...]);`, columns of data.
+    Table {
+        kw_table: kw::table,
+        name: Ident,
+        columns: Paren<Punctuated<Ident, Comma>>,
+        kw_eq: Eq,
+        source: TableSource,
+        kw_semi: Semi,
+    },
+    /// `fit line(m, b): y1 ~ m x1 + b;`, a regression fitting `m` and `b` to the data.
+    Fit {
+        kw_fit: kw::fit,
+        name: Ident,
+        params: Paren<Punctuated<Ident, Comma>>,
+        colon: Token![:],
+        lhs: Expr,
+        tilde: Token![~],
+        rhs: Expr,
+        kw_semi: Semi,
+    },
+    /// `mod name { ... }`, the statements inside are put under `name::`.
+    Mod {
+        kw_mod: Token![mod],
+        name: Ident,
+        #[parsel(recursive)]
+        body: Brace<Many<Statement>>,
+    },
+}
+
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens)]
+/// Where the columns of a table come from.
+pub enum TableSource {
+    /// A CSV file, found relative to the file it's in.
+    File(LitStr),
+    /// A list for each column.
+    Columns(Paren<Punctuated<Expr, Comma>>),
 }
 
 
@@ -70,7 +204,7 @@ pub enum Node {
     Ln(kw::ln, #[parsel(recursive)] Paren<Box<Expr>>),
     Log(kw::log, #[parsel(recursive)] Brace<Box<Expr>>, #[parsel(recursive)] Paren<Box<Expr>>),
     
-    Root(kw::log, #[parsel(recursive)] Brace<Box<Expr>>, #[parsel(recursive)] Paren<Box<Expr>>),
+    Root(kw::root, #[parsel(recursive)] Brace<Box<Expr>>, #[parsel(recursive)] Paren<Box<Expr>>),
     Sqrt(kw::sqrt, #[parsel(recursive)] Paren<Box<Expr>>),
     Cbrt(kw::cbrt, #[parsel(recursive)] Paren<Box<Expr>>),
     
@@ -89,19 +223,333 @@ pub enum Node {
 
     
     Abs(Token![|], #[parsel(recursive)] Box<Expr>, Token![|]),
+    AbsFn(kw::abs, #[parsel(recursive)] Paren<Box<Expr>>),
+    Re(kw::re, #[parsel(recursive)] Paren<Box<Expr>>),
+    Im(kw::im, #[parsel(recursive)] Paren<Box<Expr>>),
+    Arg(kw::arg, #[parsel(recursive)] Paren<Box<Expr>>),
+    Conj(kw::conj, #[parsel(recursive)] Paren<Box<Expr>>),
+    Polar(kw::polar, #[parsel(recursive)] Paren<Pair>),
+
+    Gamma(kw::gamma, #[parsel(recursive)] Paren<Box<Expr>>),
+    Lgamma(kw::lgamma, #[parsel(recursive)] Paren<Box<Expr>>),
+    Digamma(kw::digamma, #[parsel(recursive)] Paren<Box<Expr>>),
+    Factorial(kw::factorial, #[parsel(recursive)] Paren<Box<Expr>>),
+    Beta(kw::beta, #[parsel(recursive)] Paren<Pair>),
+    Erf(kw::erf, #[parsel(recursive)] Paren<Box<Expr>>),
+    Erfc(kw::erfc, #[parsel(recursive)] Paren<Box<Expr>>),
+    /// `besselj{n}(x)`
+    BesselJ(kw::besselj, #[parsel(recursive)] Brace<Box<Expr>>, #[parsel(recursive)] Paren<Box<Expr>>),
+    /// `bessely{n}(x)`
+    BesselY(kw::bessely, #[parsel(recursive)] Brace<Box<Expr>>, #[parsel(recursive)] Paren<Box<Expr>>),
+    Zeta(kw::zeta, #[parsel(recursive)] Paren<Box<Expr>>),
+
+    Floor(kw::floor, #[parsel(recursive)] Paren<Box<Expr>>),
+    Ceil(kw::ceil, #[parsel(recursive)] Paren<Box<Expr>>),
+    Round(kw::round, #[parsel(recursive)] Paren<Box<Expr>>),
+    Sign(kw::sign, #[parsel(recursive)] Paren<Box<Expr>>),
+    /// `mod(a, b)`, `mod` is already a keyword for modules.
+    Mod(Token![mod], #[parsel(recursive)] Paren<Pair>),
+    /// `gcd(list)` or `gcd(a, b, ...)`
+    Gcd(kw::gcd, #[parsel(recursive)] Paren<Punctuated<Expr, Comma>>),
+    /// `lcm(list)` or `lcm(a, b, ...)`
+    Lcm(kw::lcm, #[parsel(recursive)] Paren<Punctuated<Expr, Comma>>),
+    NCr(kw::nCr, #[parsel(recursive)] Paren<Pair>),
+    NPr(kw::nPr, #[parsel(recursive)] Paren<Pair>),
+
+    Sum(kw::sum, #[parsel(recursive)] Paren<Series>),
+    Prod(kw::prod, #[parsel(recursive)] Paren<Series>),
+
+    Total(kw::total, #[parsel(recursive)] Paren<Box<Expr>>),
+    Mean(kw::mean, #[parsel(recursive)] Paren<Box<Expr>>),
+    Len(kw::len, #[parsel(recursive)] Paren<Box<Expr>>),
+    /// `max(list)` or `max(a, b, ...)`
+    Max(kw::max, #[parsel(recursive)] Paren<Punctuated<Expr, Comma>>),
+    /// `min(list)` or `min(a, b, ...)`
+    Min(kw::min, #[parsel(recursive)] Paren<Punctuated<Expr, Comma>>),
+
+    Vec(kw::vec, #[parsel(recursive)] Paren<Punctuated<Expr, Comma>>),
+    /// `mat[[a, b], [c, d]]`, a list of rows.
+    Mat(kw::mat, #[parsel(recursive)] Bracket<Punctuated<Bracket<Punctuated<Expr, Comma>>, Comma>>),
+    Dot(kw::dot, #[parsel(recursive)] Paren<Pair>),
+    Cross(kw::cross, #[parsel(recursive)] Paren<Pair>),
+    Det(kw::det, #[parsel(recursive)] Paren<Box<Expr>>),
+    Inv(kw::inv, #[parsel(recursive)] Paren<Box<Expr>>),
+    Transpose(kw::transpose, #[parsel(recursive)] Paren<Box<Expr>>),
+
+    // * must come before `List`, which would fail on the `..`
+    Range(#[parsel(recursive)] Bracket<Range>),
+    List(#[parsel(recursive)] Bracket<Punctuated<Expr, Comma>>),
     
-    Fn(Ident, #[parsel(recursive)] Paren<Punctuated<Expr, Comma>>),
+    Fn(Path, #[parsel(recursive)] Paren<Punctuated<Expr, Comma>>),
     Paren(#[parsel(recursive)] Paren<Box<Expr>>),
+
+    // * must come before `Term`, which would only take the number and its name
+    SuffixedCall(SuffixedCall),
     
     Term(Term),
-    Neg(Minus, #[parsel(recursive)] Box<Expr>),
+    /// Binds looser than `^` but tighter than `*`, so `-x^2` is `-(x^2)` and
+    /// `-x + 1` is `(-x) + 1`.
+    Neg(Minus, #[parsel(recursive)] Box<Factor>),
+}
+
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens)]
+/// Two comma separated expressions, for builtins with two inputs.
+pub struct Pair {
+    #[parsel(recursive)]
+    pub a: Box<Expr>,
+    pub comma: Comma,
+    #[parsel(recursive)]
+    pub b: Box<Expr>,
+}
+
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens)]
+/// The inputs of `sum` and `prod`: `(k, from, to, body)`.
+pub struct Series {
+    pub var: Ident,
+    pub comma_0: Comma,
+    #[parsel(recursive)]
+    pub from: Box<Expr>,
+    pub comma_1: Comma,
+    #[parsel(recursive)]
+    pub to: Box<Expr>,
+    pub comma_2: Comma,
+    #[parsel(recursive)]
+    pub body: Box<Expr>,
+}
+
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens)]
+/// The inside of a range list, `from..to`.
+pub struct Range {
+    #[parsel(recursive)]
+    pub from: Box<Expr>,
+    pub dots: Token![..],
+    #[parsel(recursive)]
+    pub to: Box<Expr>,
 }
 
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens)]
 pub enum Term {
-    Var(Ident),
-    Float(LitFloat),
-    Int(LitInt),
+    // * must come before `Number` so the suffix isn't dropped.
+    Suffixed(Suffixed),
+    Var(Path),
+    Number(Number),
+}
+
+/// The ranges of some variables, like `x in a..b, y in c..d`.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Bounds(pub Vec<(Ident, Range)>);
+impl Parse for Bounds {
+    fn parse(input: ParseStream) -> parsel::Result<Self> {
+        let mut b = Vec::new();
+        loop {
+            let var = input.parse::<Ident>()?;
+            input.parse::<Token![in]>()?;
+            b.push((var, input.parse()?));
+            if !input.peek(Token![,]) { break }
+            input.parse::<Token![,]>()?;
+        }
+        Ok(Self(b))
+    }
+}
+impl ToTokens for Bounds {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        for (i, (var, range)) in self.0.iter().enumerate() {
+            if i != 0 {
+                <Token![,]>::default().to_tokens(tokens);
+            }
+            var.to_tokens(tokens);
+            <Token![in]>::default().to_tokens(tokens);
+            range.to_tokens(tokens);
+        }
+    }
+}
+
+/// A possibly qualified name, like `x` or `geom::area`.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Path(pub Vec<Ident>);
+impl Parse for Path {
+    fn parse(input: ParseStream) -> parsel::Result<Self> {
+        let mut p = vec![input.parse::<Ident>()?];
+        while input.peek(Token![::]) {
+            input.parse::<Token![::]>()?;
+            p.push(input.parse()?);
+        }
+        Ok(Self(p))
+    }
+}
+impl ToTokens for Path {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        for (i, ident) in self.0.iter().enumerate() {
+            if i != 0 {
+                <Token![::]>::default().to_tokens(tokens);
+            }
+            ident.to_tokens(tokens);
+        }
+    }
+}
+impl std::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.iter().map(|i| i.to_string()).collect::<Vec<String>>().join("::"))
+    }
+}
+
+/// A number directly followed by a name, like `2x` or `1.5pi`.
+/// 
+/// The tokenizer reads these as a single literal with a suffix, so they are
+/// caught here and turned into a multiplication in `convert`. The suffix `i`
+/// is special, and makes an imaginary literal like `4i` or `1.5i`.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Suffixed(pub Lit);
+impl Suffixed {
+    /// The numeric part of the literal, exactly.
+    pub fn value(&self) -> BigRational {
+        decimal(digits(&self.0)).unwrap()
+    }
+    /// Checks if `self` is an imaginary literal.
+    pub fn is_imaginary(&self) -> bool {
+        self.suffix() == "i"
+    }
+    /// The name the number is multiplied by.
+    pub fn suffix(&self) -> &str {
+        match &self.0 {
+            Lit::Int(l) => l.suffix(),
+            Lit::Float(l) => l.suffix(),
+            _ => unreachable!(),
+        }
+    }
+}
+impl Parse for Suffixed {
+    fn parse(input: ParseStream) -> parsel::Result<Self> {
+        let lit: Lit = input.parse()?;
+        let s = match &lit {
+            Lit::Int(l) if !l.suffix().is_empty() && unsigned(l.base10_digits()) => exact(lit).map(Self)?,
+            Lit::Float(l) if !l.suffix().is_empty() && unsigned(l.base10_digits()) => exact(lit).map(Self)?,
+            _ => return Err(syn::Error::new(lit.span(), "expected a number followed by a name")),
+        };
+        // `2sin` on its own isn't a number times a variable.
+        if is_builtin(s.suffix()) && !(input.peek(token::Paren) || input.peek(token::Brace)) {
+            return Err(syn::Error::new(s.0.span(), format!("expected the inputs of {}", s.suffix())));
+        }
+        Ok(s)
+    }
+}
+impl ToTokens for Suffixed {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.0.to_tokens(tokens);
+    }
+}
+
+/// Checks if `name` is a builtin function, like `sin`.
+fn is_builtin(name: &str) -> bool {
+    // * `mod` is one of Rust's keywords, so it isn't in `kw`
+    kw::BUILTINS.contains(&name) || name == "mod"
+}
+
+/// A number directly followed by a call, like `2sin(x)`, `3log{2}(x)` or `2f(x)`.
+/// 
+/// The tokenizer reads `2sin` as a single literal, which would leave `2 * sin * (x)`.
+/// Instead the name is put back in front of its inputs and parsed as a call.
+#[derive(PartialEq, Eq, Debug)]
+pub struct SuffixedCall {
+    pub number: Suffixed,
+    pub call: Box<Node>,
+}
+impl Parse for SuffixedCall {
+    fn parse(input: ParseStream) -> parsel::Result<Self> {
+        let number: Suffixed = input.parse()?;
+        if number.is_imaginary() {
+            return Err(syn::Error::new(number.0.span(), "expected a number followed by a call"));
+        }
+        let mut call = Ident::new(number.suffix(), number.0.span()).into_token_stream();
+        // * `log{2}(x)`, `root{3}(x)` and `besselj{n}(x)` take one input in braces first
+        while input.peek(token::Brace) {
+            call.extend([input.parse::<TokenTree>()?]);
+        }
+        if !input.peek(token::Paren) {
+            return Err(input.error("expected the inputs of a call"));
+        }
+        call.extend([input.parse::<TokenTree>()?]);
+        Ok(Self { number, call: Box::new(parsel::parse2(call)?) })
+    }
+}
+impl ToTokens for SuffixedCall {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.number.to_tokens(tokens);
+        // * the name is already part of the number
+        tokens.extend(self.call.to_token_stream().into_iter().skip(1));
+    }
+}
+
+/// Checks that a literal wasn't read with a `-` in front, see `Number`.
+fn unsigned(digits: &str) -> bool {
+    !digits.starts_with('-')
+}
+
+/// The largest power of 10 a literal can be written with. Far past what a float can hold,
+/// but small enough to write out exactly.
+const MAX_EXPONENT: u64 = 1000;
+
+/// The exact value of the digits of a literal, like `1.5e-3`. `None` if the exponent is
+/// past `MAX_EXPONENT`.
+fn decimal(digits: &str) -> Option<BigRational> {
+    let (mantissa, exp) = match digits.split_once(['e', 'E']) {
+        Some((m, e)) => (m, e.parse::<i64>().ok()?),
+        None => (digits, 0),
+    };
+    let (whole, fract) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let exp = exp - fract.len() as i64;
+    if exp.unsigned_abs() > MAX_EXPONENT { return None }
+
+    let n: BigInt = format!("{whole}{fract}").parse().ok()?;
+    let scale = BigInt::from(10).pow(exp.unsigned_abs() as u32);
+    if exp < 0 { return Some(BigRational::new(n, scale)) }
+    Some(BigRational::from_integer(n * scale))
+}
+
+/// The digits of a number literal, without any `_`s or suffix.
+fn digits(lit: &Lit) -> &str {
+    match lit {
+        Lit::Int(l) => l.base10_digits(),
+        Lit::Float(l) => l.base10_digits(),
+        _ => unreachable!(),
+    }
+}
+
+/// Turns down literals `decimal` can't write out, instead of letting them become infinite.
+fn exact(lit: Lit) -> parsel::Result<Lit> {
+    if decimal(digits(&lit)).is_none() {
+        return Err(syn::Error::new(lit.span(), format!("the exponent of this number is past {MAX_EXPONENT}")));
+    }
+    Ok(lit)
+}
+
+/// A number without a suffix, like `2`, `1.5` or `1e-3`.
+/// 
+/// The digits are kept as they're written, so numbers of any size stay exact.
+/// The tokenizer reads a leading `-` as part of the number, so those are turned
+/// down and left to `Node::Neg`. Otherwise `-2^2` would be `(-2)^2`.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Number(pub Lit);
+impl Number {
+    /// The value of the number, exactly. `1.5` is `3/2`.
+    pub fn value(&self) -> BigRational {
+        decimal(digits(&self.0)).unwrap()
+    }
+}
+impl Parse for Number {
+    fn parse(input: ParseStream) -> parsel::Result<Self> {
+        let lit: Lit = input.parse()?;
+        match &lit {
+            Lit::Int(l) if l.suffix().is_empty() && unsigned(l.base10_digits()) => exact(lit).map(Self),
+            Lit::Float(l) if l.suffix().is_empty() && unsigned(l.base10_digits()) => exact(lit).map(Self),
+            _ => Err(syn::Error::new(lit.span(), "expected a number")),
+        }
+    }
+}
+impl ToTokens for Number {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.0.to_tokens(tokens);
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens)]
@@ -114,6 +562,28 @@ pub enum AddOp {
 pub enum MulOp {
     Mul(Star),
     Div(Slash),
+    Implicit(Juxtapose),
+}
+
+/// The invisible operator between two juxtaposed factors (`3(x+1)`, `(a)(b)`, `x y`, `2[1, 2]`).
+/// 
+/// It never consumes anything and only matches when the next token can start a
+/// factor, so it sits at the same precedence as `*` and `/`. A leading `-` or `|`
+/// is never treated as a factor; `2 -x` and `3 -2` are still subtraction.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Juxtapose;
+impl Parse for Juxtapose {
+    fn parse(input: ParseStream) -> parsel::Result<Self> {
+        // * `Lit` would match the `-2` in `3 - 2` too
+        if input.peek(Minus) { return Err(input.error("expected a factor")) }
+        if input.peek(Ident) || input.peek(Lit) || input.peek(token::Paren) || input.peek(token::Bracket) {
+            return Ok(Self);
+        }
+        Err(input.error("expected a factor"))
+    }
+}
+impl ToTokens for Juxtapose {
+    fn to_tokens(&self, _tokens: &mut TokenStream) {}
 }
 
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens)]
@@ -122,13 +592,17 @@ pub enum PowOp {
 }
 
 /// The expression type. Made to include left- and right-associated features.
-pub type Expr = LeftAssoc<
-    AddOp,
-    LeftAssoc<
-        MulOp,
-        LeftAssoc<
-            PowOp,
-            Node,
-        >
-    >
->;
\ No newline at end of file
+pub type Expr = LeftAssoc<AddOp, Product>;
+/// `*`, `/` and implicit multiplication, left-associative.
+pub type Product = LeftAssoc<MulOp, Factor>;
+/// `^`, right-associative so `2^3^2` is `2^(3^2)`.
+pub type Factor = RightAssoc<PowOp, Postfix>;
+
+/// A node with an optional `!` after it, for factorials. Binds tighter than `^`, so
+/// `2^n!` is `2^(n!)` and `n!^2` is `(n!)^2`.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens)]
+pub struct Postfix {
+    #[parsel(recursive)]
+    pub node: Node,
+    pub bang: Maybe<Token![!]>,
+}
\ No newline at end of file
@@ -0,0 +1,166 @@
+use crate::{convert, expr, parse};
+
+
+/// Parses and converts a single expression into an expression tree.
+fn tree(s: &str) -> expr::Expr {
+    *convert::convert_expr(parse::expr_parse(s))
+}
+
+/// Asserts that `a` parses into the same tree as the explicitly parenthesized `b`.
+fn same(a: &str, b: &str) {
+    assert_eq!(tree(a), tree(b), "`{a}` should parse like `{b}`");
+}
+
+/// Asserts that `a` and `b` parse into different trees.
+fn different(a: &str, b: &str) {
+    assert_ne!(tree(a), tree(b), "`{a}` should not parse like `{b}`");
+}
+
+
+#[test]
+fn pow_is_right_associative() {
+    same("2^3^2", "2^(3^2)");
+    same("a^b^c^d", "a^(b^(c^d))");
+    different("2^3^2", "(2^3)^2");
+}
+
+#[test]
+fn neg_binds_looser_than_pow() {
+    same("-x^2", "-(x^2)");
+    same("-2x^2", "-(2 * x^2)");
+    same("-1.5^2", "-(1.5^2)");
+    same("-2^2", "-(2^2)");
+    same("-x^y^z", "-(x^(y^z))");
+    different("-x^2", "(-x)^2");
+}
+
+#[test]
+fn neg_binds_tighter_than_add_and_mul() {
+    same("-x + 1", "(-x) + 1");
+    same("-a - b", "(-a) - b");
+    same("-a * b", "(-a) * b");
+    same("-a / b", "(-a) / b");
+    same("-x y", "(-x) y");
+    different("-x + 1", "-(x + 1)");
+}
+
+#[test]
+fn neg_in_exponent() {
+    same("2^-x", "2^(-x)");
+    same("a^-b^c", "a^(-(b^c))");
+    same("e^-x^2", "e^(-(x^2))");
+}
+
+#[test]
+fn add_and_mul_are_left_associative() {
+    same("a - b - c", "(a - b) - c");
+    same("a / b / c", "(a / b) / c");
+    same("a - b + c", "(a - b) + c");
+    same("a / b * c", "(a / b) * c");
+    different("a - b - c", "a - (b - c)");
+    different("a / b / c", "a / (b / c)");
+}
+
+#[test]
+fn mixed_precedence() {
+    same("a + b * c", "a + (b * c)");
+    same("a * b ^ c", "a * (b ^ c)");
+    same("a + b ^ c * d", "a + ((b ^ c) * d)");
+    same("a * b + c * d", "(a * b) + (c * d)");
+    same("|x|^2", "(|x|)^2");
+    same("sin(x)^2", "(sin(x))^2");
+}
+
+#[test]
+fn implicit_multiplication_precedence() {
+    same("a / b c", "(a / b) * c");
+    same("2x^2", "2 * (x^2)");
+    same("3(x+1)^2", "3 * ((x+1)^2)");
+    same("x y^2", "x * (y^2)");
+    same("2 -x", "2 - x");
+    same("3 - 2", "3 + -2");
+    same("x - 1", "x + -1");
+    same("(x) -2", "(x) + -2");
+    different("3 - 2", "3 * -2");
+}
+
+#[test]
+fn numbers_stay_exact() {
+    let value = |s: &str| tree(s).flatten().reduce_const();
+    let exact = |s: &str| expr::Expr::Term(expr::Term::Rational(s.parse().unwrap()));
+    assert_eq!(value("-2"), exact("-2"));
+    assert_eq!(value("-(-3)"), exact("3"));
+    // * past u128, which used to be the biggest literal
+    assert_eq!(value("1000000000000000000000000000000000000001"), exact("1000000000000000000000000000000000000001"));
+    // Decimals are exact too.
+    assert!(matches!(value("-2.5"), expr::Expr::Term(expr::Term::Rational(r)) if r == "-5/2".parse().unwrap()));
+    assert!(matches!(value("0.1 + 0.2"), expr::Expr::Term(expr::Term::Rational(r)) if r == "3/10".parse().unwrap()));
+    assert!(matches!(value("1e-3"), expr::Expr::Term(expr::Term::Rational(r)) if r == "1/1000".parse().unwrap()));
+    assert_eq!(value("1.5x/3"), expr::Expr::Mul(vec![exact("1/2"), tree("x")]));
+    assert_eq!(value("1.5 x/3"), expr::Expr::Mul(vec![exact("1/2"), tree("x")]));
+}
+
+#[test]
+fn suffixed_numbers_stay_exact() {
+    let times = |n: &str, t: expr::Term| expr::Expr::Mul(vec![expr::Expr::Term(expr::Term::Rational(n.parse().unwrap())), expr::Expr::Term(t)]);
+    let big = format!("1{}", "0".repeat(400));
+    // * both are past what a float can hold
+    assert_eq!(tree("1e400x"), times(&big, expr::Term::Var("x".to_string())));
+    assert_eq!(tree("1e400i"), times(&big, expr::Term::Const(crate::constant::Constant::I)));
+    assert_eq!(tree("123456789012345678901234567890x"), times("123456789012345678901234567890", expr::Term::Var("x".to_string())));
+    assert_eq!(tree("2.5e-3x"), times("1/400", expr::Term::Var("x".to_string())));
+    assert!(parse::try_parse("let a = 1e2000x;").is_err());
+}
+
+#[test]
+fn numbers_in_front_of_calls() {
+    same("2sin(x)", "2 * sin(x)");
+    same("2x sin(x)", "2 * x * sin(x)");
+    same("3log{2}(x)", "3 * log{2}(x)");
+    same("1.5max(a, b)", "1.5 * max(a, b)");
+    same("2f(x, y)", "2 * f(x, y)");
+    // Like `2x^2` and `2x!`.
+    same("2sin(x)^2", "2 * sin(x)^2");
+    same("2f(x)!", "2 * f(x)!");
+    same("2i(x)", "2i * (x)");
+    // A builtin can't be a variable.
+    assert!(parse::try_parse("let a = 2sin;").is_err());
+    assert!(parse::try_parse("let a = 2sin x;").is_err());
+}
+
+#[test]
+fn factorial_binds_tightest() {
+    same("n!", "factorial(n)");
+    same("2^n!", "2^(n!)");
+    same("n!^2", "(n!)^2");
+    same("-n!", "-(n!)");
+    same("2n!", "2 * n!");
+    same("(n + 1)! / n!", "factorial(n + 1) / factorial(n)");
+    different("2n!", "(2n)!");
+}
+
+#[test]
+fn special_functions() {
+    same("besselj{2}(x)^2", "(besselj{2}(x))^2");
+    same("gamma(x) beta(x, y)", "gamma(x) * beta(x, y)");
+    different("erf(x)", "erfc(x)");
+}
+
+#[test]
+fn whole_number_functions() {
+    same("mod(x, 2)^2", "(mod(x, 2))^2");
+    same("floor(x) nCr(n, k)", "floor(x) * nCr(n, k)");
+    same("gcd(a, b, c)", "gcd([a, b, c])");
+    different("nCr(n, k)", "nPr(n, k)");
+}
+
+#[test]
+fn tables_and_fits() {
+    let mut s = parse::str_parse("table t(x1, y1) = \"data.csv\"; table u(a) = ([1, 2]); fit line(m, b): y1 ~ m x1 + b;");
+    assert!(matches!(&s[0], parse::Statement::Table { source: parse::TableSource::File(_), .. }));
+    assert!(matches!(&s[1], parse::Statement::Table { source: parse::TableSource::Columns(_), .. }));
+    // The left side stops at `~`.
+    let parse::Statement::Fit { lhs, rhs, .. } = s.remove(2) else { panic!("not a fit") };
+    assert_eq!(*convert::convert_expr(lhs), tree("y1"));
+    assert_eq!(*convert::convert_expr(rhs), tree("m x1 + b"));
+}
@@ -0,0 +1,223 @@
+/*
+This is where polynomials are picked out of expressions and solved.
+
+`as_polynomial` reads the coefficients off a flattened tree of `Add`, `Mul` and
+`Pow` with whole powers. Polynomials up to degree 4 are solved with the usual
+formulas, and bigger ones with the Aberth method, which moves guesses for all of
+the roots at once until they settle. Either way the roots are polished with a few
+steps of Newton's method on the polynomial, since the formulas lose precision.
+*/
+
+use num::{One, Zero};
+use num_complex::Complex64;
+
+use crate::expr::{Expr, Term};
+
+/// The highest power `as_polynomial` writes out.
+pub const MAX_DEGREE: usize = 256;
+/// The most rounds of the Aberth method.
+const MAX_ROUNDS: usize = 500;
+/// How far (relative to their size) roots can move in a round before they count as settled.
+const SETTLED: f64 = 1e-15;
+/// Newton steps taken on every root at the end.
+const POLISH_STEPS: usize = 3;
+
+impl Expr {
+    /// The coefficients of `self` as a polynomial in `var`, lowest power first, or `None` if it
+    /// isn't one. Expects constants to have been reduced. Trailing zeros are left out, so `0` has
+    /// no coefficients.
+    pub fn as_polynomial(&self, var: &str) -> Option<Vec<Term>> {
+        let mut p = self.poly(var)?;
+        trim(&mut p);
+        Some(p)
+    }
+
+    fn poly(&self, var: &str) -> Option<Vec<Term>> {
+        match self {
+            Self::Term(Term::Var(v)) => (v == var).then(|| vec![Term::zero(), Term::one()]),
+            Self::Term(t) => Some(vec![t.clone()]),
+            Self::Add(n) => n.iter().try_fold(Vec::new(), |acc, a| Some(add(&acc, &a.poly(var)?))),
+            Self::Mul(n) => n.iter().try_fold(vec![Term::one()], |acc, a| Some(mul(&acc, &a.poly(var)?))),
+            Self::Pow(a, b) => {
+                let b = b.force_const_checked()?.as_complex();
+                if (b.im != 0.0) | (b.re < 0.0) | (b.re.fract() != 0.0) { return None }
+                let a = a.poly(var)?;
+                // * `a^n` has a degree of `n` times the degree of `a`
+                if (b.re as usize).saturating_mul(a.len().max(2) - 1) > MAX_DEGREE { return None }
+                Some((0..b.re as usize).fold(vec![Term::one()], |acc, _| mul(&acc, &a)))
+            },
+            _ => None,
+        }
+    }
+
+    /// The roots of `self` as a polynomial in `var`, repeated as many times as they're roots.
+    /// `None` if it isn't a polynomial, and no roots if it's `0`.
+    pub fn polynomial_roots(&self, var: &str) -> Option<Vec<Complex64>> {
+        let p = self.as_polynomial(var)?;
+        Some(solve(&p.iter().map(|c| c.as_complex()).collect::<Vec<Complex64>>()))
+    }
+
+    fn force_const_checked(&self) -> Option<Term> {
+        self.is_const().then(|| self.force_const())
+    }
+}
+
+fn add(a: &[Term], b: &[Term]) -> Vec<Term> {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).cloned().unwrap_or_else(Term::zero) + b.get(i).cloned().unwrap_or_else(Term::zero))
+        .collect()
+}
+
+fn mul(a: &[Term], b: &[Term]) -> Vec<Term> {
+    if a.is_empty() | b.is_empty() { return Vec::new() }
+    let mut p = vec![Term::zero(); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            p[i + j] = p[i + j].clone() + x.clone() * y.clone();
+        }
+    }
+    p
+}
+
+fn trim(p: &mut Vec<Term>) {
+    while p.last().is_some_and(|c| c.is_zero()) {
+        p.pop();
+    }
+}
+
+/// The roots of the polynomial with `coefficients`, lowest power first. Roots are repeated
+/// as many times as they're roots. Constant polynomials have none.
+pub fn solve(coefficients: &[Complex64]) -> Vec<Complex64> {
+    let mut p = coefficients.to_vec();
+    while p.last().is_some_and(|c| c.is_zero()) {
+        p.pop();
+    }
+    // Roots at 0 are taken out exactly.
+    let zeros = p.iter().take_while(|c| c.is_zero()).count();
+    let mut roots = vec![Complex64::zero(); zeros];
+    let p = &p[zeros..];
+    if p.len() < 2 { return roots }
+
+    // Make it monic, `x^n + ...`.
+    let lead = p[p.len() - 1];
+    let monic: Vec<Complex64> = p.iter().map(|c| c / lead).collect();
+    let found = match monic.len() - 1 {
+        1 => vec![-monic[0]],
+        2 => quadratic(monic[1], monic[0]).to_vec(),
+        3 => cubic(monic[2], monic[1], monic[0]).to_vec(),
+        4 => quartic(monic[3], monic[2], monic[1], monic[0]).to_vec(),
+        _ => aberth(&monic),
+    };
+    roots.extend(found.into_iter().map(|z| polish(&monic, z)));
+    roots
+}
+
+/// The roots of `x^2 + b x + c`.
+fn quadratic(b: Complex64, c: Complex64) -> [Complex64; 2] {
+    // * picking the sign that adds avoids cancelling, the other root comes from `x1 * x2 = c`
+    let d = (b * b - 4.0 * c).sqrt();
+    let q = if (b.conj() * d).re >= 0.0 { -(b + d) / 2.0 } else { -(b - d) / 2.0 };
+    if q.is_zero() { return [Complex64::zero(); 2] }
+    [q, c / q]
+}
+
+/// The roots of `x^3 + a x^2 + b x + c`, with Cardano's formula.
+fn cubic(a: Complex64, b: Complex64, c: Complex64) -> [Complex64; 3] {
+    // Substituting `x = t - a/3` gives `t^3 + p t + q`.
+    let shift = a / 3.0;
+    let p = b - a * shift;
+    let q = 2.0 * shift * shift * shift - shift * b + c;
+
+    let d = (q * q / 4.0 + p * p * p / 27.0).sqrt();
+    // * the larger of the two choices keeps `u` away from 0
+    let s = if (-q / 2.0 + d).norm() >= (-q / 2.0 - d).norm() { -q / 2.0 + d } else { -q / 2.0 - d };
+    let u = s.cbrt();
+    if u.is_zero() { return [-shift; 3] }
+
+    let w = Complex64::new(-0.5, 3f64.sqrt() / 2.0);
+    let mut roots = [Complex64::zero(); 3];
+    let mut uk = u;
+    for r in roots.iter_mut() {
+        *r = uk - p / (3.0 * uk) - shift;
+        uk *= w;
+    }
+    roots
+}
+
+/// The roots of `x^4 + a x^3 + b x^2 + c x + d`, with Ferrari's method.
+fn quartic(a: Complex64, b: Complex64, c: Complex64, d: Complex64) -> [Complex64; 4] {
+    // Substituting `x = y - a/4` gives `y^4 + p y^2 + q y + r`.
+    let shift = a / 4.0;
+    let p = b - 6.0 * shift * shift;
+    let q = c - 2.0 * b * shift + 8.0 * shift * shift * shift;
+    let r = d - c * shift + b * shift * shift - 3.0 * shift * shift * shift * shift;
+
+    let roots = if q.norm() <= 1e-14 * (1.0 + p.norm() + r.norm()) {
+        // `y^4 + p y^2 + r` is a quadratic in `y^2`.
+        let [s, t] = quadratic(p, r);
+        [s.sqrt(), -s.sqrt(), t.sqrt(), -t.sqrt()]
+    } else {
+        // Adding `m` in `(y^2 + p/2 + m)^2` leaves a perfect square when `m` is a root of
+        // `8 m^3 + 8 p m^2 + (2 p^2 - 8 r) m - q^2`.
+        let ms = cubic(p, p * p / 4.0 - r, -q * q / 8.0);
+        let m = ms.into_iter().fold(Complex64::zero(), |a, b| if b.norm() > a.norm() { b } else { a });
+        let s = (2.0 * m).sqrt();
+        let [y1, y2] = quadratic(s, p / 2.0 + m - q / (2.0 * s));
+        let [y3, y4] = quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+        [y1, y2, y3, y4]
+    };
+    roots.map(|y| y - shift)
+}
+
+/// The roots of the monic polynomial `p` with the Aberth method.
+fn aberth(p: &[Complex64]) -> Vec<Complex64> {
+    let n = p.len() - 1;
+    // Every root is within this of 0 (Cauchy's bound), so the guesses start on a circle inside it.
+    let bound = 1.0 + p[..n].iter().map(|c| c.norm()).fold(0.0, f64::max);
+    let radius = bound / 2.0;
+    // * the odd angle keeps the guesses off any symmetry the roots have
+    let mut z: Vec<Complex64> = (0..n).map(|k| Complex64::from_polar(radius, std::f64::consts::TAU * k as f64 / n as f64 + 0.4)).collect();
+
+    for _ in 0..MAX_ROUNDS {
+        let mut settled = true;
+        for k in 0..n {
+            let (v, dv) = evaluate(p, z[k]);
+            if v.is_zero() { continue }
+            let ratio = v / dv;
+            let repel: Complex64 = (0..n).filter(|j| *j != k).map(|j| (z[k] - z[j]).inv()).sum();
+            let step = ratio / (Complex64::from(1.0) - ratio * repel);
+            if !step.is_finite() { continue }
+            z[k] -= step;
+            if step.norm() > SETTLED * z[k].norm().max(1.0) {
+                settled = false;
+            }
+        }
+        if settled { break }
+    }
+    z
+}
+
+/// A few Newton steps towards a root of `p`, keeping whichever point is closest to a root.
+fn polish(p: &[Complex64], mut z: Complex64) -> Complex64 {
+    let mut best = evaluate(p, z).0.norm();
+    for _ in 0..POLISH_STEPS {
+        let (v, dv) = evaluate(p, z);
+        if dv.is_zero() { break }
+        let next = z - v / dv;
+        let err = evaluate(p, next).0.norm();
+        if err.is_nan() || err >= best { break }
+        (z, best) = (next, err);
+    }
+    z
+}
+
+/// `p(z)` and `p'(z)`, with Horner's method.
+fn evaluate(p: &[Complex64], z: Complex64) -> (Complex64, Complex64) {
+    let mut v = Complex64::zero();
+    let mut dv = Complex64::zero();
+    for c in p.iter().rev() {
+        dv = dv * z + v;
+        v = v * z + c;
+    }
+    (v, dv)
+}
@@ -0,0 +1,112 @@
+use num_complex::Complex64;
+
+use crate::expr::{f::*, Term};
+use crate::poly;
+use crate::test_util::var;
+
+
+fn c(re: f64, im: f64) -> Complex64 {
+    Complex64::new(re, im)
+}
+
+/// Asserts that `found` has the same roots as `expected`, in any order, to within `tolerance` relative to their size.
+fn same_roots(found: Vec<Complex64>, expected: &[Complex64], tolerance: f64) {
+    assert_eq!(found.len(), expected.len(), "{found:?} isn't {expected:?}");
+    let mut left = found.clone();
+    for e in expected {
+        let (i, d) = left.iter().enumerate().map(|(i, z)| (i, (z - e).norm())).fold((0, f64::INFINITY), |a, b| if b.1 < a.1 { b } else { a });
+        assert!(d < tolerance * (1.0 + e.norm()), "{found:?} doesn't have {e}");
+        left.remove(i);
+    }
+}
+
+/// The coefficients of the polynomial with `roots`, lowest power first.
+fn from_roots(roots: &[Complex64]) -> Vec<Complex64> {
+    roots.iter().fold(vec![c(1.0, 0.0)], |p, r| {
+        let mut next = vec![c(0.0, 0.0); p.len() + 1];
+        for (i, a) in p.iter().enumerate() {
+            next[i + 1] += a;
+            next[i] -= a * r;
+        }
+        next
+    })
+}
+
+
+#[test]
+fn polynomials_are_recognised() {
+    let x = var("x");
+    // (x + 1)^3 * (x - 2) = x^4 + x^3 - 3x^2 - 5x - 2
+    let e = mul(pow(add(x.clone(), num(1.0)), num(3.0)), sub(x.clone(), num(2.0))).flatten().reduce_const();
+    assert_eq!(e.as_polynomial("x").unwrap(), [-2.0, -5.0, -3.0, 1.0, 1.0].map(Term::from).to_vec());
+
+    let e = add(mul(term(Complex64::I.into()), x.clone()), num(3.0));
+    assert_eq!(e.as_polynomial("x").unwrap(), vec![Term::from(3.0), Term::from(Complex64::I)]);
+    // Other variables are only constants if they're a different polynomial's variable.
+    assert_eq!(var("y").as_polynomial("y").unwrap(), vec![Term::from(0.0), Term::from(1.0)]);
+    assert!(sub(x.clone(), x.clone()).flatten().reduce_const().as_polynomial("x").unwrap().is_empty());
+}
+
+#[test]
+fn other_expressions_are_not_polynomials() {
+    let x = var("x");
+    for e in [
+        sin(x.clone()),
+        pow(x.clone(), num(-1.0)),
+        pow(x.clone(), num(0.5)),
+        pow(x.clone(), var("y")),
+        add(x.clone(), var("y")),
+        pow(x.clone(), num(1000.0)),
+    ] {
+        assert!(e.as_polynomial("x").is_none(), "{e:?}");
+    }
+}
+
+#[test]
+fn closed_forms() {
+    same_roots(poly::solve(&[c(-6.0, 0.0), c(2.0, 0.0)]), &[c(3.0, 0.0)], 1e-12);
+    same_roots(poly::solve(&[c(1.0, 0.0), c(0.0, 0.0), c(1.0, 0.0)]), &[c(0.0, 1.0), c(0.0, -1.0)], 1e-12);
+    // Cancelling would lose the small root.
+    same_roots(poly::solve(&[c(1.0, 0.0), c(-1e8, 0.0), c(1.0, 0.0)]), &[c(1e8, 0.0), c(1e-8, 0.0)], 1e-12);
+
+    let cases: Vec<Vec<Complex64>> = vec![
+        vec![c(1.0, 0.0), c(2.0, 0.0), c(3.0, 0.0)],
+        vec![c(-1.0, 0.0), c(0.5, 2.0), c(0.5, -2.0)],
+        vec![c(2.0, 0.0), c(2.0, 0.0), c(2.0, 0.0)],
+        vec![c(1.0, 1.0), c(-3.0, 0.5), c(0.0, 2.0)],
+        vec![c(1.0, 0.0), c(-1.0, 0.0), c(2.0, 0.0), c(-2.0, 0.0)],
+        vec![c(0.0, 1.0), c(0.0, -1.0), c(0.0, 1.0), c(0.0, -1.0)],
+        vec![c(3.0, 0.0), c(3.0, 0.0), c(-1.0, 2.0), c(-1.0, -2.0)],
+        vec![c(1.0, 2.0), c(-0.5, 0.25), c(4.0, -1.0), c(0.1, 0.0)],
+    ];
+    for roots in cases {
+        // * repeated roots are only found to about the square or cube root of the precision
+        let repeated = roots.iter().enumerate().any(|(i, a)| roots[..i].contains(a));
+        same_roots(poly::solve(&from_roots(&roots)), &roots, if repeated { 1e-4 } else { 1e-9 });
+    }
+}
+
+#[test]
+fn higher_degrees() {
+    let unity: Vec<Complex64> = (0..8).map(|k| Complex64::from_polar(1.0, std::f64::consts::TAU * k as f64 / 8.0)).collect();
+    same_roots(poly::solve(&from_roots(&unity)), &unity, 1e-9);
+
+    let roots = [c(1.0, 0.0), c(2.0, 0.0), c(-3.0, 1.0), c(-3.0, -1.0), c(0.5, 0.5), c(0.0, -4.0), c(10.0, 0.0)];
+    same_roots(poly::solve(&from_roots(&roots)), &roots, 1e-9);
+}
+
+#[test]
+fn zero_roots_and_constants() {
+    same_roots(poly::solve(&[c(0.0, 0.0), c(0.0, 0.0), c(-4.0, 0.0), c(0.0, 0.0), c(1.0, 0.0)]), &[c(0.0, 0.0), c(0.0, 0.0), c(2.0, 0.0), c(-2.0, 0.0)], 1e-12);
+    assert!(poly::solve(&[c(5.0, 0.0)]).is_empty());
+    assert!(poly::solve(&[]).is_empty());
+}
+
+#[test]
+fn roots_of_expressions() {
+    let x = var("x");
+    let e = sub(pow(x.clone(), num(2.0)), num(2.0)).flatten().reduce_const();
+    let s = 2f64.sqrt();
+    same_roots(e.polynomial_roots("x").unwrap(), &[c(s, 0.0), c(-s, 0.0)], 1e-12);
+    assert!(ln(x.clone()).polynomial_roots("x").is_none());
+}
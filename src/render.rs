@@ -1,10 +1,17 @@
 
 
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
 use std::ops::RangeInclusive;
-use std::time::Instant;
+use std::rc::Rc;
+use std::time::{Instant, SystemTime};
 
-use crate::{convert, expr, parse};
-use kiss3d::nalgebra::Point3;
+use crate::{analysis, convert, implicit, mesh, sample};
+use crate::expr::{Expr, Func};
+use kiss3d::nalgebra::{Point3, Vector3};
+use kiss3d::resource::Mesh;
+use kiss3d::scene::SceneNode;
 use kiss3d::window::Window;
 use kiss3d::light::Light;
 
@@ -26,46 +33,171 @@ pub fn axis(w: &mut Window, bounds: &RangeInclusive<f64>) {
     w.draw_line(&Point3::new(0.0, 0.0, *bounds.start() as f32), &Point3::new(0.0, 0.0, *bounds.end() as f32), &Point3::new(0.0, 0.0, 1.0));
 } 
 
-pub fn render() {
-    
-    // let mut window = Window::new("Kiss3d: wavelet");
+/// Adds a mesh to the scene. kiss3d indexes vertices with `u16`, so triangles using
+/// vertices past that are left out.
+pub fn add_mesh(w: &mut Window, m: &mesh::Mesh) -> SceneNode {
+    let coords = m.vertices.iter().map(|v| Point3::new(v[0] as f32, v[1] as f32, v[2] as f32)).collect();
+    let normals = m.normals.iter().map(|n| Vector3::new(n[0] as f32, n[1] as f32, n[2] as f32)).collect();
+    let faces = m.triangles.iter()
+        .filter(|t| t.iter().all(|i| *i <= u16::MAX as u32))
+        .map(|t| Point3::new(t[0] as u16, t[1] as u16, t[2] as u16))
+        .collect();
 
-    // let f = std::fs::read_to_string("test.txt").unwrap();
-    // let p = parse::str_parse(&f);
-    // let mut ctx = convert::convert(p).simplify(false);
-    
-    
-    // //let _ = std::fs::write("output", format!("{:#00.2?}", o));
+    let mesh = Mesh::new(coords, faces, Some(normals), None, false);
+    let mut node = w.add_mesh(Rc::new(RefCell::new(mesh)), Vector3::new(1.0, 1.0, 1.0));
+    // Surfaces are seen from both sides.
+    node.enable_backface_culling(false);
+    node.set_color(0.3, 0.6, 1.0);
+    node
+}
+
+/// A small cross at a root, extremum or intersection.
+pub fn marker(w: &mut Window, m: &analysis::Marker) {
+    let (x, y, s) = (m.x as f32, m.y as f32, 0.05);
+    let colour = match m.kind {
+        analysis::Kind::Root | analysis::Kind::Intersection => Point3::new(1.0, 1.0, 0.0),
+        analysis::Kind::Maximum | analysis::Kind::Minimum => Point3::new(1.0, 0.5, 0.0),
+    };
+    w.draw_line(&Point3::new(x - s, y - s, 0.0), &Point3::new(x + s, y + s, 0.0), &colour);
+    w.draw_line(&Point3::new(x - s, y + s, 0.0), &Point3::new(x + s, y - s, 0.0), &colour);
+}
+
+/// Circles at every whole radius and lines every 30 degrees, out to the edge of `bounds`.
+pub fn polar_grid(w: &mut Window, bounds: &RangeInclusive<f64>) {
+    let colour = Point3::new(0.4, 0.4, 0.4);
+    let edge = bounds.start().abs().max(bounds.end().abs()) as f32;
+
+    for r in 1..=edge.floor() as usize {
+        let r = r as f32;
+        let circle: Vec<Point3<f32>> = (0..=64)
+            .map(|i| i as f32 * std::f32::consts::TAU / 64.0)
+            .map(|a| Point3::new(r * a.cos(), r * a.sin(), 0.0))
+            .collect();
+        for p in circle.windows(2) {
+            w.draw_line(&p[0], &p[1], &colour);
+        }
+    }
+    for i in 0..12 {
+        let a = i as f32 * std::f32::consts::PI / 6.0;
+        w.draw_line(&Point3::origin(), &Point3::new(edge * a.cos(), edge * a.sin(), 0.0), &colour);
+    }
+}
+
+/// What's drawn behind the curves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grid {
+    /// The `x`, `y` and `z` axes, see `axis`.
+    Cartesian,
+    /// Polar grid lines, see `polar_grid`.
+    Polar,
+}
+
+/// Turns an evaluated point into a 3D point, with the imaginary part going into the screen.
+pub fn to_point(p: &(f64, num_complex::Complex64)) -> (f64, f64, f64) {
+    (p.0, p.1.re, p.1.im)
+}
+
+/// Everything in a file that doesn't depend on the bounds, so it's only worked out when the
+/// file is reloaded.
+struct Scene {
+    e: Expr,
+    funcs: HashMap<String, Func>,
+    lines: Vec<Vec<(f64, f64, f64)>>,
+    meshes: Vec<mesh::Mesh>,
+    markers: Vec<analysis::Marker>,
+}
+impl Scene {
+    fn load(path: &str) -> Result<Self, Vec<String>> {
+        let ctx = convert::convert_file(path)?;
+        // The bounds move between -4 and 4, so everything that could be on screen is marked.
+        // * this also checks that `out` is defined
+        let mut markers = analysis::roots(&ctx, "out", -4.0..=4.0).map_err(|e| vec![e])?;
+        // Not everything can be differentiated, which only means there's nothing to mark.
+        match analysis::extrema(&ctx, "out", -4.0..=4.0) {
+            Ok(m) => markers.extend(m),
+            Err(e) => println!("{e}"),
+        }
+        let (e, funcs) = ctx.simplify_for_var("out");
+        let mut lines = sample::curves(&ctx)?;
+        lines.extend(implicit::curves(&ctx)?);
+        let mut meshes = mesh::surfaces(&ctx)?;
+        meshes.extend(implicit::surfaces(&ctx)?);
+        Ok(Self { e, funcs, lines, meshes, markers })
+    }
+
+    /// Nothing to draw, for when the file can't be loaded to begin with.
+    fn empty() -> Self {
+        Self { e: Expr::List(Vec::new()), funcs: HashMap::new(), lines: Vec::new(), meshes: Vec::new(), markers: Vec::new() }
+    }
+}
+
+/// When `path` was last changed, or `None` if that can't be read.
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Draws `out` and everything else in `tst/test.txt`, reloading it when it changes. With a
+/// `precision`, graphs are evaluated with that many bits (see `--precise`).
+/// 
+/// Only `tst/test.txt` itself is watched, so changes to files it includes show up the next
+/// time it's saved. When it doesn't convert, the errors are printed and the last version that
+/// did stays on screen.
+pub fn render(grid: Grid, precision: Option<u32>) {
+    let path = "tst/test.txt";
+    let mut window = Window::new("kesmos");
+
+    let mut last_modified = modified(path);
+    let mut scene = Scene::load(path).unwrap_or_else(|errs| {
+        errs.iter().for_each(|e| println!("{e}"));
+        Scene::empty()
+    });
+    let mut surfaces: Vec<SceneNode> = scene.meshes.iter().map(|m| add_mesh(&mut window, m)).collect();
     
-    // window.set_light(Light::StickToCamera);
+    window.set_light(Light::StickToCamera);
     
-    // let mut t: u64 = 0;
-    // let mut last_timestamp = Instant::now();
+    let mut t: u64 = 0;
+    let mut last_timestamp = Instant::now();
     
-    // while window.render() {
-    //     let delta_time = last_timestamp.elapsed();
+    while window.render() {
+        let delta_time = last_timestamp.elapsed();
         
-    //     last_timestamp = Instant::now();
-    //     t += 1;
-    //     let b_start = -3.0 + (0.01 * t as f64).sin() * 1.0;
-    //     let b_end = 3.0 + (0.01 * t as f64).sin() * 1.0;
-    //     let bounds = b_start..=b_end;
+        last_timestamp = Instant::now();
+        t += 1;
+        let b_start = -3.0 + (0.01 * t as f64).sin() * 1.0;
+        let b_end = 3.0 + (0.01 * t as f64).sin() * 1.0;
+        let bounds = b_start..=b_end;
         
-    //     if t % 10 == 0 {
-    //         let f = std::fs::read_to_string("test.txt").unwrap();
-    //         let p = parse::str_parse(&f);
-    //         ctx = convert::convert(p).simplify(false);
-    //     }
+        if t.is_multiple_of(10) && modified(path) != last_modified {
+            last_modified = modified(path);
+            match Scene::load(path) {
+                Ok(s) => {
+                    scene = s;
+                    for s in surfaces.iter_mut() {
+                        window.remove_node(s);
+                    }
+                    surfaces = scene.meshes.iter().map(|m| add_mesh(&mut window, m)).collect();
+                },
+                Err(errs) => errs.iter().for_each(|e| println!("{e}")),
+            }
+        }
         
-    //     let o = ctx
-    //     .evaluate_with_x("out", bounds.clone(), 500, false).unwrap()
-    //     .into_iter().map(|v| expr::f_2_c((v.0, v.1.unwrap()))).collect::<Vec<(f64, f64, f64)>>();
+        // A list-valued `out` is drawn as one curve per item, and each curve is broken at jumps and poles.
+        let curves: Vec<Vec<(f64, f64, f64)>> = scene.e.items().iter()
+            .flat_map(|c| sample::graph(c, &scene.funcs, bounds.clone(), precision))
+            .collect();
 
-    //     let calc_time = last_timestamp.elapsed();
-    //     axis(&mut window, &bounds);
-    //     line(&mut window, &o);
+        let calc_time = last_timestamp.elapsed();
+        match grid {
+            Grid::Cartesian => axis(&mut window, &bounds),
+            Grid::Polar => polar_grid(&mut window, &bounds),
+        }
+        for c in curves.iter().chain(scene.lines.iter()) {
+            line(&mut window, c);
+        }
+        for m in scene.markers.iter() {
+            marker(&mut window, m);
+        }
 
-        
-    //     println!("frame rendered in {:00.2?} (~{:00.2?} fps) - calculation time: {:00.2?}", delta_time, 1.0 / delta_time.as_secs_f64(), calc_time);
-    // }
-}
\ No newline at end of file
+        println!("frame rendered in {:00.2?} (~{:00.2?} fps) - calculation time: {:00.2?}", delta_time, 1.0 / delta_time.as_secs_f64(), calc_time);
+    }
+}
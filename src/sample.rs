@@ -0,0 +1,251 @@
+/*
+This is where simplified expressions are turned into points for the renderer.
+
+Sampling is adaptive: a segment is split in half whenever its midpoint is too far
+from the straight line between its ends, or the curve turns sharply at the midpoint,
+so bends get more points than straight parts.
+
+Lines are broken up instead of being drawn across jumps, poles (`1/x` at `0`) and
+places where the value isn't a number (`sqrt(x)` for `x < 0`). A jump is found by
+splitting a segment as far as possible: where the curve is continuous both halves
+of the last split move about as far, but across a jump one half does all of it.
+Curves that are only steep look like that too, and curves on both sides of a pole
+can move about as far, so interval arithmetic decides where it can: the line is
+broken if the curve is unbounded over the last split, and kept if it and its slope
+are bounded there. Step functions like `floor` don't need guessing, since they jump exactly where
+they change pieces, even when the jump is too small to see.
+*/
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use num_complex::Complex64;
+
+use crate::expr::{Builtin, Context, Expr, Func};
+use crate::interval::Interval;
+
+/// The number of evenly spaced segments sampling starts with.
+const INITIAL_SEGMENTS: usize = 64;
+/// How many times a segment can be split in half.
+const MAX_DEPTH: u32 = 10;
+/// How far a midpoint can be from its chord, relative to the size of the whole curve.
+const TOLERANCE: f64 = 1e-3;
+/// The sharpest turn (in radians) allowed at a midpoint. Catches fast changes of direction
+/// on short segments, like polar curves going through the origin.
+const MAX_TURN: f64 = 0.2;
+/// How much more one half of the smallest segment can move than the other before
+/// it counts as a jump, as a fraction of how far both move together.
+const JUMP: f64 = 0.5;
+
+pub type Point = (f64, f64, f64);
+/// Which piece of each step function a point is on, see `Builtin::piece`.
+pub type Piece = Vec<f64>;
+/// A parameter, the point there and its pieces.
+type Sample = (f64, Point, Piece);
+
+/// Samples every curve in `ctx` into polylines.
+pub fn curves(ctx: &Context) -> Result<Vec<Vec<Point>>, Vec<String>> {
+    let mut lines = Vec::new();
+    let mut errs = Vec::new();
+    for name in ctx.curve_names() {
+        match curve(ctx, name) {
+            Ok(l) => lines.extend(l),
+            Err(e) => errs.push(e),
+        }
+    }
+
+    if errs.is_empty() { return Ok(lines) }
+    Err(errs)
+}
+
+/// Simplifies a curve component by component and samples it into polylines.
+/// 2D curves are put on the `z = 0` plane. Only the real part of each component is used.
+pub fn curve(ctx: &Context, name: &str) -> Result<Vec<Vec<Point>>, String> {
+    let c = ctx.get_curve(name).unwrap();
+    let range = c.range(ctx)?;
+    let (components, funcs) = ctx.simplify_curve(name);
+    Ok(parametric(&components, c.param(), &funcs, range))
+}
+
+/// Adaptively samples `components` as `param` goes over `range`.
+pub fn parametric(components: &[Expr], param: &str, funcs: &HashMap<String, Func>, range: RangeInclusive<f64>) -> Vec<Vec<Point>> {
+    let steps: Vec<(Builtin, Vec<Expr>)> = components.iter().flat_map(|e| e.steps()).collect();
+    let mut vars = HashMap::new();
+    let mut f = |t: f64| {
+        vars.insert(param.to_string(), Complex64::from(t));
+        let mut p = components.iter().map(|e| e.evaluate(&vars, funcs).re);
+        ((p.next().unwrap(), p.next().unwrap(), p.next().unwrap_or(0.0)), pieces(&steps, &vars, funcs))
+    };
+    adaptive(&mut f, &mut continuous(components, param, funcs), range)
+}
+
+/// Adaptively samples `e` as a function of `x`, with the imaginary part going into the screen.
+/// With a `precision`, `e` is evaluated with that many bits before rounding to floats.
+pub fn graph(e: &Expr, funcs: &HashMap<String, Func>, bounds: RangeInclusive<f64>, precision: Option<u32>) -> Vec<Vec<Point>> {
+    let steps = e.steps();
+    let mut vars = HashMap::new();
+    let mut f = |x: f64| {
+        vars.insert("x".to_string(), Complex64::from(x));
+        let y = match precision {
+            Some(p) => e.evaluate_precise(&vars, funcs, p),
+            None => e.evaluate(&vars, funcs),
+        };
+        ((x, y.re, y.im), pieces(&steps, &vars, funcs))
+    };
+    adaptive(&mut f, &mut continuous(std::slice::from_ref(e), "x", funcs), bounds)
+}
+
+/// Whether `components` are continuous as `param` goes between two values, if interval
+/// arithmetic can tell: they aren't if one is unbounded there, and they are if they and their
+/// derivatives are bounded. Complex values and components that can't be differentiated
+/// can't be told.
+fn continuous<'a>(components: &'a [Expr], param: &'a str, funcs: &'a HashMap<String, Func>) -> impl FnMut(f64, f64) -> Option<bool> + 'a {
+    let slopes: Option<Vec<Expr>> = components.iter().map(|e| e.derivative(param)).collect();
+    move |a, b| {
+        let vars = HashMap::from([(param.to_string(), Interval::new(a.min(b), a.max(b)))]);
+        let bounded = |e: &Expr| {
+            let i = e.evaluate_interval(&vars, funcs);
+            (!i.complex).then_some(i.lo.is_finite() & i.hi.is_finite())
+        };
+        for e in components {
+            if !bounded(e)? { return Some(false) }
+        }
+        // * a slope that's unbounded could be a jump or just a corner, like `|x|` at 0
+        for e in slopes.as_ref()? {
+            if !bounded(e)? { return None }
+        }
+        Some(true)
+    }
+}
+
+/// The pieces of `steps` (from `Expr::steps`) with the variables in `vars`.
+fn pieces(steps: &[(Builtin, Vec<Expr>)], vars: &HashMap<String, Complex64>, funcs: &HashMap<String, Func>) -> Piece {
+    steps.iter().map(|(b, args)| b.piece(&args.iter().map(|a| a.evaluate(vars, funcs)).collect::<Vec<Complex64>>())).collect()
+}
+
+/// Samples `f` over `range`, splitting segments that bend too much. The line is broken
+/// wherever `f` jumps or isn't finite, and wherever it changes pieces. `continuous` says
+/// whether `f` is continuous between two parameters, if it can tell.
+pub fn adaptive(f: &mut dyn FnMut(f64) -> (Point, Piece), continuous: &mut dyn FnMut(f64, f64) -> Option<bool>, range: RangeInclusive<f64>) -> Vec<Vec<Point>> {
+    let step = (range.end() - range.start()) / INITIAL_SEGMENTS as f64;
+    let coarse: Vec<Sample> = (0..=INITIAL_SEGMENTS)
+        .map(|i| range.start() + step * i as f64)
+        .map(|t| { let (p, piece) = f(t); (t, p, piece) })
+        .collect();
+
+    // The tolerance scales with the curve so zooming out doesn't change how it looks.
+    let tol = TOLERANCE * size(coarse.iter().map(|(_, p, _)| *p));
+
+    let mut lines = Lines(vec![Vec::new()]);
+    lines.push(coarse[0].1);
+    for w in coarse.windows(2) {
+        refine(f, continuous, &w[0], &w[1], tol, 0, &mut lines);
+    }
+    lines.0.into_iter().filter(|l| l.len() > 1).collect()
+}
+
+/// Polylines being built, the last one is the one points are added to.
+struct Lines(Vec<Vec<Point>>);
+impl Lines {
+    /// Adds a point, or breaks the line if it isn't finite.
+    fn push(&mut self, p: Point) {
+        if !finite(p) { return self.split() }
+        self.0.last_mut().unwrap().push(p);
+    }
+
+    /// Starts a new line, unless the current one is still empty.
+    fn split(&mut self) {
+        if !self.0.last().unwrap().is_empty() {
+            self.0.push(Vec::new());
+        }
+    }
+}
+
+/// Adds the points after `a` up to and including `b`, splitting the segment if needed.
+fn refine(f: &mut dyn FnMut(f64) -> (Point, Piece), continuous: &mut dyn FnMut(f64, f64) -> Option<bool>, a: &Sample, b: &Sample, tol: f64, depth: u32, lines: &mut Lines) {
+    let t = (a.0 + b.0) / 2.0;
+    let (m, piece) = f(t);
+
+    let split = match (finite(a.1), finite(m), finite(b.1)) {
+        (true, true, true) => {
+            let chord = ((a.1.0 + b.1.0) / 2.0, (a.1.1 + b.1.1) / 2.0, (a.1.2 + b.1.2) / 2.0);
+            let bends = distance(m, chord) > tol;
+            let turns = distance(a.1, b.1) > tol && turn(a.1, m, b.1) > MAX_TURN;
+            bends || turns || !same(&a.2, &b.2)
+        },
+        // Nothing to draw here.
+        (false, false, false) => false,
+        // Find where the line starts or stops being a number.
+        _ => true,
+    };
+
+    let mid = (t, m, piece);
+    if split && depth < MAX_DEPTH {
+        refine(f, continuous, a, &mid, tol, depth + 1, lines);
+        refine(f, continuous, &mid, b, tol, depth + 1, lines);
+        return;
+    }
+
+    // Step functions say where they jump, so there's no need to guess.
+    if !same(&a.2, &b.2) {
+        if !same(&a.2, &mid.2) { lines.split() }
+        lines.push(m);
+        if !same(&mid.2, &b.2) { lines.split() }
+        lines.push(b.1);
+        return;
+    }
+    if split && !continuous(a.0, b.0).unwrap_or_else(|| !jumps(a.1, m, b.1, tol)) {
+        lines.split();
+        lines.push(b.1);
+        return;
+    }
+    lines.push(m);
+    lines.push(b.1);
+}
+
+/// Whether the curve jumps somewhere between `a` and `b`, given the point `m` between them.
+fn jumps(a: Point, m: Point, b: Point, tol: f64) -> bool {
+    if !(finite(a) && finite(m) && finite(b)) { return false }
+    let (l, r) = (distance(a, m), distance(m, b));
+    l.max(r) > tol && (l - r).abs() > JUMP * (l + r)
+}
+
+/// Whether two points are on the same pieces. Pieces that aren't numbers are all the same.
+fn same(a: &Piece, b: &Piece) -> bool {
+    a.iter().zip(b).all(|(a, b)| (a == b) | (a.is_nan() & b.is_nan()))
+}
+
+fn finite(p: Point) -> bool {
+    p.0.is_finite() && p.1.is_finite() && p.2.is_finite()
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt()
+}
+
+/// The angle between `a -> m` and `m -> b`.
+fn turn(a: Point, m: Point, b: Point) -> f64 {
+    let u = (m.0 - a.0, m.1 - a.1, m.2 - a.2);
+    let v = (b.0 - m.0, b.1 - m.1, b.2 - m.2);
+    let cross = (u.1 * v.2 - u.2 * v.1, u.2 * v.0 - u.0 * v.2, u.0 * v.1 - u.1 * v.0);
+    let dot = u.0 * v.0 + u.1 * v.1 + u.2 * v.2;
+    distance(cross, (0.0, 0.0, 0.0)).atan2(dot)
+}
+
+/// How big a curve is, ignoring the biggest and smallest tenth of the finite points along
+/// each axis so a pole doesn't make everything else look flat.
+fn size(points: impl Iterator<Item = Point>) -> f64 {
+    let mut axes = [Vec::new(), Vec::new(), Vec::new()];
+    for p in points.filter(|p| finite(*p)) {
+        axes[0].push(p.0);
+        axes[1].push(p.1);
+        axes[2].push(p.2);
+    }
+    axes.iter_mut().map(|a| {
+        if a.is_empty() { return 0.0 }
+        a.sort_by(f64::total_cmp);
+        let (lo, hi) = (a[a.len() / 10], a[a.len() - 1 - a.len() / 10]);
+        // * the middle 80% is scaled back up to about the size of the whole curve
+        ((hi - lo) * 1.25).powi(2)
+    }).sum::<f64>().sqrt()
+}
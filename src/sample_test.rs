@@ -0,0 +1,95 @@
+use crate::{convert, parse, sample};
+use crate::expr::Context;
+use crate::sample::Point;
+use crate::test_util::program;
+
+
+/// The polylines the curve `name` is sampled into.
+fn lines(ctx: &Context, name: &str) -> Vec<Vec<Point>> {
+    sample::curve(ctx, name).unwrap()
+}
+
+fn radius(p: Point) -> f64 {
+    (p.0 * p.0 + p.1 * p.1 + p.2 * p.2).sqrt()
+}
+
+fn close(a: Point, b: Point) -> bool {
+    radius((a.0 - b.0, a.1 - b.1, a.2 - b.2)) < 1e-9
+}
+
+
+#[test]
+fn circles_and_helices() {
+    let ctx = program("curve c(t) = (cos(t), sin(t)) for t in 0..2pi;").unwrap();
+    let circle = lines(&ctx, "c");
+    assert_eq!(circle.len(), 1);
+    let line = &circle[0];
+    assert!(line.iter().all(|p| (radius(*p) - 1.0).abs() < 1e-12 && p.2 == 0.0));
+    assert!(close(line[0], (1.0, 0.0, 0.0)) && close(*line.last().unwrap(), (1.0, 0.0, 0.0)));
+
+    // The spec's helix, with the bounds worked out from a variable.
+    let ctx = program("let turns = 2; curve h(t) = (cos(t), sin(t), t/5) for t in 0..turns*5;").unwrap();
+    let line = &lines(&ctx, "h")[0];
+    assert!(close(*line.last().unwrap(), (10f64.cos(), 10f64.sin(), 2.0)));
+    assert!(line.windows(2).all(|w| w[0].2 < w[1].2));
+}
+
+#[test]
+fn bends_get_more_points() {
+    // Straight lines aren't split, so they only get the evenly spaced points and their midpoints.
+    let ctx = program("curve s(t) = (t, 2t + 1) for t in 0..1; curve p(t) = (t, sin(20t)) for t in 0..1;").unwrap();
+    let straight = &lines(&ctx, "s")[0];
+    let wiggly = &lines(&ctx, "p")[0];
+    assert_eq!(straight.len(), 129);
+    assert!(wiggly.len() > 2 * straight.len(), "{}", wiggly.len());
+    // Each midpoint is close to the chord of its neighbours.
+    for w in wiggly.windows(3) {
+        let chord = ((w[0].1 + w[2].1) / 2.0, (w[0].0 + w[2].0) / 2.0);
+        assert!((w[1].1 - chord.0).abs() < 0.05 && (w[1].0 - chord.1).abs() < 0.05);
+    }
+}
+
+#[test]
+fn bad_curves() {
+    for (src, error) in [
+        ("curve c(t) = (t, t) for s in 0..1;", "ranges over s"),
+        ("curve c(t) = (t, vec(t, t)) for t in 0..1;", "component"),
+    ] {
+        let errs = convert::convert(parse::str_parse(src)).unwrap_err();
+        assert!(errs[0].contains(error), "{src}: {errs:?}");
+    }
+    let ctx = program("curve c(t) = (t, t) for t in 0..t;").unwrap();
+    assert!(sample::curve(&ctx, "c").unwrap_err().contains("can't depend on t"));
+}
+
+#[test]
+fn polar_curves() {
+    let ctx = program("polar c(θ) = 2 for θ in 0..2pi; polar rose(θ) = cos(2θ) for θ in 0..2pi;").unwrap();
+    let circle = &lines(&ctx, "c")[0];
+    assert!(circle.iter().all(|p| (radius(*p) - 2.0).abs() < 1e-12));
+    // Every point of the rose is `cos(2θ)` from the origin, on one side or the other.
+    let rose = &lines(&ctx, "rose")[0];
+    for p in rose {
+        assert!((radius(*p) - (2.0 * p.1.atan2(p.0)).cos().abs()).abs() < 1e-9, "{p:?}");
+    }
+    // The petals turn around quickly at the origin, so the rose gets more points than the circle.
+    assert!(rose.len() > circle.len(), "{} and {}", rose.len(), circle.len());
+}
+
+#[test]
+fn steep_curves_stay_whole() {
+    let graph = |src: &str| {
+        let (e, funcs) = program(src).unwrap().simplify_for_var("out");
+        sample::graph(&e, &funcs, -1.0..=1.0, None)
+    };
+    // Steep enough that one half of the last split does nearly all of the moving, but the
+    // slope is bounded there, so it isn't a jump.
+    for src in ["let out = atan(100000 (x - 0.01));", "let out = tanh(3000 (x + 0.3));"] {
+        assert_eq!(graph(src).len(), 1, "{src}");
+    }
+    // Poles are still broken right at the pole, even when both sides are huge.
+    let hyperbola = graph("let out = 1/(x - 0.01);");
+    assert_eq!(hyperbola.len(), 2);
+    assert!((hyperbola[0].last().unwrap().1 < -1e3) & (hyperbola[1][0].1 > 1e3), "{:?} {:?}", hyperbola[0].last(), hyperbola[1][0]);
+    assert_eq!(graph("let out = tan(x pi);").len(), 3);
+}
@@ -0,0 +1,366 @@
+/*
+This is where the special functions are worked out: the gamma function and the ones
+built on it, the error function, Bessel functions and the Riemann zeta function.
+
+They're only worked out with floats, to within about 1e-14 of the value (or of the
+largest term, for Bessel functions near their zeros). Real inputs are worked out
+along the real line where complex arithmetic would round differently. Poles, like
+`gamma(0)` or `zeta(1)`, are infinite.
+*/
+
+use std::f64::consts::PI;
+
+use num::{BigInt, BigRational, One, Zero};
+use num_complex::Complex64;
+
+/// Lanczos coefficients for `g = 7`, good to about 1e-15.
+const LANCZOS_G: f64 = 7.0;
+const LANCZOS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+
+/// `B_2k / (2k (2k-1))`, the coefficients of Stirling's series.
+const STIRLING: [f64; 8] = [
+    1.0 / 12.0, -1.0 / 360.0, 1.0 / 1260.0, -1.0 / 1680.0, 1.0 / 1188.0, -691.0 / 360_360.0, 1.0 / 156.0, -3617.0 / 122_400.0,
+];
+/// `B_2k / 2k`, the coefficients of the asymptotic series of the digamma function.
+const DIGAMMA: [f64; 7] = [1.0 / 12.0, -1.0 / 120.0, 1.0 / 252.0, -1.0 / 240.0, 1.0 / 132.0, -691.0 / 32760.0, 1.0 / 12.0];
+/// `zeta(k)` for `k = 2..=25`, for the series of `lgamma` near 1 and 2.
+const ZETA_INT: [f64; 24] = [
+    1.644_934_066_848_226_4, 1.202_056_903_159_594_2, 1.082_323_233_711_138_1, 1.036_927_755_143_37,
+    1.017_343_061_984_449_2, 1.008_349_277_381_923, 1.004_077_356_197_944_4, 1.002_008_392_826_082_1,
+    1.000_994_575_127_818, 1.000_494_188_604_119_4, 1.000_246_086_553_308, 1.000_122_713_347_578_5,
+    1.000_061_248_135_058_8, 1.000_030_588_236_307, 1.000_015_282_259_408_6, 1.000_007_637_197_637_9,
+    1.000_003_817_293_265, 1.000_001_908_212_716_5, 1.000_000_953_962_033_8, 1.000_000_476_932_986_9,
+    1.000_000_238_450_502_7, 1.000_000_119_219_926, 1.000_000_059_608_189, 1.000_000_029_803_503_4,
+];
+/// The Euler-Mascheroni constant.
+const EULER: f64 = 0.577_215_664_901_532_9;
+/// Asymptotic series are used past this, and smaller inputs are moved up to it.
+const ASYMPTOTIC: f64 = 10.0;
+
+/// Whether `z` is 0 or a negative whole number, where the gamma function has poles.
+fn is_pole(z: Complex64) -> bool {
+    (z.im == 0.0) & (z.re <= 0.0) & (z.re.fract() == 0.0)
+}
+
+/// `sin(pi*x)`, with `x` brought down to `[-1, 1]` first so whole numbers give exactly 0.
+fn sin_pi(x: f64) -> f64 {
+    let r = x - 2.0 * (x / 2.0).round();
+    if r.fract() == 0.0 { return 0.0 }
+    (PI * r).sin()
+}
+fn sin_pi_complex(z: Complex64) -> Complex64 {
+    (PI * Complex64::new(z.re - 2.0 * (z.re / 2.0).round(), z.im)).sin()
+}
+
+pub fn gamma(z: Complex64) -> Complex64 {
+    if is_pole(z) { return f64::INFINITY.into() }
+    if z.im == 0.0 { return gamma_real(z.re).into() }
+    // gamma(z) gamma(1-z) = pi / sin(pi*z)
+    if z.re < 0.5 { return PI / (sin_pi_complex(z) * gamma(1.0 - z)) }
+    let z = z - 1.0;
+    let x = LANCZOS.iter().enumerate().skip(1).fold(Complex64::from(LANCZOS[0]), |x, (i, c)| x + c / (z + i as f64));
+    let t = z + LANCZOS_G + 0.5;
+    // * split in two so `t^(z+1/2)` doesn't overflow before `e^-t` brings it back down
+    let h = t.powc((z + 0.5) / 2.0);
+    (2.0 * PI).sqrt() * h * (h * (-t).exp()) * x
+}
+
+fn gamma_real(x: f64) -> f64 {
+    if x < 0.5 { return PI / (sin_pi(x) * gamma_real(1.0 - x)) }
+    let x = x - 1.0;
+    let a = LANCZOS.iter().enumerate().skip(1).fold(LANCZOS[0], |a, (i, c)| a + c / (x + i as f64));
+    let t = x + LANCZOS_G + 0.5;
+    let h = t.powf((x + 0.5) / 2.0);
+    (2.0 * PI).sqrt() * h * (h * (-t).exp()) * a
+}
+
+/// The log of the gamma function. It's continued from the positive real line without going
+/// across it, so it isn't always `ln(gamma(z))`: on the negative real line the imaginary part
+/// is `-pi` for every pole passed, like `lgamma(-2.5) = -0.056 - 3pi*i`.
+pub fn lgamma(z: Complex64) -> Complex64 {
+    if is_pole(z) { return f64::INFINITY.into() }
+    if z.im == 0.0 {
+        let x = z.re;
+        if x >= ASYMPTOTIC { return stirling(z) }
+        // * lgamma is 0 at 1 and 2, and `ln` of `gamma` can't get close to that precisely
+        if (x - 1.0).abs() < 0.2 { return lgamma_near_one(x - 1.0).into() }
+        if (x - 2.0).abs() < 0.2 { return (lgamma_near_one(x - 2.0) + (x - 1.0).ln()).into() }
+        if x > 0.0 { return gamma_real(x).ln().into() }
+        // ln|gamma(x)| from the reflection formula, and -pi for each pole between x and 0
+        let re = PI.ln() - sin_pi(x).abs().ln() - lgamma((1.0 - x).into()).re;
+        return Complex64::new(re, -PI * (-x).ceil());
+    }
+    // lgamma(z) = lgamma(z + 1) - ln(z)
+    let (mut z, mut shift) = (z, Complex64::from(0.0));
+    while z.re < ASYMPTOTIC {
+        shift += z.ln();
+        z += 1.0;
+    }
+    stirling(z) - shift
+}
+
+/// `lgamma(1 + e) = -EULER e + zeta(2) e^2 / 2 - zeta(3) e^3 / 3 + ...`, for small `e`.
+fn lgamma_near_one(e: f64) -> f64 {
+    let series = ZETA_INT.iter().enumerate().rev().fold(0.0, |s, (i, z)| {
+        let k = (i + 2) as f64;
+        s * -e + z / k
+    });
+    e * (-EULER + e * series)
+}
+
+/// Stirling's series for `lgamma(z)`, for `re(z) >= ASYMPTOTIC`.
+fn stirling(z: Complex64) -> Complex64 {
+    let w = z.inv() * z.inv();
+    let series = STIRLING.iter().rev().fold(Complex64::from(0.0), |s, c| s * w + c) / z;
+    (z - 0.5) * z.ln() - z + 0.5 * (2.0 * PI).ln() + series
+}
+
+/// The derivative of `lgamma`, which is also `gamma'(z) / gamma(z)`.
+pub fn digamma(z: Complex64) -> Complex64 {
+    if is_pole(z) { return f64::INFINITY.into() }
+    if z.im == 0.0 {
+        let (mut x, mut shift) = (z.re, 0.0);
+        // psi(x) = psi(1 - x) - pi cot(pi x), so negative numbers aren't moved up one at a time
+        if x < 0.0 {
+            let s = sin_pi(x);
+            return (digamma((1.0 - x).into()).re - PI * sin_pi(x + 0.5) / s).into();
+        }
+        while x < ASYMPTOTIC {
+            shift += 1.0 / x;
+            x += 1.0;
+        }
+        let w = 1.0 / (x * x);
+        return (x.ln() - 0.5 / x - DIGAMMA.iter().rev().fold(0.0, |s, c| s * w + c) * w - shift).into();
+    }
+    let (mut z, mut shift) = (z, Complex64::from(0.0));
+    while z.re < ASYMPTOTIC {
+        shift += z.inv();
+        z += 1.0;
+    }
+    let w = z.inv() * z.inv();
+    z.ln() - 0.5 / z - DIGAMMA.iter().rev().fold(Complex64::from(0.0), |s, c| s * w + c) * w - shift
+}
+
+/// `gamma(a) gamma(b) / gamma(a + b)`.
+pub fn beta(a: Complex64, b: Complex64) -> Complex64 {
+    let r = gamma(a) * gamma(b) / gamma(a + b);
+    if r.is_finite() & !r.is_zero() { return r }
+    // * the gammas overflow long before the result does
+    (lgamma(a) + lgamma(b) - lgamma(a + b)).exp()
+}
+
+pub fn erf(z: Complex64) -> Complex64 {
+    // erf is odd, so only the right half plane is needed.
+    if z.re < 0.0 { return -erf(-z) }
+    if use_fraction(z) { return 1.0 - erfc_fraction(z) }
+    erf_series(z)
+}
+
+pub fn erfc(z: Complex64) -> Complex64 {
+    if z.re < 0.0 { return 2.0 - erfc(-z) }
+    if use_fraction(z) { return erfc_fraction(z) }
+    1.0 - erf_series(z)
+}
+
+/// Whether `erfc_fraction` converges quickly enough at `z`, which is in the right half plane.
+fn use_fraction(z: Complex64) -> bool {
+    (z.re >= 2.0) | ((z.re >= 0.5) & (z.norm() >= 3.0))
+}
+
+/// erf(z) as a power series, for anything not far into the right half plane.
+fn erf_series(z: Complex64) -> Complex64 {
+    let mut sum = Complex64::from(0.0);
+    // Near the real line, `e^-z^2 * sum(2^n z^(2n+1) / (2n+1)!!)` has no cancelling.
+    // Near the imaginary axis that one cancels, and the usual series doesn't.
+    if z.re >= z.im.abs() {
+        let (w, mut term) = (2.0 * z * z, z);
+        for n in 0..1000 {
+            sum += term;
+            if term.norm() <= 1e-17 * sum.norm() { break }
+            term *= w / (2 * n + 3) as f64;
+        }
+        return 2.0 / PI.sqrt() * (-z * z).exp() * sum;
+    }
+    let (w, mut term) = (-z * z, z);
+    for n in 0..10000 {
+        sum += term / (2 * n + 1) as f64;
+        if term.norm() <= 1e-17 * sum.norm() { break }
+        term *= w / (n + 1) as f64;
+    }
+    2.0 / PI.sqrt() * sum
+}
+
+/// erfc(z) from its continued fraction, for `re(z) > 0` and away from 0.
+fn erfc_fraction(z: Complex64) -> Complex64 {
+    // e^-z^2 / sqrt(pi) / (z + (1/2) / (z + 1 / (z + (3/2) / (z + ...)))), with Lentz's method
+    let tiny = Complex64::from(1e-150);
+    let (mut f, mut c, mut d) = (z, z, Complex64::from(0.0));
+    for n in 1..5000 {
+        let a = n as f64 / 2.0;
+        d = z + a * d;
+        if d.norm() < 1e-150 { d = tiny }
+        d = d.inv();
+        c = z + a / c;
+        if c.norm() < 1e-150 { c = tiny }
+        let delta = c * d;
+        f *= delta;
+        if (delta - 1.0).norm() < 1e-16 { break }
+    }
+    (-z * z).exp() / PI.sqrt() / f
+}
+
+/// The Riemann zeta function.
+pub fn zeta(s: Complex64) -> Complex64 {
+    if s == Complex64::from(1.0) { return f64::INFINITY.into() }
+    // * the trivial zeros, which the reflection would only get close to
+    if (s.im == 0.0) & (s.re < 0.0) & (s.re.rem_euclid(2.0) == 0.0) { return 0.0.into() }
+    if s.re < 0.5 {
+        // zeta(s) = 2^s pi^(s-1) sin(pi s / 2) gamma(1-s) zeta(1-s)
+        let sin = sin_pi_complex(s / 2.0);
+        return Complex64::from(2.0).powc(s) * Complex64::from(PI).powc(s - 1.0) * sin * gamma(1.0 - s) * zeta(1.0 - s);
+    }
+    // Borwein's method on the alternating series, zeta(s) = eta(s) / (1 - 2^(1-s)).
+    // The error shrinks by about 5.8 for each term, but grows with im(s).
+    let n = 50 + (s.im.abs() * 1.5).min(350.0) as usize;
+    let mut d = Vec::with_capacity(n + 1);
+    let (mut term, mut acc) = (1.0 / n as f64, 0.0);
+    for i in 0..=n {
+        acc += term;
+        d.push(n as f64 * acc);
+        term *= 4.0 * (n + i) as f64 * (n - i) as f64 / ((2 * i + 1) * (2 * i + 2)) as f64;
+    }
+    let mut sum = Complex64::from(0.0);
+    for k in 0..n {
+        let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+        sum += sign * (d[k] - d[n]) * Complex64::from((k + 1) as f64).powc(-s);
+    }
+    -sum / (d[n] * (1.0 - Complex64::from(2.0).powc(1.0 - s)))
+}
+
+/// The Bessel function of the first kind, for whole orders and real inputs. Other inputs
+/// give NaN.
+pub fn bessel_j(n: Complex64, x: Complex64) -> Complex64 {
+    let Some((n, x)) = bessel_inputs(n, x) else { return f64::NAN.into() };
+    // J_-n(x) = (-1)^n J_n(x) = J_n(-x)
+    let sign = if (n < 0) ^ (x < 0.0) { parity(n) } else { 1.0 };
+    (sign * bessel_jy(n.unsigned_abs(), x.abs()).0).into()
+}
+
+/// The Bessel function of the second kind, for whole orders and real inputs. Negative inputs
+/// give complex results, continued from above the real line.
+pub fn bessel_y(n: Complex64, x: Complex64) -> Complex64 {
+    let Some((n, x)) = bessel_inputs(n, x) else { return f64::NAN.into() };
+    let sign = if n < 0 { parity(n) } else { 1.0 };
+    let (j, y) = bessel_jy(n.unsigned_abs(), x.abs());
+    if x < 0.0 {
+        // Y_n(-x) = (-1)^n (Y_n(x) + 2i J_n(x))
+        return sign * parity(n) * Complex64::new(y, 2.0 * j);
+    }
+    (sign * y).into()
+}
+
+fn bessel_inputs(n: Complex64, x: Complex64) -> Option<(i64, f64)> {
+    if (n.im != 0.0) | (x.im != 0.0) | (n.re.fract() != 0.0) | (n.re.abs() > 1e6) { return None }
+    Some((n.re as i64, x.re))
+}
+
+fn parity(n: i64) -> f64 {
+    if n % 2 == 0 { 1.0 } else { -1.0 }
+}
+
+/// `J_n(x)` and `Y_n(x)` for `x >= 0`.
+fn bessel_jy(n: u64, x: f64) -> (f64, f64) {
+    if x == 0.0 { return (if n == 0 { 1.0 } else { 0.0 }, f64::NEG_INFINITY) }
+    if x.is_infinite() { return (0.0, 0.0) }
+    if x < 1e-8 {
+        // * the first terms of the series, which are all that's left for tiny inputs
+        let y = if n == 0 { 2.0 / PI * ((x / 2.0).ln() + EULER) } else { -gamma_real(n as f64) / PI * (2.0 / x).powi(n as i32) };
+        return ((x / 2.0).powi(n as i32) / gamma_real(n as f64 + 1.0), y);
+    }
+    if (x > 1e6) | ((x > 1e4) & ((n * n) as f64 * 10.0 < x)) { return hankel(n, x) }
+
+    // Miller's method: J_k for k below some m is worked out with the recurrence going down,
+    // which is stable that way, then scaled so J_0 + 2 J_2 + 2 J_4 + ... = 1.
+    let big = (n as f64).max(x.ceil());
+    let m = 2 * ((big + 30.0 + (50.0 * big).sqrt()) as usize / 2 + 1);
+    let mut j = vec![0.0; m + 2];
+    j[m] = 1.0;
+    for k in (1..=m).rev() {
+        j[k - 1] = 2.0 * k as f64 / x * j[k] - j[k + 1];
+        // * keeps the values that grow going down from overflowing
+        if j[k - 1].abs() > 1e250 {
+            j.iter_mut().for_each(|v| *v *= 1e-250);
+        }
+    }
+    let norm = j[0] + 2.0 * j.iter().skip(2).step_by(2).sum::<f64>();
+    j.iter_mut().for_each(|v| *v /= norm);
+    let jn = j.get(n as usize).copied().unwrap_or(0.0);
+
+    // Neumann's series for Y_0 and Y_1, then the recurrence going up, which is stable that way.
+    let l = (x / 2.0).ln() + EULER;
+    let mut y0 = l * j[0];
+    let mut y1 = l * j[1] - j[0] / x - j[1];
+    for k in 1..m / 2 {
+        let sign = parity(k as i64);
+        y0 -= 2.0 * sign * j[2 * k] / k as f64;
+        y1 -= sign * (2 * k + 1) as f64 / (k * (k + 1)) as f64 * j[2 * k + 1];
+    }
+    let (mut a, mut b) = (2.0 / PI * y0, 2.0 / PI * y1);
+    if n == 0 { return (jn, a) }
+    for k in 1..n {
+        (a, b) = (b, 2.0 * k as f64 / x * b - a);
+    }
+    (jn, b)
+}
+
+/// Hankel's asymptotic series for `J_n(x)` and `Y_n(x)`, for `x` much larger than `n^2`.
+fn hankel(n: u64, x: f64) -> (f64, f64) {
+    let mu = 4.0 * (n * n) as f64;
+    let (mut p, mut q, mut term) = (0.0, 0.0, 1.0);
+    for k in 0..60 {
+        let t = if k % 4 < 2 { term } else { -term };
+        if k % 2 == 0 { p += t } else { q += t }
+        let next = term * (mu - ((2 * k + 1) * (2 * k + 1)) as f64) / ((k + 1) as f64 * 8.0 * x);
+        if next.abs() >= term.abs() || next == 0.0 { break }
+        term = next;
+    }
+    // * cos(x - phi) written out, since the library's reduction of `x` is exact and subtracting isn't
+    let phi = (n as f64 / 2.0 + 0.25) * PI;
+    let (s, c) = x.sin_cos();
+    let (sp, cp) = phi.sin_cos();
+    let (sin, cos) = (s * cp - c * sp, c * cp + s * sp);
+    let amp = (2.0 / (PI * x)).sqrt();
+    (amp * (p * cos - q * sin), amp * (p * sin + q * cos))
+}
+
+/// Bernoulli numbers take `n^2` steps with growing fractions, so exact values that need them
+/// are only worked out below this.
+pub const BERNOULLI_LIMIT: usize = 64;
+
+/// The Bernoulli number `B_n`, with `B_1 = 1/2`.
+pub fn bernoulli(n: usize) -> BigRational {
+    // The Akiyama-Tanigawa algorithm.
+    let mut a: Vec<BigRational> = Vec::with_capacity(n + 1);
+    for m in 0..=n {
+        a.push(BigRational::new(BigInt::one(), BigInt::from(m + 1)));
+        for j in (1..=m).rev() {
+            a[j - 1] = BigRational::from_integer(BigInt::from(j)) * (&a[j - 1] - &a[j]);
+        }
+    }
+    a.into_iter().next().unwrap_or_else(BigRational::zero)
+}
+
+/// `n!`.
+pub fn factorial(n: u32) -> BigInt {
+    (1..=n).fold(BigInt::one(), |a, k| a * k)
+}
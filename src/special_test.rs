@@ -0,0 +1,159 @@
+use num_complex::Complex64;
+
+use crate::constant::Constant;
+use crate::expr::{f::*, Builtin, Expr, Term};
+use crate::special;
+use crate::test_util::{ratio, reduced};
+
+
+/// Inputs and their expected values, as `(re, im)` pairs.
+type Table = [((f64, f64), (f64, f64))];
+
+fn c((re, im): (f64, f64)) -> Complex64 {
+    Complex64::new(re, im)
+}
+
+/// Asserts that `f` is within `tol` of every value in the table, relative to the value.
+fn matches(name: &str, f: fn(Complex64) -> Complex64, table: &Table, tol: f64) {
+    for (z, want) in table {
+        let (got, want) = (f(c(*z)), c(*want));
+        assert!((got - want).norm() <= tol * want.norm(), "{name}{z:?} is {got} but should be {want}");
+    }
+}
+
+
+// The tables are from mpmath, to 30 digits and rounded.
+
+#[test]
+fn gamma_functions() {
+    matches("gamma", special::gamma, &[
+        ((0.5, 0.0), (1.772453850905516, 0.0)),
+        ((5.0, 0.0), (24.0, 0.0)),
+        ((0.1, 0.0), (9.51350769866873, 0.0)),
+        ((-0.5, 0.0), (-3.544907701811032, 0.0)),
+        ((-2.5, 0.0), (-0.9453087204829419, 0.0)),
+        ((170.5, 0.0), (5.56209241456e+305, 0.0)),
+        ((3.7, 0.0), (4.170651783796604, 0.0)),
+        ((1.0, 1.0), (0.49801566811835607, -0.15494982830181067)),
+        ((-3.2, 0.7), (-0.02678379726557048, 0.0924681979553583)),
+        ((0.3, -5.0), (-0.000648613670482922, -0.00027746302681981274)),
+    ], 1e-12);
+    matches("lgamma", special::lgamma, &[
+        ((0.5, 0.0), (0.5723649429247001, 0.0)),
+        ((3.7, 0.0), (1.428072326665388, 0.0)),
+        ((100.0, 0.0), (359.1342053695754, 0.0)),
+        ((100000.0, 0.0), (1051287.7089736569, 0.0)),
+        ((-2.5, 0.0), (-0.056243716497674054, -9.42477796076938)),
+        ((1.0, 1.0), (-0.6509231993018564, -0.3016403204675332)),
+        ((-3.2, 0.7), (-2.3406078939632624, -10.713635915626588)),
+        ((-50.5, 0.1), (-149.34495792864575, -159.82804110389512)),
+    ], 1e-13);
+    matches("digamma", special::digamma, &[
+        ((1.0, 0.0), (-0.5772156649015329, 0.0)),
+        ((0.5, 0.0), (-1.9635100260214235, 0.0)),
+        ((-3.3, 0.0), (3.620353460592126, 0.0)),
+        ((30.0, 0.0), (3.384438132685525, 0.0)),
+        ((0.3, -5.0), (1.6085668554975934, -1.6109098352332925)),
+    ], 1e-13);
+    // Poles, and lgamma being 0 where gamma is 1.
+    assert!(special::gamma(c((-3.0, 0.0))).is_infinite());
+    assert_eq!(special::lgamma(c((1.0, 0.0))), c((0.0, 0.0)));
+    assert!(special::lgamma(c((2.0 + 1e-9, 0.0))).re > 0.0);
+    let b = special::beta(c((100.0, 0.0)), c((200.0, 0.0)));
+    assert!((b.re / 3.607285449794666e-84 - 1.0).abs() < 1e-11, "{b}");
+}
+
+#[test]
+fn error_functions() {
+    matches("erf", special::erf, &[
+        ((0.5, 0.0), (0.5204998778130465, 0.0)),
+        ((2.0, 0.0), (0.9953222650189527, 0.0)),
+        ((-0.3, 0.0), (-0.3286267594591274, 0.0)),
+        ((1e-10, 0.0), (1.1283791670955126e-10, 0.0)),
+        ((3.0, 0.0), (0.9999779095030014, 0.0)),
+        ((1.0, 1.0), (1.3161512816979477, 0.19045346923783468)),
+        ((0.2, 3.0), (1405.762057754058, 676.6737879272429)),
+        ((-1.0, -4.0), (-456592.3043809454, -52731.82036767025)),
+    ], 1e-13);
+    // erfc doesn't lose the tail to `1 - erf`.
+    matches("erfc", special::erfc, &[
+        ((0.5, 0.0), (0.4795001221869535, 0.0)),
+        ((3.0, 0.0), (2.209049699858544e-05, 0.0)),
+        ((6.0, 0.0), (2.1519736712498913e-17, 0.0)),
+        ((10.0, 0.0), (2.088487583762545e-45, 0.0)),
+        ((-2.0, 0.0), (1.9953222650189528, 0.0)),
+        ((3.0, 2.0), (0.001036721143182731, 1.1546724379290603e-05)),
+        ((2.5, -2.5), (0.12363680464957867, 0.09992877379159747)),
+    ], 1e-13);
+}
+
+#[test]
+fn zeta_function() {
+    matches("zeta", special::zeta, &[
+        ((3.0, 0.0), (1.2020569031595942, 0.0)),
+        ((0.5, 0.0), (-1.4603545088095868, 0.0)),
+        ((-2.5, 0.0), (0.008516928777850331, 0.0)),
+        ((10.5, 0.0), (1.000700842641736, 0.0)),
+        ((2.0, 3.0), (0.7980219851462758, -0.1137443080529385)),
+        ((-1.5, 2.0), (0.12424726557777474, -0.015707749528273203)),
+        ((0.5, 100.0), (2.692619885681324, -0.020386029602598162)),
+    ], 1e-12);
+    assert_eq!(special::zeta(c((-4.0, 0.0))), c((0.0, 0.0)));
+    assert!(special::zeta(c((1.0, 0.0))).is_infinite());
+}
+
+#[test]
+fn bessel_functions() {
+    let j = [
+        (0, 1.0, 0.7651976865579666),
+        (1, 2.5, 0.49709410246427405),
+        (0, 10.0, -0.24593576445134835),
+        (5, 10.0, -0.23406152818679363),
+        (2, 33.3, -0.055899317905390315),
+        (20, 10.0, 1.1513369247813398e-05),
+        (-3, -4.2, 0.4343942763872008),
+        (1, 20000.0, -0.0009223097469764594),
+        (0, 3000000.0, -0.00013053162249034237),
+    ];
+    let y = [
+        (0, 1.0, 0.08825696421567696),
+        (1, 2.5, 0.1459181379667858),
+        (0, 10.0, 0.055671167283599395),
+        (5, 10.0, 0.13540304768936232),
+        (2, 33.3, -0.12659123624061006),
+        (20, 10.0, -1597.483848269626),
+        (1, 20000.0, -0.0055659979679167),
+        (0, 1e-10, -14.732516272697241),
+    ];
+    // * values near zeros are only close relative to the size of the oscillations, about sqrt(2 / (pi x))
+    let size = |x: f64, v: f64| v.abs().max((2.0 / (std::f64::consts::PI * x.abs())).sqrt());
+    for (f, table) in [(special::bessel_j as fn(Complex64, Complex64) -> Complex64, &j[..]), (special::bessel_y, &y[..])] {
+        for (n, x, want) in table {
+            let got = f((*n as f64).into(), (*x).into());
+            assert!((got.re - want).abs() <= 1e-13 * size(*x, *want) && got.im == 0.0, "order {n} at {x} is {got} but should be {want}");
+        }
+    }
+    let y = special::bessel_y(1.0.into(), (-2.0).into());
+    assert!((y - c((0.10703243154093754, -1.1534496155137468))).norm() < 1e-14, "{y}");
+    assert!(special::bessel_j(0.5.into(), 1.0.into()).is_nan());
+}
+
+#[test]
+fn exact_values() {
+    assert_eq!(reduced(*factorial(num(20.0))), *num(2432902008176640000.0));
+    assert_eq!(reduced(*gamma(num(5.0))), *num(24.0));
+    assert_eq!(reduced(*beta(num(2.0), num(3.0))), Expr::Term(ratio(1, 12)));
+    assert_eq!(reduced(*zeta(num(-1.0))), Expr::Term(ratio(-1, 12)));
+    assert!(reduced(*zeta(num(-2.0))).is_zero());
+    assert!(reduced(*erf(num(0.0))).is_zero());
+    assert_eq!(reduced(*besselj(num(0.0), num(0.0))), *num(1.0));
+    // zeta(2) = pi^2 / 6 and gamma(1/2) = sqrt(pi), which stay symbolic.
+    let pi = || term(Term::Const(Constant::Pi));
+    assert_eq!(reduced(*zeta(num(2.0))), reduced(*div(pow(pi(), num(2.0)), num(6.0))));
+    assert_eq!(reduced(*gamma(div(num(-1.0), num(2.0)))), reduced(*mul(num(-2.0), pow(pi(), div(num(1.0), num(2.0))))));
+    // Other inputs are floats, and poles are infinite.
+    assert!(matches!(reduced(*gamma(num(0.5))), Expr::Term(Term::Real(_))));
+    assert!(matches!(reduced(*gamma(num(-2.0))), Expr::Term(Term::Real(v)) if v.is_infinite()));
+    assert!(matches!(reduced(*erf(num(0.5))), Expr::Term(Term::Real(_))));
+    assert!(Builtin::Gamma.is_special());
+}
@@ -0,0 +1,38 @@
+/*
+This is where the helpers used by more than one of the `*_test` modules are.
+*/
+
+use num::BigRational;
+
+use crate::{convert, parse};
+use crate::expr::{f, Context, Expr, Term};
+
+/// The variable `s`.
+pub fn var(s: &str) -> Box<Expr> {
+    f::term(Term::Var(s.to_string()))
+}
+
+/// The exact fraction `n/d`.
+pub fn ratio(n: i64, d: i64) -> Term {
+    Term::Rational(BigRational::new(n.into(), d.into()))
+}
+
+/// The exact whole number `n`.
+pub fn int(n: i64) -> Expr {
+    Expr::Term(ratio(n, 1))
+}
+
+/// `e` with its constants folded, and nothing else simplified.
+pub fn reduced(e: Expr) -> Expr {
+    e.flatten().reduce_const()
+}
+
+/// Parses and converts a whole program.
+pub fn program(src: &str) -> Result<Context, Vec<String>> {
+    convert::convert(parse::str_parse(src))
+}
+
+/// The simplified value of `out` in `src`.
+pub fn out(src: &str) -> Expr {
+    program(src).unwrap().simplify_for_var("out").0
+}